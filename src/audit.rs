@@ -0,0 +1,500 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use actix_session::Session;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::storage::StorageError;
+
+/// Fields whose values never appear in the log
+const REDACTED_FIELDS: &[&str] = &["password_hash"];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEntity {
+    MenuItem,
+    Notice,
+    MenuPreset,
+    MenuSchedule,
+    MenuCategory,
+    Ingredient,
+    /// A diner's rating or comment
+    Feedback,
+    AdminUser,
+    /// A snapshot of the whole dataset
+    Snapshot,
+}
+
+/// Who made a change
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Actor {
+    pub user_id: Option<Uuid>,
+    pub username: String,
+}
+
+impl Actor {
+    /// The logged-in admin, or `anonymous` for requests without a session
+    pub fn from_session(session: &Session) -> Self {
+        Self {
+            user_id: session.get::<Uuid>("user_id").ok().flatten(),
+            username: session
+                .get::<String>("username")
+                .ok()
+                .flatten()
+                .unwrap_or_else(|| "anonymous".to_string()),
+        }
+    }
+
+    /// Changes the application makes on its own, e.g. at startup
+    pub fn system() -> Self {
+        Self {
+            user_id: None,
+            username: "system".to_string(),
+        }
+    }
+
+    /// Changes made through the command line maintenance commands
+    pub fn cli() -> Self {
+        Self {
+            user_id: None,
+            username: "cli".to_string(),
+        }
+    }
+}
+
+/// Value of one field before and after a change; `null` when absent
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FieldChange {
+    pub before: Value,
+    pub after: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    pub id: Uuid,
+    pub timestamp: DateTime<Utc>,
+    pub actor: Actor,
+    pub action: AuditAction,
+    pub entity: AuditEntity,
+    pub entity_id: Uuid,
+    /// Only the top-level fields that changed
+    pub changes: BTreeMap<String, FieldChange>,
+}
+
+/// Filters for [`AuditLog::query`]; every field is optional
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditQuery {
+    pub entity: Option<AuditEntity>,
+    pub entity_id: Option<Uuid>,
+    /// Username or user id of the actor
+    pub actor: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.entity.is_none_or(|entity| entry.entity == entity)
+            && self.entity_id.is_none_or(|id| entry.entity_id == id)
+            && self.actor.as_deref().is_none_or(|actor| {
+                entry.actor.username == actor
+                    || entry
+                        .actor
+                        .user_id
+                        .is_some_and(|id| id.to_string() == actor)
+            })
+            && self.from.is_none_or(|from| entry.timestamp >= from)
+            && self.to.is_none_or(|to| entry.timestamp <= to)
+    }
+}
+
+enum Sink {
+    /// JSON Lines file, only ever appended to
+    File(PathBuf),
+    Memory(Vec<AuditEntry>),
+}
+
+/// Append-only record of every change made through the admin API.
+///
+/// Kept apart from [`Storage`](crate::storage::Storage) so that restoring or
+/// replacing the data never rewrites its history.
+///
+/// Recording is best-effort. Entries are written after the change has been
+/// committed, so a full disk or unwritable log file cannot undo the change;
+/// the entry is lost and the failure is logged at error level instead. Watch
+/// for "Failed to write audit entry" if the log must be complete.
+pub struct AuditLog {
+    sink: Mutex<Sink>,
+}
+
+impl AuditLog {
+    /// Log to the JSON Lines file at `path`, creating it on the first entry
+    pub fn open(path: &str) -> Result<Self, StorageError> {
+        if let Some(parent) = Path::new(path).parent()
+            && !parent.as_os_str().is_empty()
+            && !parent.exists()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(Self {
+            sink: Mutex::new(Sink::File(PathBuf::from(path))),
+        })
+    }
+
+    /// Keep entries in memory only, for the in-memory storage backend
+    pub fn in_memory() -> Self {
+        Self {
+            sink: Mutex::new(Sink::Memory(Vec::new())),
+        }
+    }
+
+    /// Record a change to one record. `before` is `None` for creates and
+    /// `after` is `None` for deletes.
+    ///
+    /// Failures are logged rather than returned, see [`AuditLog`].
+    pub fn record<T: Serialize>(
+        &self,
+        actor: &Actor,
+        action: AuditAction,
+        entity: AuditEntity,
+        entity_id: Uuid,
+        before: Option<&T>,
+        after: Option<&T>,
+    ) {
+        let entry = match diff(before, after) {
+            Ok(changes) => AuditEntry {
+                id: Uuid::new_v4(),
+                timestamp: Utc::now(),
+                actor: actor.clone(),
+                action,
+                entity,
+                entity_id,
+                changes,
+            },
+            Err(e) => {
+                log::error!(
+                    "Failed to build audit entry for {:?} {}: {}",
+                    entity,
+                    entity_id,
+                    e
+                );
+                return;
+            }
+        };
+        if let Err(e) = self.append(entry) {
+            log::error!(
+                "Failed to write audit entry for {:?} {}: {}",
+                entity,
+                entity_id,
+                e
+            );
+        }
+    }
+
+    fn append(&self, entry: AuditEntry) -> Result<(), StorageError> {
+        let mut sink = self.sink.lock().map_err(|_| StorageError::PoisonError)?;
+        match &mut *sink {
+            Sink::File(path) => {
+                let mut line = serde_json::to_string(&entry)?;
+                line.push('\n');
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                file.write_all(line.as_bytes())?;
+                file.sync_data()?;
+            }
+            Sink::Memory(entries) => entries.push(entry),
+        }
+        Ok(())
+    }
+
+    /// Entries matching `query`, newest first
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>, StorageError> {
+        let sink = self.sink.lock().map_err(|_| StorageError::PoisonError)?;
+        let mut entries = match &*sink {
+            Sink::File(path) => read_entries(path)?,
+            Sink::Memory(entries) => entries.clone(),
+        };
+        drop(sink);
+
+        entries.retain(|entry| query.matches(entry));
+        entries.reverse();
+        if let Some(limit) = query.limit {
+            entries.truncate(limit);
+        }
+        Ok(entries)
+    }
+}
+
+fn read_entries(path: &Path) -> Result<Vec<AuditEntry>, StorageError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut entries = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // A crash mid-append can leave a torn last line; skip it instead of
+        // making the whole history unreadable
+        match serde_json::from_str(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => log::warn!(
+                "Skipping unreadable audit entry on line {} of {:?}: {}",
+                number + 1,
+                path,
+                e
+            ),
+        }
+    }
+    Ok(entries)
+}
+
+/// Top-level fields that differ between two versions of a record
+fn diff<T: Serialize>(
+    before: Option<&T>,
+    after: Option<&T>,
+) -> Result<BTreeMap<String, FieldChange>, StorageError> {
+    let before = fields(before)?;
+    let after = fields(after)?;
+
+    let mut changes = BTreeMap::new();
+    for name in before.keys().chain(after.keys()) {
+        let old = before.get(name).cloned().unwrap_or(Value::Null);
+        let new = after.get(name).cloned().unwrap_or(Value::Null);
+        if old == new || changes.contains_key(name) {
+            continue;
+        }
+        let change = if REDACTED_FIELDS.contains(&name.as_str()) {
+            FieldChange {
+                before: redact(&old),
+                after: redact(&new),
+            }
+        } else {
+            FieldChange {
+                before: old,
+                after: new,
+            }
+        };
+        changes.insert(name.clone(), change);
+    }
+    Ok(changes)
+}
+
+fn fields<T: Serialize>(
+    record: Option<&T>,
+) -> Result<serde_json::Map<String, Value>, StorageError> {
+    match record.map(serde_json::to_value).transpose()? {
+        Some(Value::Object(fields)) => Ok(fields),
+        _ => Ok(serde_json::Map::new()),
+    }
+}
+
+fn redact(value: &Value) -> Value {
+    if value.is_null() {
+        Value::Null
+    } else {
+        Value::String("[redacted]".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn actor(username: &str) -> Actor {
+        Actor {
+            user_id: Some(Uuid::new_v4()),
+            username: username.to_string(),
+        }
+    }
+
+    #[test]
+    fn diff_keeps_only_changed_fields() {
+        let before = json!({ "name": "Soup", "is_available": true, "revision": 3 });
+        let after = json!({ "name": "Soup", "is_available": false, "revision": 4 });
+
+        let changes = diff(Some(&before), Some(&after)).unwrap();
+
+        assert_eq!(
+            changes.keys().collect::<Vec<_>>(),
+            ["is_available", "revision"]
+        );
+        assert_eq!(changes["is_available"].before, json!(true));
+        assert_eq!(changes["is_available"].after, json!(false));
+    }
+
+    #[test]
+    fn diff_of_a_create_or_delete_lists_every_field() {
+        let record = json!({ "name": "Soup", "revision": 0 });
+
+        let created = diff(None, Some(&record)).unwrap();
+        assert_eq!(created["name"].before, Value::Null);
+        assert_eq!(created["name"].after, json!("Soup"));
+
+        let deleted = diff(Some(&record), None).unwrap();
+        assert_eq!(deleted["revision"].before, json!(0));
+        assert_eq!(deleted["revision"].after, Value::Null);
+    }
+
+    #[test]
+    fn diff_redacts_password_hashes() {
+        let before = json!({ "username": "admin", "password_hash": "$2b$12$old" });
+        let after = json!({ "username": "admin", "password_hash": "$2b$12$new" });
+
+        let changes = diff(Some(&before), Some(&after)).unwrap();
+        assert_eq!(changes["password_hash"].before, json!("[redacted]"));
+        assert_eq!(changes["password_hash"].after, json!("[redacted]"));
+
+        let created = diff(None, Some(&after)).unwrap();
+        assert_eq!(created["password_hash"].before, Value::Null);
+        assert_eq!(created["password_hash"].after, json!("[redacted]"));
+    }
+
+    #[test]
+    fn query_filters_and_returns_newest_first() {
+        let log = AuditLog::in_memory();
+        let alice = actor("alice");
+        let bob = actor("bob");
+        let soup = Uuid::new_v4();
+        let notice = Uuid::new_v4();
+        let record = json!({ "name": "Soup" });
+
+        log.record(
+            &alice,
+            AuditAction::Create,
+            AuditEntity::MenuItem,
+            soup,
+            None,
+            Some(&record),
+        );
+        let between = Utc::now();
+        log.record(
+            &bob,
+            AuditAction::Delete,
+            AuditEntity::MenuItem,
+            soup,
+            Some(&record),
+            None,
+        );
+        log.record(
+            &bob,
+            AuditAction::Create,
+            AuditEntity::Notice,
+            notice,
+            None,
+            Some(&record),
+        );
+
+        let actions = |query: AuditQuery| -> Vec<(AuditEntity, AuditAction)> {
+            log.query(&query)
+                .unwrap()
+                .into_iter()
+                .map(|entry| (entry.entity, entry.action))
+                .collect()
+        };
+
+        assert_eq!(
+            actions(AuditQuery::default()),
+            [
+                (AuditEntity::Notice, AuditAction::Create),
+                (AuditEntity::MenuItem, AuditAction::Delete),
+                (AuditEntity::MenuItem, AuditAction::Create),
+            ]
+        );
+        assert_eq!(
+            actions(AuditQuery {
+                entity_id: Some(soup),
+                ..Default::default()
+            }),
+            [
+                (AuditEntity::MenuItem, AuditAction::Delete),
+                (AuditEntity::MenuItem, AuditAction::Create),
+            ]
+        );
+        assert_eq!(
+            actions(AuditQuery {
+                entity: Some(AuditEntity::Notice),
+                ..Default::default()
+            }),
+            [(AuditEntity::Notice, AuditAction::Create)]
+        );
+        assert_eq!(
+            actions(AuditQuery {
+                actor: Some(alice.user_id.unwrap().to_string()),
+                ..Default::default()
+            }),
+            [(AuditEntity::MenuItem, AuditAction::Create)]
+        );
+        assert_eq!(
+            actions(AuditQuery {
+                actor: Some("bob".to_string()),
+                from: Some(between),
+                limit: Some(1),
+                ..Default::default()
+            }),
+            [(AuditEntity::Notice, AuditAction::Create)]
+        );
+        assert_eq!(
+            actions(AuditQuery {
+                to: Some(between),
+                ..Default::default()
+            }),
+            [(AuditEntity::MenuItem, AuditAction::Create)]
+        );
+    }
+
+    #[test]
+    fn file_log_round_trips_and_skips_a_torn_line() {
+        let dir = std::env::temp_dir().join(format!("audit-test-{}", Uuid::new_v4()));
+        let path = dir.join("audit.jsonl");
+        let log = AuditLog::open(path.to_str().unwrap()).unwrap();
+        let soup = Uuid::new_v4();
+
+        log.record(
+            &actor("alice"),
+            AuditAction::Update,
+            AuditEntity::MenuItem,
+            soup,
+            Some(&json!({ "is_available": true })),
+            Some(&json!({ "is_available": false })),
+        );
+        // A crash part way through the next append
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"id\": \"")
+            .unwrap();
+
+        let entries = AuditLog::open(path.to_str().unwrap())
+            .unwrap()
+            .query(&AuditQuery::default())
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].entity_id, soup);
+        assert_eq!(entries[0].actor.username, "alice");
+        assert_eq!(entries[0].changes["is_available"].after, json!(false));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -8,8 +8,9 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use uuid::Uuid;
 
+use crate::audit::{Actor, AuditAction, AuditEntity, AuditLog};
 use crate::error_handler::{AppError, ResultExt};
-use crate::storage::{AdminUser, JsonStorage, StorageError};
+use crate::storage::{AdminUser, Storage, StorageError};
 #[derive(Debug, Deserialize)]
 pub struct LoginRequest {
     pub username: String,
@@ -59,7 +60,7 @@ pub fn verify_password(password: &str, hash: &str) -> Result<bool, AppError> {
 
 /// Login handler for POST /admin/login
 pub async fn login_handler(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
     session: Session,
     login_data: web::Json<LoginRequest>,
 ) -> Result<impl Responder, AppError> {
@@ -146,7 +147,10 @@ pub async fn require_auth(session: &Session) -> Result<Uuid, AppError> {
 }
 
 /// Create a default admin user if none exists
-pub async fn create_default_admin(storage: web::Data<JsonStorage>) -> Result<(), AppError> {
+pub async fn create_default_admin(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+) -> Result<(), AppError> {
     log::debug!("create_default_admin() started");
 
     log::debug!("Getting admin users list");
@@ -168,10 +172,19 @@ pub async fn create_default_admin(storage: web::Data<JsonStorage>) -> Result<(),
         log::debug!("Adding admin user to storage on blocking thread");
         // Move the blocking storage operation to a dedicated thread
         let storage_clone = storage.clone();
-        actix_rt::task::spawn_blocking(move || storage_clone.get_ref().add_admin_user(admin_user))
+        let new_user = admin_user.clone();
+        actix_rt::task::spawn_blocking(move || storage_clone.get_ref().add_admin_user(new_user))
             .await
             .map_err(|e| AppError::Internal(e.to_string()))?
             .map_storage_err()?;
+        audit.record(
+            &Actor::system(),
+            AuditAction::Create,
+            AuditEntity::AdminUser,
+            admin_user.id,
+            None,
+            Some(&admin_user),
+        );
 
         log::info!("Default admin user created: username='admin', password='admin123'");
     } else {
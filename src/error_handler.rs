@@ -31,6 +31,14 @@ pub enum AppError {
     #[error("Not found: {0}")]
     NotFound(String),
 
+    /// Conflicts with the current state, e.g. a record that is still referenced
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    /// Stale revision errors (If-Match did not match)
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+
     /// Internal server errors
     #[error("Internal server error: {0}")]
     Internal(String),
@@ -64,6 +72,18 @@ impl AppError {
                 error_type: "NOT_FOUND".to_string(),
                 details: None,
             },
+            AppError::Conflict(msg) => ErrorResponse {
+                error: "Conflict".to_string(),
+                message: msg.clone(),
+                error_type: "CONFLICT".to_string(),
+                details: None,
+            },
+            AppError::PreconditionFailed(msg) => ErrorResponse {
+                error: "Precondition Failed".to_string(),
+                message: msg.clone(),
+                error_type: "PRECONDITION_FAILED".to_string(),
+                details: None,
+            },
             AppError::Internal(msg) => ErrorResponse {
                 error: "Internal Server Error".to_string(),
                 message: "An unexpected error occurred. Please try again later.".to_string(),
@@ -86,6 +106,8 @@ impl ResponseError for AppError {
             AppError::Auth(_) => actix_web::http::StatusCode::UNAUTHORIZED,
             AppError::Validation(_) => actix_web::http::StatusCode::BAD_REQUEST,
             AppError::NotFound(_) => actix_web::http::StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => actix_web::http::StatusCode::CONFLICT,
+            AppError::PreconditionFailed(_) => actix_web::http::StatusCode::PRECONDITION_FAILED,
             AppError::Internal(_) => actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
         };
 
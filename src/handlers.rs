@@ -1,15 +1,29 @@
-use actix_web::{HttpRequest, HttpResponse, Responder, web};
+use actix_files::NamedFile;
+use actix_multipart::Multipart;
+use actix_web::http::header::{self, ETag, EntityTag, IfMatch};
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, Responder, web};
 use chrono::Utc;
+use futures_util::TryStreamExt;
 use serde::{Deserialize, Serialize};
 use tera::Tera;
 use uuid::Uuid;
 
+use crate::audit::{Actor, AuditAction, AuditEntity, AuditLog, AuditQuery};
 use crate::auth::require_auth;
 use crate::error_handler::{AppError, ResultExt};
+use crate::i18n::Locales;
+use crate::markdown;
+use crate::rate_limit::RateLimiter;
 use crate::storage::{
-    JsonStorage, MenuItem, MenuPreset, MenuSchedule, Notice, ScheduleRecurrence, ScheduleStatus,
-    StorageError,
+    Allergen, BatchOp, Coded, Currency, Derived, DietaryTag, Feedback, ImageKind, Ingredient,
+    MAX_COMMENT_CHARS, MAX_IMAGE_BYTES, MAX_RATING, MENU_ITEM_FIELDS, MIN_RATING, MediaStore,
+    MenuCategory, MenuItem, MenuPreset, MenuSchedule, ModerationStatus, NOTICE_FIELDS, Notice,
+    NoticeSeverity, Nutrition, PortionSize, PriceTier, Prices, RatingSummary, RecipeIngredient,
+    ScheduleRecurrence, ScheduleStatus, SnapshotStore, Storage, StorageError, Translations,
+    TrendPeriod, TrendPoint, Variant, process_image, rating_trend,
 };
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr};
 
 #[derive(Debug, Serialize)]
 pub struct ApiError {
@@ -24,18 +38,24 @@ pub enum ApiErrorType {
     Validation(String),
     #[error("Not found: {0}")]
     NotFound(String),
+    /// The client has used up its request budget; holds the seconds until it may try again
+    #[error("Too many requests: try again in {0} seconds")]
+    RateLimited(u64),
 }
 
 impl From<AppError> for ApiErrorType {
     fn from(app_error: AppError) -> Self {
         match app_error {
-            AppError::Storage(msg) => ApiErrorType::Storage(StorageError::Io(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                msg,
-            ))),
+            AppError::Storage(msg) => {
+                ApiErrorType::Storage(StorageError::Io(std::io::Error::other(msg)))
+            }
             AppError::Auth(msg) => ApiErrorType::Validation(format!("Auth error: {}", msg)),
             AppError::Validation(msg) => ApiErrorType::Validation(msg),
             AppError::NotFound(msg) => ApiErrorType::NotFound(msg),
+            AppError::Conflict(msg) => ApiErrorType::Storage(StorageError::Conflict(msg)),
+            AppError::PreconditionFailed(msg) => {
+                ApiErrorType::Storage(StorageError::PreconditionFailed(msg))
+            }
             AppError::Internal(msg) => ApiErrorType::Validation(format!("Internal error: {}", msg)),
         }
     }
@@ -45,40 +65,242 @@ impl actix_web::ResponseError for ApiErrorType {
     fn error_response(&self) -> HttpResponse {
         let error_message = self.to_string();
         let status = match self {
+            ApiErrorType::Storage(StorageError::NotFound(_)) => {
+                actix_web::http::StatusCode::NOT_FOUND
+            }
+            ApiErrorType::Storage(StorageError::Conflict(_)) => {
+                actix_web::http::StatusCode::CONFLICT
+            }
+            ApiErrorType::Storage(StorageError::PreconditionFailed(_)) => {
+                actix_web::http::StatusCode::PRECONDITION_FAILED
+            }
             ApiErrorType::Storage(_) => actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
             ApiErrorType::Validation(_) => actix_web::http::StatusCode::BAD_REQUEST,
             ApiErrorType::NotFound(_) => actix_web::http::StatusCode::NOT_FOUND,
+            ApiErrorType::RateLimited(_) => actix_web::http::StatusCode::TOO_MANY_REQUESTS,
         };
 
-        HttpResponse::build(status).json(ApiError {
+        let mut response = HttpResponse::build(status);
+        if let ApiErrorType::RateLimited(seconds) = self {
+            response.insert_header((header::RETRY_AFTER, seconds.to_string()));
+        }
+        response.json(ApiError {
             error: error_message,
         })
     }
 }
 
+/// Entity tag for a record revision
+fn etag(revision: u64) -> ETag {
+    ETag(EntityTag::new_strong(revision.to_string()))
+}
+
+/// Check the request's `If-Match` header against a record's current revision.
+///
+/// Returns `None` when the client sent no precondition, otherwise the
+/// revision that matched so storage can re-check it under its write lock.
+fn check_if_match(
+    req: &HttpRequest,
+    label: &str,
+    id: Uuid,
+    current_revision: u64,
+) -> Result<Option<u64>, StorageError> {
+    if !req.headers().contains_key(header::IF_MATCH) {
+        return Ok(None);
+    }
+
+    let current = EntityTag::new_strong(current_revision.to_string());
+    let matches = match req.get_header::<IfMatch>() {
+        Some(IfMatch::Any) => true,
+        Some(IfMatch::Items(tags)) => tags.iter().any(|tag| tag.strong_eq(&current)),
+        // A header we cannot parse can never match
+        None => false,
+    };
+
+    if matches {
+        Ok(Some(current_revision))
+    } else {
+        Err(StorageError::PreconditionFailed(format!(
+            "{} with id {} has been modified; its current ETag is {}",
+            label, id, current
+        )))
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateMenuItemRequest {
     pub name: String,
-    pub category: String,
+    pub category_id: Uuid,
     pub description: String,
-    pub allergens: Vec<String>,
+    /// Required unless the item has a recipe; left out, the allergens and
+    /// tags are worked out from the recipe
+    pub allergens: Option<Vec<String>>,
+    pub may_contain: Option<Vec<String>>,
+    pub dietary_tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub recipe: Vec<RecipeIngredient>,
+    pub nutrition: Option<Nutrition>,
+    /// Price per tier in minor units of the configured currency
+    #[serde(default)]
+    pub prices: BTreeMap<String, u64>,
+    /// Portions on hand; left out, stock is not counted
+    pub stock: Option<u32>,
+    #[serde(default)]
+    pub variants: Vec<VariantRequest>,
     pub is_available: bool,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateMenuItemRequest {
     pub name: Option<String>,
-    pub category: Option<String>,
+    pub category_id: Option<Uuid>,
     pub description: Option<String>,
     pub allergens: Option<Vec<String>>,
+    pub may_contain: Option<Vec<String>>,
+    pub dietary_tags: Option<Vec<String>>,
+    /// Replaces the recipe; allergens and tags that followed the old recipe
+    /// follow the new one unless set in the same request
+    pub recipe: Option<Vec<RecipeIngredient>>,
+    /// `null` removes the nutrition facts, leaving the field out keeps them
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub nutrition: Option<Option<Nutrition>>,
+    /// Replaces every price; tiers left out no longer have one
+    pub prices: Option<BTreeMap<String, u64>>,
+    /// `null` stops counting stock, leaving the field out keeps the count
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub stock: Option<Option<u32>>,
+    /// Replaces every variant; one sent with the id of an existing variant
+    /// keeps that id, the rest are added as new
+    pub variants: Option<Vec<VariantRequest>>,
     pub is_available: Option<bool>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct VariantRequest {
+    pub id: Option<Uuid>,
+    pub name: String,
+    pub size: String,
+    /// Price per tier in minor units; tiers left out are charged the item's price
+    #[serde(default)]
+    pub prices: BTreeMap<String, u64>,
+    pub nutrition: Option<Nutrition>,
+    pub is_available: bool,
+}
+
+/// Tell a field set to `null` apart from one left out of the request
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkMenuItemUpdate {
+    pub id: Uuid,
+    #[serde(flatten)]
+    pub changes: UpdateMenuItemRequest,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkUpdateMenuItemsRequest {
+    pub updates: Vec<BulkMenuItemUpdate>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DecrementStockQuery {
+    /// Portions served; defaults to one
+    #[serde(default = "one_portion")]
+    pub portions: u32,
+}
+
+fn one_portion() -> u32 {
+    1
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteQuery {
+    /// Also remove or detach the records that still refer to this one
+    #[serde(default)]
+    pub cascade: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateMenuCategoryRequest {
+    pub name: String,
+    #[serde(default)]
+    pub icon: String,
+    /// Position on the menu; defaults to after every existing category
+    pub display_order: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateMenuCategoryRequest {
+    pub name: Option<String>,
+    pub icon: Option<String>,
+    pub display_order: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateIngredientRequest {
+    pub name: String,
+    pub allergens: Vec<String>,
+    #[serde(default)]
+    pub may_contain: Vec<String>,
+    #[serde(default)]
+    pub dietary_tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateIngredientRequest {
+    pub name: Option<String>,
+    pub allergens: Option<Vec<String>>,
+    pub may_contain: Option<Vec<String>>,
+    pub dietary_tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubmitFeedbackRequest {
+    pub rating: u8,
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FeedbackQuery {
+    pub menu_item_id: Option<Uuid>,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FeedbackStatsQuery {
+    /// Only count ratings of this item
+    pub menu_item_id: Option<Uuid>,
+    #[serde(default)]
+    pub period: TrendPeriod,
+    /// Only count ratings given on or after this time
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModerateFeedbackRequest {
+    pub status: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateNoticeRequest {
     pub title: String,
     pub content: String,
     pub is_active: bool,
+    pub publish_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub expire_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Defaults to `Info`
+    pub severity: Option<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub emergency: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -86,6 +308,19 @@ pub struct UpdateNoticeRequest {
     pub title: Option<String>,
     pub content: Option<String>,
     pub is_active: Option<bool>,
+    /// `null` removes the time, leaving the field out keeps it
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub publish_at: Option<Option<chrono::DateTime<chrono::Utc>>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub expire_at: Option<Option<chrono::DateTime<chrono::Utc>>>,
+    pub severity: Option<String>,
+    pub pinned: Option<bool>,
+    pub emergency: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreviewNoticeRequest {
+    pub content: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -93,6 +328,10 @@ pub struct CreateMenuPresetRequest {
     pub name: String,
     pub description: String,
     pub menu_item_ids: Vec<uuid::Uuid>,
+    #[serde(default)]
+    pub price_overrides: BTreeMap<Uuid, BTreeMap<String, u64>>,
+    #[serde(default)]
+    pub par_levels: BTreeMap<Uuid, u32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -100,6 +339,10 @@ pub struct UpdateMenuPresetRequest {
     pub name: Option<String>,
     pub description: Option<String>,
     pub menu_item_ids: Option<Vec<uuid::Uuid>>,
+    /// Replaces every override; items left out are charged their usual prices
+    pub price_overrides: Option<BTreeMap<Uuid, BTreeMap<String, u64>>>,
+    /// Replaces every par level; items left out keep no count when applied
+    pub par_levels: Option<BTreeMap<Uuid, u32>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -136,202 +379,2692 @@ pub struct ValidateScheduleRequest {
     pub schedule_id: Option<Uuid>, // For update validation
 }
 
-// Menu Items Handlers
+/// Parse a code or label, rejecting anything outside the fixed set
+fn parse_code<T: Coded>(field: &str, value: &str) -> Result<T, ApiErrorType> {
+    T::parse(value).ok_or_else(|| {
+        let known = T::ALL
+            .iter()
+            .map(|known| known.code())
+            .collect::<Vec<_>>()
+            .join(", ");
+        ApiErrorType::Validation(format!(
+            "Unknown {} '{}' in {}; expected one of {}",
+            T::KIND,
+            value,
+            field,
+            known
+        ))
+    })
+}
 
-pub async fn list_menu_items(
-    storage: web::Data<JsonStorage>,
-) -> Result<impl Responder, ApiErrorType> {
-    let items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
-    Ok(HttpResponse::Ok().json(items))
+/// Parse a list of codes or labels into a sorted list without duplicates
+fn parse_codes<T: Coded>(field: &str, values: &[String]) -> Result<Vec<T>, ApiErrorType> {
+    let mut parsed = values
+        .iter()
+        .map(|value| parse_code(field, value))
+        .collect::<Result<Vec<_>, _>>()?;
+    parsed.sort();
+    parsed.dedup();
+    Ok(parsed)
 }
 
-pub async fn create_menu_item(
-    storage: web::Data<JsonStorage>,
-    item_data: web::Json<CreateMenuItemRequest>,
-) -> Result<impl Responder, ApiErrorType> {
-    println!(
-        "DEBUG: create_menu_item() called with data: {:?}",
-        item_data
-    );
-    // Validate category
-    let category = match item_data.category.as_str() {
-        "Mains" => crate::storage::MenuCategory::Mains,
-        "Sides" => crate::storage::MenuCategory::Sides,
-        "Desserts" => crate::storage::MenuCategory::Desserts,
-        "Beverages" => crate::storage::MenuCategory::Beverages,
-        _ => return Err(ApiErrorType::Validation("Invalid category".to_string())),
-    };
+/// Parse prices keyed by tier code or label
+fn parse_prices(field: &str, values: &BTreeMap<String, u64>) -> Result<Prices, ApiErrorType> {
+    let mut prices = Prices::new();
+    for (tier, amount) in values {
+        let tier: PriceTier = parse_code(field, tier)?;
+        if prices.insert(tier, *amount).is_some() {
+            return Err(ApiErrorType::Validation(format!(
+                "{} lists a price for {} more than once",
+                field,
+                tier.code()
+            )));
+        }
+    }
+    Ok(prices)
+}
 
-    let new_item = MenuItem {
-        id: Uuid::new_v4(),
-        name: item_data.name.clone(),
-        category,
-        description: item_data.description.clone(),
-        allergens: item_data.allergens.clone(),
-        is_available: item_data.is_available,
-    };
+/// Parse a preset's price overrides, which may only cover the preset's own items
+fn parse_price_overrides(
+    menu_item_ids: &[Uuid],
+    values: &BTreeMap<Uuid, BTreeMap<String, u64>>,
+) -> Result<BTreeMap<Uuid, Prices>, ApiErrorType> {
+    let mut overrides = BTreeMap::new();
+    for (item_id, prices) in values {
+        if !menu_item_ids.contains(item_id) {
+            return Err(ApiErrorType::Validation(format!(
+                "price_overrides lists menu item {}, which is not in the preset",
+                item_id
+            )));
+        }
+        let prices = parse_prices("price_overrides", prices)?;
+        if !prices.is_empty() {
+            overrides.insert(*item_id, prices);
+        }
+    }
+    Ok(overrides)
+}
 
-    println!("DEBUG: About to add menu item to storage: {:?}", new_item);
-    storage
-        .add_menu_item(new_item.clone())
-        .map_err(ApiErrorType::from)?;
-    println!("DEBUG: Menu item added to storage successfully");
+/// Check a preset's par levels, which may only cover the preset's own items
+fn check_par_levels(
+    menu_item_ids: &[Uuid],
+    par_levels: &BTreeMap<Uuid, u32>,
+) -> Result<(), ApiErrorType> {
+    match par_levels
+        .keys()
+        .find(|item_id| !menu_item_ids.contains(item_id))
+    {
+        Some(item_id) => Err(ApiErrorType::Validation(format!(
+            "par_levels lists menu item {}, which is not in the preset",
+            item_id
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Longest icon accepted, in characters; enough for an emoji sequence
+const MAX_ICON_CHARS: usize = 16;
 
-    Ok(HttpResponse::Created().json(new_item))
+/// A menu item can only be filed under a category that exists
+fn check_category(categories: &[MenuCategory], category_id: Uuid) -> Result<(), ApiErrorType> {
+    if categories.iter().any(|category| category.id == category_id) {
+        Ok(())
+    } else {
+        Err(ApiErrorType::Validation(format!(
+            "Menu category with id {} not found",
+            category_id
+        )))
+    }
 }
 
-pub async fn update_menu_item(
-    storage: web::Data<JsonStorage>,
-    path: web::Path<Uuid>,
-    update_data: web::Json<UpdateMenuItemRequest>,
-) -> Result<impl Responder, ApiErrorType> {
-    let item_id = path.into_inner();
+/// Category names must be present and unique, ignoring case, so the menu
+/// never shows two sections with the same heading
+fn validate_category(
+    categories: &[MenuCategory],
+    category: &MenuCategory,
+) -> Result<(), ApiErrorType> {
+    if category.name.is_empty() {
+        return Err(ApiErrorType::Validation(
+            "Category name cannot be empty".to_string(),
+        ));
+    }
+    if category.icon.chars().count() > MAX_ICON_CHARS {
+        return Err(ApiErrorType::Validation(format!(
+            "Category icon cannot be longer than {} characters",
+            MAX_ICON_CHARS
+        )));
+    }
+    if let Some(existing) = categories.iter().find(|existing| {
+        existing.id != category.id && existing.name.to_lowercase() == category.name.to_lowercase()
+    }) {
+        return Err(ApiErrorType::Validation(format!(
+            "A category named '{}' already exists ({})",
+            existing.name, existing.id
+        )));
+    }
+    Ok(())
+}
 
-    // Get existing item
-    let items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
-    let existing_item = items
+/// An allergen is either contained or a trace warning, never both
+fn check_allergen_overlap(
+    contains: &[Allergen],
+    may_contain: &[Allergen],
+) -> Result<(), ApiErrorType> {
+    match may_contain
         .iter()
-        .find(|item| item.id == item_id)
-        .ok_or_else(|| {
-            ApiErrorType::NotFound(format!("Menu item with id {} not found", item_id))
-        })?;
+        .find(|allergen| contains.contains(allergen))
+    {
+        Some(allergen) => Err(ApiErrorType::Validation(format!(
+            "{} is listed in both allergens and may_contain",
+            allergen.code()
+        ))),
+        None => Ok(()),
+    }
+}
 
-    // Validate category if provided
-    let category = if let Some(category_str) = &update_data.category {
-        match category_str.as_str() {
-            "Mains" => crate::storage::MenuCategory::Mains,
-            "Sides" => crate::storage::MenuCategory::Sides,
-            "Desserts" => crate::storage::MenuCategory::Desserts,
-            "Beverages" => crate::storage::MenuCategory::Beverages,
-            _ => return Err(ApiErrorType::Validation("Invalid category".to_string())),
+/// Refuse tags the declared allergens contradict, e.g. a vegan item with milk
+fn check_dietary_tags(tags: &[DietaryTag], allergens: &[Allergen]) -> Result<(), ApiErrorType> {
+    for tag in tags {
+        if let Some(allergen) = tag
+            .excluded_allergens()
+            .iter()
+            .find(|allergen| allergens.contains(allergen))
+        {
+            return Err(ApiErrorType::Validation(format!(
+                "A {} item cannot contain {}",
+                tag.code(),
+                allergen.code()
+            )));
         }
-    } else {
-        existing_item.category.clone()
-    };
-
-    let updated_item = MenuItem {
-        id: item_id,
-        name: update_data
-            .name
-            .clone()
-            .unwrap_or_else(|| existing_item.name.clone()),
-        category,
-        description: update_data
-            .description
-            .clone()
-            .unwrap_or_else(|| existing_item.description.clone()),
-        allergens: update_data
-            .allergens
-            .clone()
-            .unwrap_or_else(|| existing_item.allergens.clone()),
-        is_available: update_data
-            .is_available
-            .unwrap_or(existing_item.is_available),
-    };
+    }
+    Ok(())
+}
 
-    storage
-        .update_menu_item(item_id, updated_item.clone())
-        .map_err(ApiErrorType::from)?;
+/// Nutrition facts must be physically possible for the stated serving
+fn validate_nutrition(nutrition: &Nutrition) -> Result<(), ApiErrorType> {
+    let fields = [
+        ("serving_size_g", nutrition.serving_size_g),
+        ("energy_kcal", nutrition.energy_kcal),
+        ("fat_g", nutrition.fat_g),
+        ("saturates_g", nutrition.saturates_g),
+        ("carbohydrate_g", nutrition.carbohydrate_g),
+        ("sugars_g", nutrition.sugars_g),
+        ("protein_g", nutrition.protein_g),
+        ("salt_g", nutrition.salt_g),
+    ];
+    if let Some((name, _)) = fields
+        .iter()
+        .find(|(_, value)| !value.is_finite() || *value < 0.0)
+    {
+        return Err(ApiErrorType::Validation(format!(
+            "nutrition.{} must be a non-negative number",
+            name
+        )));
+    }
+    if nutrition.serving_size_g <= 0.0 {
+        return Err(ApiErrorType::Validation(
+            "nutrition.serving_size_g must be greater than zero".to_string(),
+        ));
+    }
+    if nutrition.saturates_g > nutrition.fat_g {
+        return Err(ApiErrorType::Validation(
+            "nutrition.saturates_g cannot exceed nutrition.fat_g".to_string(),
+        ));
+    }
+    if nutrition.sugars_g > nutrition.carbohydrate_g {
+        return Err(ApiErrorType::Validation(
+            "nutrition.sugars_g cannot exceed nutrition.carbohydrate_g".to_string(),
+        ));
+    }
+    let mass = nutrition.fat_g + nutrition.carbohydrate_g + nutrition.protein_g + nutrition.salt_g;
+    if mass > nutrition.serving_size_g {
+        return Err(ApiErrorType::Validation(format!(
+            "nutrition facts add up to {} g, more than the {} g serving",
+            mass, nutrition.serving_size_g
+        )));
+    }
+    Ok(())
+}
 
-    Ok(HttpResponse::Ok().json(updated_item))
+/// Check and build an item's variants. Ids sent must be of variants the item
+/// already has, and no two variants may share a name.
+fn parse_variants(
+    existing: &[Variant],
+    values: &[VariantRequest],
+) -> Result<Vec<Variant>, ApiErrorType> {
+    let mut variants: Vec<Variant> = Vec::with_capacity(values.len());
+    for (index, value) in values.iter().enumerate() {
+        let field = format!("variants[{}]", index);
+        let name = value.name.trim();
+        if name.is_empty() {
+            return Err(ApiErrorType::Validation(format!(
+                "{}.name cannot be empty",
+                field
+            )));
+        }
+        if variants
+            .iter()
+            .any(|variant| variant.name.eq_ignore_ascii_case(name))
+        {
+            return Err(ApiErrorType::Validation(format!(
+                "More than one variant is named '{}'",
+                name
+            )));
+        }
+        let id = match value.id {
+            Some(id) if !existing.iter().any(|variant| variant.id == id) => {
+                return Err(ApiErrorType::Validation(format!(
+                    "{}.id {} is not a variant of this item",
+                    field, id
+                )));
+            }
+            Some(id) if variants.iter().any(|variant| variant.id == id) => {
+                return Err(ApiErrorType::Validation(format!(
+                    "Variant {} is listed more than once",
+                    id
+                )));
+            }
+            Some(id) => id,
+            None => Uuid::new_v4(),
+        };
+        if let Some(nutrition) = &value.nutrition {
+            validate_nutrition(nutrition)?;
+        }
+        variants.push(Variant {
+            id,
+            name: name.to_string(),
+            size: parse_code(&format!("{}.size", field), &value.size)?,
+            prices: parse_prices(&format!("{}.prices", field), &value.prices)?,
+            nutrition: value.nutrition.clone(),
+            is_available: value.is_available,
+        });
+    }
+    // Stable, so variants of the same size keep the order they were sent in
+    variants.sort_by_key(|variant| variant.size);
+    Ok(variants)
 }
 
-pub async fn delete_menu_item(
-    storage: web::Data<JsonStorage>,
-    path: web::Path<Uuid>,
-) -> Result<impl Responder, ApiErrorType> {
-    let item_id = path.into_inner();
+/// Vegan food is vegetarian too, so tag it as such
+fn with_implied_tags(mut tags: Vec<DietaryTag>) -> Vec<DietaryTag> {
+    if tags.contains(&DietaryTag::Vegan) && !tags.contains(&DietaryTag::Vegetarian) {
+        tags.push(DietaryTag::Vegetarian);
+        tags.sort();
+    }
+    tags
+}
 
-    storage
-        .delete_menu_item(item_id)
-        .map_err(ApiErrorType::from)?;
+/// Ingredient names must be present and unique, ignoring case, and the
+/// allergens and diets must not contradict each other
+fn validate_ingredient(
+    ingredients: &[Ingredient],
+    ingredient: &Ingredient,
+) -> Result<(), ApiErrorType> {
+    if ingredient.name.is_empty() {
+        return Err(ApiErrorType::Validation(
+            "Ingredient name cannot be empty".to_string(),
+        ));
+    }
+    if let Some(existing) = ingredients.iter().find(|existing| {
+        existing.id != ingredient.id
+            && existing.name.to_lowercase() == ingredient.name.to_lowercase()
+    }) {
+        return Err(ApiErrorType::Validation(format!(
+            "An ingredient named '{}' already exists ({})",
+            existing.name, existing.id
+        )));
+    }
+    check_allergen_overlap(&ingredient.allergens, &ingredient.may_contain)?;
+    check_dietary_tags(&ingredient.dietary_tags, &ingredient.allergens)
+}
 
-    Ok(HttpResponse::NoContent())
+/// Every line of a recipe names a different, existing ingredient and a
+/// positive quantity
+fn check_recipe(
+    ingredients: &[Ingredient],
+    recipe: &[RecipeIngredient],
+) -> Result<(), ApiErrorType> {
+    for (index, line) in recipe.iter().enumerate() {
+        if !line.quantity.is_finite() || line.quantity <= 0.0 {
+            return Err(ApiErrorType::Validation(format!(
+                "recipe[{}].quantity must be greater than zero",
+                index
+            )));
+        }
+        if !ingredients
+            .iter()
+            .any(|ingredient| ingredient.id == line.ingredient_id)
+        {
+            return Err(ApiErrorType::Validation(format!(
+                "Ingredient with id {} not found",
+                line.ingredient_id
+            )));
+        }
+        if recipe[..index]
+            .iter()
+            .any(|earlier| earlier.ingredient_id == line.ingredient_id)
+        {
+            return Err(ApiErrorType::Validation(format!(
+                "Ingredient {} is listed more than once in the recipe",
+                line.ingredient_id
+            )));
+        }
+    }
+    Ok(())
 }
 
-// Notices Handlers
+/// Value for a field an update leaves out: it keeps following the recipe if
+/// it did before, or if the item had no recipe until now, and otherwise stays
+/// as it was set by hand
+fn carried_over<T: Clone + PartialEq>(
+    current: &[T],
+    old: Option<&[T]>,
+    new: Option<&[T]>,
+) -> Vec<T> {
+    match new {
+        Some(new) if old.is_none_or(|old| old == current) => new.to_vec(),
+        _ => current.to_vec(),
+    }
+}
 
-pub async fn list_notices(storage: web::Data<JsonStorage>) -> Result<impl Responder, ApiErrorType> {
-    let notices = storage.get_notices().map_err(ApiErrorType::Storage)?;
-    Ok(HttpResponse::Ok().json(notices))
+/// A saved menu item, with the ways its hand-set allergens or tags disagree
+/// with its recipe
+#[derive(Debug, Serialize)]
+pub struct MenuItemResponse {
+    #[serde(flatten)]
+    pub item: MenuItem,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
 }
 
-pub async fn create_notice(
-    storage: web::Data<JsonStorage>,
-    notice_data: web::Json<CreateNoticeRequest>,
-) -> Result<impl Responder, ApiErrorType> {
-    use chrono::Utc;
+impl MenuItemResponse {
+    fn new(item: MenuItem, ingredients: &[Ingredient]) -> Self {
+        let warnings = item.recipe_mismatches(ingredients);
+        if !warnings.is_empty() {
+            log::warn!(
+                "Menu item '{}' ({}) disagrees with its recipe: {}",
+                item.name,
+                item.id,
+                warnings.join("; ")
+            );
+        }
+        Self { item, warnings }
+    }
+}
 
-    let new_notice = Notice {
-        id: Uuid::new_v4(),
-        title: notice_data.title.clone(),
-        content: notice_data.content.clone(),
-        is_active: notice_data.is_active,
-        created_at: Utc::now(),
-        updated_at: Utc::now(),
-    };
+/// One price as shown to diners
+#[derive(Debug, Serialize)]
+pub struct PriceLine {
+    pub tier: &'static str,
+    pub price: String,
+}
 
-    storage
-        .add_notice(new_notice.clone())
-        .map_err(ApiErrorType::from)?;
+/// An item's current prices, formatted in the configured currency
+fn price_lines(item: &MenuItem, currency: &Currency) -> Vec<PriceLine> {
+    item.current_prices()
+        .into_iter()
+        .map(|(tier, amount)| PriceLine {
+            tier: tier.label(),
+            price: currency.format(amount),
+        })
+        .collect()
+}
 
-    Ok(HttpResponse::Created().json(new_notice))
+/// One of an item's variants as shown to diners
+#[derive(Debug, Serialize)]
+pub struct VariantLine {
+    pub name: String,
+    pub size: &'static str,
+    pub prices: Vec<PriceLine>,
+    /// Per serving, from the variant's nutrition facts or else the item's
+    pub serving_size_g: Option<f64>,
+    pub energy_kcal: Option<f64>,
+    pub is_available: bool,
 }
 
-pub async fn update_notice(
-    storage: web::Data<JsonStorage>,
+/// An item's variants with their prices formatted in the configured currency
+fn variant_lines(item: &MenuItem, currency: &Currency) -> Vec<VariantLine> {
+    item.variants
+        .iter()
+        .map(|variant| {
+            let nutrition = variant.nutrition.as_ref().or(item.nutrition.as_ref());
+            VariantLine {
+                name: variant.name.clone(),
+                size: variant.size.label(),
+                prices: item
+                    .variant_prices(variant)
+                    .into_iter()
+                    .map(|(tier, amount)| PriceLine {
+                        tier: tier.label(),
+                        price: currency.format(amount),
+                    })
+                    .collect(),
+                serving_size_g: nutrition.map(|nutrition| nutrition.serving_size_g),
+                energy_kcal: nutrition.map(|nutrition| nutrition.energy_kcal),
+                is_available: variant.is_available,
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct CodeInfo {
+    pub code: &'static str,
+    pub label: &'static str,
+}
+
+fn code_infos<T: Coded>() -> Vec<CodeInfo> {
+    T::ALL
+        .iter()
+        .map(|value| CodeInfo {
+            code: value.code(),
+            label: value.label(),
+        })
+        .collect()
+}
+
+fn labels<T: Coded>(
+    value: &tera::Value,
+    _args: &std::collections::HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let codes: Vec<String> = tera::from_value(value.clone())?;
+    let labels = codes
+        .iter()
+        .map(|code| T::parse(code).map_or(code.as_str(), |value| value.label()))
+        .collect::<Vec<_>>();
+    Ok(tera::to_value(labels)?)
+}
+
+/// Tera filter turning a list of allergen codes into the labels shown to diners
+pub fn allergen_labels_filter(
+    value: &tera::Value,
+    args: &std::collections::HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    labels::<Allergen>(value, args)
+}
+
+/// Tera filter turning a list of dietary tag codes into the labels shown to diners
+pub fn dietary_labels_filter(
+    value: &tera::Value,
+    args: &std::collections::HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    labels::<DietaryTag>(value, args)
+}
+
+/// Diner-facing filter for menu items, read from the query string.
+///
+/// Each parameter takes codes or labels, either repeated (as HTML forms send
+/// them) or comma-separated: `?tags=Vegan&exclude_allergens=Milk,Sesame`.
+#[derive(Debug, Default, Serialize)]
+pub struct MenuFilter {
+    /// Items must carry every one of these tags
+    pub tags: Vec<DietaryTag>,
+    /// Items must carry none of these tags
+    pub exclude_tags: Vec<DietaryTag>,
+    /// Items must contain every one of these allergens
+    pub allergens: Vec<Allergen>,
+    /// Items must neither contain nor may contain any of these allergens
+    pub exclude_allergens: Vec<Allergen>,
+    /// Least energy per serving in kcal; items without nutrition facts never match
+    pub min_calories: Option<f64>,
+    /// Most energy per serving in kcal; items without nutrition facts never match
+    pub max_calories: Option<f64>,
+}
+
+impl MenuFilter {
+    pub fn from_query(query: &str) -> Result<Self, ApiErrorType> {
+        let mut values: std::collections::HashMap<String, Vec<String>> =
+            std::collections::HashMap::new();
+        for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+            values.entry(key.into_owned()).or_default().extend(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|value| !value.is_empty())
+                    .map(str::to_string),
+            );
+        }
+        let field = |name: &str| values.get(name).cloned().unwrap_or_default();
+        let calories = |name: &str| -> Result<Option<f64>, ApiErrorType> {
+            match field(name).last() {
+                None => Ok(None),
+                Some(value) => match value.parse::<f64>() {
+                    Ok(kcal) if kcal.is_finite() && kcal >= 0.0 => Ok(Some(kcal)),
+                    _ => Err(ApiErrorType::Validation(format!(
+                        "{} must be a non-negative number of kcal, got '{}'",
+                        name, value
+                    ))),
+                },
+            }
+        };
+
+        let filter = Self {
+            tags: parse_codes("tags", &field("tags"))?,
+            exclude_tags: parse_codes("exclude_tags", &field("exclude_tags"))?,
+            allergens: parse_codes("allergens", &field("allergens"))?,
+            exclude_allergens: parse_codes("exclude_allergens", &field("exclude_allergens"))?,
+            min_calories: calories("min_calories")?,
+            max_calories: calories("max_calories")?,
+        };
+        if let (Some(min), Some(max)) = (filter.min_calories, filter.max_calories)
+            && min > max
+        {
+            return Err(ApiErrorType::Validation(format!(
+                "min_calories ({}) is greater than max_calories ({})",
+                min, max
+            )));
+        }
+        Ok(filter)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+            && self.exclude_tags.is_empty()
+            && self.allergens.is_empty()
+            && self.exclude_allergens.is_empty()
+            && self.min_calories.is_none()
+            && self.max_calories.is_none()
+    }
+
+    pub fn matches(&self, item: &MenuItem) -> bool {
+        self.tags.iter().all(|tag| item.dietary_tags.contains(tag))
+            && !self
+                .exclude_tags
+                .iter()
+                .any(|tag| item.dietary_tags.contains(tag))
+            && self
+                .allergens
+                .iter()
+                .all(|allergen| item.allergens.contains(allergen))
+            && !self.exclude_allergens.iter().any(|allergen| {
+                item.allergens.contains(allergen) || item.may_contain.contains(allergen)
+            })
+            && self.matches_calories(item)
+    }
+
+    fn matches_calories(&self, item: &MenuItem) -> bool {
+        if self.min_calories.is_none() && self.max_calories.is_none() {
+            return true;
+        }
+        let Some(nutrition) = &item.nutrition else {
+            return false;
+        };
+        self.min_calories
+            .is_none_or(|min| nutrition.energy_kcal >= min)
+            && self
+                .max_calories
+                .is_none_or(|max| nutrition.energy_kcal <= max)
+    }
+}
+
+// Menu Items Handlers
+
+pub async fn list_menu_items(
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
+    locales: web::Data<Locales>,
+) -> Result<impl Responder, ApiErrorType> {
+    let filter = MenuFilter::from_query(req.query_string())?;
+    let mut items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
+    items.retain(|item| filter.matches(item));
+    // Only an explicit ?lang= localizes, so editors always get the text they saved
+    if let Some(locale) = locales.requested(&req) {
+        items = items
+            .into_iter()
+            .map(|item| item.localized(locale))
+            .collect();
+    }
+    Ok(HttpResponse::Ok().json(items))
+}
+
+pub async fn create_menu_item(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    item_data: web::Json<CreateMenuItemRequest>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    println!(
+        "DEBUG: create_menu_item() called with data: {:?}",
+        item_data
+    );
+    let categories = storage
+        .get_menu_categories()
+        .map_err(ApiErrorType::Storage)?;
+    check_category(&categories, item_data.category_id)?;
+    let ingredients = storage.get_ingredients().map_err(ApiErrorType::Storage)?;
+    check_recipe(&ingredients, &item_data.recipe)?;
+    let derived = Derived::from_recipe(&item_data.recipe, &ingredients);
+    let allergens = match (&item_data.allergens, &derived) {
+        (Some(values), _) => parse_codes("allergens", values)?,
+        (None, Some(derived)) => derived.allergens.clone(),
+        (None, None) => {
+            return Err(ApiErrorType::Validation(
+                "allergens are required for an item without a recipe".to_string(),
+            ));
+        }
+    };
+    let may_contain = match (&item_data.may_contain, &derived) {
+        (Some(values), _) => parse_codes("may_contain", values)?,
+        (None, Some(derived)) => derived.may_contain.clone(),
+        (None, None) => Vec::new(),
+    };
+    check_allergen_overlap(&allergens, &may_contain)?;
+    let dietary_tags = match (&item_data.dietary_tags, &derived) {
+        (Some(values), _) => with_implied_tags(parse_codes("dietary_tags", values)?),
+        (None, Some(derived)) => derived.dietary_tags.clone(),
+        (None, None) => Vec::new(),
+    };
+    check_dietary_tags(&dietary_tags, &allergens)?;
+    if let Some(nutrition) = &item_data.nutrition {
+        validate_nutrition(nutrition)?;
+    }
+    let prices = parse_prices("prices", &item_data.prices)?;
+    let variants = parse_variants(&[], &item_data.variants)?;
+
+    let new_item = MenuItem {
+        id: Uuid::new_v4(),
+        name: item_data.name.clone(),
+        category_id: item_data.category_id,
+        description: item_data.description.clone(),
+        translations: Translations::new(),
+        allergens,
+        may_contain,
+        unmapped_allergens: Vec::new(),
+        dietary_tags,
+        recipe: item_data.recipe.clone(),
+        nutrition: item_data.nutrition.clone(),
+        prices,
+        preset_prices: Prices::new(),
+        image: None,
+        stock: item_data.stock,
+        variants,
+        is_available: item_data.is_available,
+        revision: 0,
+        trashed_at: None,
+    };
+
+    println!("DEBUG: About to add menu item to storage: {:?}", new_item);
+    storage
+        .add_menu_item(new_item.clone())
+        .map_err(ApiErrorType::from)?;
+    println!("DEBUG: Menu item added to storage successfully");
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Create,
+        AuditEntity::MenuItem,
+        new_item.id,
+        None,
+        Some(&new_item),
+    );
+
+    Ok(HttpResponse::Created()
+        .insert_header(etag(new_item.revision))
+        .json(MenuItemResponse::new(new_item, &ingredients)))
+}
+
+/// Merge the fields of an update request into an existing menu item
+fn apply_menu_item_update(
+    existing_item: &MenuItem,
+    update_data: &UpdateMenuItemRequest,
+    categories: &[MenuCategory],
+    ingredients: &[Ingredient],
+) -> Result<MenuItem, ApiErrorType> {
+    // Validate category if provided
+    let category_id = match update_data.category_id {
+        Some(category_id) => {
+            check_category(categories, category_id)?;
+            category_id
+        }
+        None => existing_item.category_id,
+    };
+
+    let recipe = match &update_data.recipe {
+        Some(recipe) => {
+            check_recipe(ingredients, recipe)?;
+            recipe.clone()
+        }
+        None => existing_item.recipe.clone(),
+    };
+    let old = existing_item.derived(ingredients);
+    let new = Derived::from_recipe(&recipe, ingredients);
+    let allergens = match &update_data.allergens {
+        Some(values) => parse_codes("allergens", values)?,
+        None => carried_over(
+            &existing_item.allergens,
+            old.as_ref().map(|old| old.allergens.as_slice()),
+            new.as_ref().map(|new| new.allergens.as_slice()),
+        ),
+    };
+    let may_contain = match &update_data.may_contain {
+        Some(values) => parse_codes("may_contain", values)?,
+        None => carried_over(
+            &existing_item.may_contain,
+            old.as_ref().map(|old| old.may_contain.as_slice()),
+            new.as_ref().map(|new| new.may_contain.as_slice()),
+        ),
+    };
+    check_allergen_overlap(&allergens, &may_contain)?;
+    let dietary_tags = match &update_data.dietary_tags {
+        Some(values) => with_implied_tags(parse_codes("dietary_tags", values)?),
+        None => carried_over(
+            &existing_item.dietary_tags,
+            old.as_ref().map(|old| old.dietary_tags.as_slice()),
+            new.as_ref().map(|new| new.dietary_tags.as_slice()),
+        ),
+    };
+    check_dietary_tags(&dietary_tags, &allergens)?;
+    let nutrition = match &update_data.nutrition {
+        Some(nutrition) => nutrition.clone(),
+        None => existing_item.nutrition.clone(),
+    };
+    if let Some(nutrition) = &nutrition {
+        validate_nutrition(nutrition)?;
+    }
+    let prices = match &update_data.prices {
+        Some(values) => parse_prices("prices", values)?,
+        None => existing_item.prices.clone(),
+    };
+    let variants = match &update_data.variants {
+        Some(values) => parse_variants(&existing_item.variants, values)?,
+        None => existing_item.variants.clone(),
+    };
+    // Setting the allergens means someone has reviewed them, which settles
+    // anything left over from the free-text migration
+    let unmapped_allergens = if update_data.allergens.is_some() {
+        Vec::new()
+    } else {
+        existing_item.unmapped_allergens.clone()
+    };
+
+    Ok(MenuItem {
+        id: existing_item.id,
+        name: update_data
+            .name
+            .clone()
+            .unwrap_or_else(|| existing_item.name.clone()),
+        category_id,
+        description: update_data
+            .description
+            .clone()
+            .unwrap_or_else(|| existing_item.description.clone()),
+        translations: existing_item.translations.clone(),
+        allergens,
+        may_contain,
+        unmapped_allergens,
+        dietary_tags,
+        recipe,
+        nutrition,
+        prices,
+        preset_prices: existing_item.preset_prices.clone(),
+        image: existing_item.image.clone(),
+        stock: update_data.stock.unwrap_or(existing_item.stock),
+        variants,
+        is_available: update_data
+            .is_available
+            .unwrap_or(existing_item.is_available),
+        revision: existing_item.revision,
+        trashed_at: existing_item.trashed_at,
+    })
+}
+
+pub async fn get_menu_item(
+    storage: web::Data<dyn Storage>,
+    locales: web::Data<Locales>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, ApiErrorType> {
+    let item_id = path.into_inner();
+
+    let items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
+    let item = items
+        .into_iter()
+        .find(|item| item.id == item_id)
+        .ok_or_else(|| {
+            ApiErrorType::NotFound(format!("Menu item with id {} not found", item_id))
+        })?;
+    let item = match locales.requested(&req) {
+        Some(locale) => item.localized(locale),
+        None => item,
+    };
+
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(item.revision))
+        .json(item))
+}
+
+pub async fn update_menu_item(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    update_data: web::Json<UpdateMenuItemRequest>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let item_id = path.into_inner();
+
+    // Get existing item
+    let items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
+    let existing_item = items
+        .iter()
+        .find(|item| item.id == item_id)
+        .ok_or_else(|| {
+            ApiErrorType::NotFound(format!("Menu item with id {} not found", item_id))
+        })?;
+    check_if_match(&req, "Menu item", item_id, existing_item.revision)?;
+
+    let categories = storage
+        .get_menu_categories()
+        .map_err(ApiErrorType::Storage)?;
+    let ingredients = storage.get_ingredients().map_err(ApiErrorType::Storage)?;
+    let mut updated_item =
+        apply_menu_item_update(existing_item, &update_data, &categories, &ingredients)?;
+
+    storage
+        .update_menu_item(item_id, updated_item.clone())
+        .map_err(ApiErrorType::from)?;
+    // Storage bumped the stored revision on success
+    updated_item.revision += 1;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Update,
+        AuditEntity::MenuItem,
+        item_id,
+        Some(existing_item),
+        Some(&updated_item),
+    );
+
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(updated_item.revision))
+        .json(MenuItemResponse::new(updated_item, &ingredients)))
+}
+
+pub async fn bulk_update_menu_items(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    bulk_data: web::Json<BulkUpdateMenuItemsRequest>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
+    let categories = storage
+        .get_menu_categories()
+        .map_err(ApiErrorType::Storage)?;
+    let ingredients = storage.get_ingredients().map_err(ApiErrorType::Storage)?;
+
+    // Validate every update up front so the batch is all-or-nothing
+    let mut ops = Vec::with_capacity(bulk_data.updates.len());
+    let mut updated_items = Vec::with_capacity(bulk_data.updates.len());
+    let mut previous_items = Vec::with_capacity(bulk_data.updates.len());
+    for update in &bulk_data.updates {
+        let existing_item = items
+            .iter()
+            .find(|item| item.id == update.id)
+            .ok_or_else(|| {
+                ApiErrorType::NotFound(format!("Menu item with id {} not found", update.id))
+            })?;
+        let updated_item =
+            apply_menu_item_update(existing_item, &update.changes, &categories, &ingredients)?;
+        ops.push(BatchOp::UpdateMenuItem(update.id, updated_item.clone()));
+        updated_items.push(updated_item);
+        previous_items.push(existing_item);
+    }
+
+    storage.apply_batch(ops).map_err(ApiErrorType::Storage)?;
+    // Storage bumped every stored revision on success
+    for item in &mut updated_items {
+        item.revision += 1;
+    }
+    let actor = Actor::from_session(&session);
+    for (before, after) in previous_items.iter().zip(&updated_items) {
+        audit.record(
+            &actor,
+            AuditAction::Update,
+            AuditEntity::MenuItem,
+            after.id,
+            Some(*before),
+            Some(after),
+        );
+    }
+
+    let responses: Vec<MenuItemResponse> = updated_items
+        .into_iter()
+        .map(|item| MenuItemResponse::new(item, &ingredients))
+        .collect();
+    Ok(HttpResponse::Ok().json(responses))
+}
+
+pub async fn delete_menu_item(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    query: web::Query<DeleteQuery>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let item_id = path.into_inner();
+
+    // Honour If-Match if the item exists; a missing item is reported by storage
+    let items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
+    let existing_item = items.iter().find(|item| item.id == item_id);
+    let expected_revision = match existing_item {
+        Some(item) => check_if_match(&req, "Menu item", item_id, item.revision)?,
+        None => None,
+    };
+
+    let detached_from = if query.cascade {
+        storage.delete_menu_item_cascade(item_id, expected_revision)
+    } else {
+        storage
+            .delete_menu_item(item_id, expected_revision)
+            .map(|_| Vec::new())
+    }
+    .map_err(ApiErrorType::from)?;
+
+    let actor = Actor::from_session(&session);
+    for (before, after) in &detached_from {
+        audit.record(
+            &actor,
+            AuditAction::Update,
+            AuditEntity::MenuPreset,
+            after.id,
+            Some(before),
+            Some(after),
+        );
+    }
+    audit.record(
+        &actor,
+        AuditAction::Delete,
+        AuditEntity::MenuItem,
+        item_id,
+        existing_item,
+        None,
+    );
+
+    Ok(HttpResponse::NoContent())
+}
+
+// Menu Item Image Handlers
+
+/// Images are cached for a year; a new upload always gets a new URL
+const IMAGE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// Read the `image` field of a multipart upload, checking its type and size
+async fn read_image_upload(mut payload: Multipart) -> Result<(Vec<u8>, ImageKind), ApiErrorType> {
+    while let Some(mut field) = payload
+        .try_next()
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Invalid multipart body: {}", e)))?
+    {
+        if field.name() != Some("image") {
+            continue;
+        }
+        let content_type = field
+            .content_type()
+            .map(|mime| mime.essence_str().to_string())
+            .unwrap_or_default();
+        let kind = ImageKind::from_content_type(&content_type).ok_or_else(|| {
+            ApiErrorType::Validation(format!(
+                "Unsupported image type '{}'; expected one of: {}",
+                content_type,
+                ImageKind::ALL.map(ImageKind::content_type).join(", ")
+            ))
+        })?;
+        let bytes = field
+            .bytes(MAX_IMAGE_BYTES)
+            .await
+            .map_err(|_| {
+                ApiErrorType::Validation(format!(
+                    "Image is larger than the {} byte limit",
+                    MAX_IMAGE_BYTES
+                ))
+            })?
+            .map_err(|e| ApiErrorType::Validation(format!("Invalid multipart body: {}", e)))?;
+        return Ok((bytes.to_vec(), kind));
+    }
+    Err(ApiErrorType::Validation(
+        "Expected the image file in a multipart field named 'image'".to_string(),
+    ))
+}
+
+/// Attach a photo to a menu item, replacing any it already has
+pub async fn upload_menu_item_image(
+    storage: web::Data<dyn Storage>,
+    media: web::Data<MediaStore>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    payload: Multipart,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let item_id = path.into_inner();
+    let items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
+    let existing_item = items
+        .iter()
+        .find(|item| item.id == item_id)
+        .ok_or_else(|| {
+            ApiErrorType::NotFound(format!("Menu item with id {} not found", item_id))
+        })?;
+    check_if_match(&req, "Menu item", item_id, existing_item.revision)?;
+
+    let (bytes, kind) = read_image_upload(payload).await?;
+    // Decoding and scaling are CPU-bound, so keep them off the worker thread
+    let processed = web::block(move || process_image(bytes, kind))
+        .await
+        .map_err(|e| ApiErrorType::Storage(StorageError::Io(std::io::Error::other(e))))?
+        .map_err(|e| ApiErrorType::Validation(format!("Could not read the image: {}", e)))?;
+    let image = media.save(processed).map_err(ApiErrorType::Storage)?;
+
+    let mut updated_item = existing_item.clone();
+    updated_item.image = Some(image.clone());
+    if let Err(e) = storage.update_menu_item(item_id, updated_item.clone()) {
+        if let Err(remove_error) = media.remove(&image) {
+            log::warn!(
+                "Could not remove unused image {}: {}",
+                image.id,
+                remove_error
+            );
+        }
+        return Err(ApiErrorType::from(e));
+    }
+    if let Some(previous) = &existing_item.image
+        && let Err(e) = media.remove(previous)
+    {
+        log::warn!("Could not remove replaced image {}: {}", previous.id, e);
+    }
+    // Storage bumped the stored revision on success
+    updated_item.revision += 1;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Update,
+        AuditEntity::MenuItem,
+        item_id,
+        Some(existing_item),
+        Some(&updated_item),
+    );
+
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(updated_item.revision))
+        .json(updated_item))
+}
+
+/// Remove a menu item's photo and delete its files
+pub async fn delete_menu_item_image(
+    storage: web::Data<dyn Storage>,
+    media: web::Data<MediaStore>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let item_id = path.into_inner();
+    let items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
+    let existing_item = items
+        .iter()
+        .find(|item| item.id == item_id)
+        .ok_or_else(|| {
+            ApiErrorType::NotFound(format!("Menu item with id {} not found", item_id))
+        })?;
+    check_if_match(&req, "Menu item", item_id, existing_item.revision)?;
+    let image = existing_item.image.as_ref().ok_or_else(|| {
+        ApiErrorType::NotFound(format!("Menu item with id {} has no image", item_id))
+    })?;
+
+    let mut updated_item = existing_item.clone();
+    updated_item.image = None;
+    storage
+        .update_menu_item(item_id, updated_item.clone())
+        .map_err(ApiErrorType::from)?;
+    media.remove(image).map_err(ApiErrorType::Storage)?;
+    // Storage bumped the stored revision on success
+    updated_item.revision += 1;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Update,
+        AuditEntity::MenuItem,
+        item_id,
+        Some(existing_item),
+        Some(&updated_item),
+    );
+
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(updated_item.revision))
+        .json(updated_item))
+}
+
+fn serve_image(
+    media: &MediaStore,
+    req: &HttpRequest,
+    id: Uuid,
+    thumbnail: bool,
+) -> Result<HttpResponse, ApiErrorType> {
+    let path = media
+        .find(id, thumbnail)
+        .ok_or_else(|| ApiErrorType::NotFound(format!("Image with id {} not found", id)))?;
+    let file = NamedFile::open(path).map_err(|e| ApiErrorType::Storage(e.into()))?;
+    let mut response = file.into_response(req);
+    response.headers_mut().insert(
+        header::CACHE_CONTROL,
+        header::HeaderValue::from_static(IMAGE_CACHE_CONTROL),
+    );
+    Ok(response)
+}
+
+pub async fn menu_item_image(
+    media: web::Data<MediaStore>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiErrorType> {
+    serve_image(&media, &req, path.into_inner(), false)
+}
+
+pub async fn menu_item_thumbnail(
+    media: web::Data<MediaStore>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiErrorType> {
+    serve_image(&media, &req, path.into_inner(), true)
+}
+
+// Menu Item Stock Handlers
+
+/// Times a write that counts stock is retried when another change to the
+/// item lands between reading and writing it
+pub const STOCK_UPDATE_ATTEMPTS: u32 = 3;
+
+/// Count portions of an item as served. At zero the public menu shows it
+/// as sold out; its availability is left alone.
+pub async fn decrement_menu_item_stock(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    query: web::Query<DecrementStockQuery>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let item_id = path.into_inner();
+    if query.portions == 0 {
+        return Err(ApiErrorType::Validation(
+            "portions must be at least 1".to_string(),
+        ));
+    }
+    // Without If-Match the caller only cares that the portions are counted,
+    // so a write that raced another one is simply tried again
+    let conditional = req.headers().contains_key(header::IF_MATCH);
+
+    let mut attempt = 1;
+    loop {
+        let items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
+        let existing_item = items
+            .iter()
+            .find(|item| item.id == item_id)
+            .ok_or_else(|| {
+                ApiErrorType::NotFound(format!("Menu item with id {} not found", item_id))
+            })?;
+        check_if_match(&req, "Menu item", item_id, existing_item.revision)?;
+        let remaining = existing_item.stock.ok_or_else(|| {
+            ApiErrorType::Storage(StorageError::Conflict(format!(
+                "Menu item '{}' does not count stock",
+                existing_item.name
+            )))
+        })?;
+        if remaining == 0 {
+            return Err(ApiErrorType::Storage(StorageError::Conflict(format!(
+                "Menu item '{}' is already sold out",
+                existing_item.name
+            ))));
+        }
+        if query.portions > remaining {
+            return Err(ApiErrorType::Storage(StorageError::Conflict(format!(
+                "Menu item '{}' has only {} left, fewer than the {} portions served",
+                existing_item.name, remaining, query.portions
+            ))));
+        }
+
+        let mut updated_item = existing_item.clone();
+        updated_item.stock = Some(remaining - query.portions);
+        match storage.update_menu_item(item_id, updated_item.clone()) {
+            Ok(()) => {
+                // Storage bumped the stored revision on success
+                updated_item.revision += 1;
+                audit.record(
+                    &Actor::from_session(&session),
+                    AuditAction::Update,
+                    AuditEntity::MenuItem,
+                    item_id,
+                    Some(existing_item),
+                    Some(&updated_item),
+                );
+                return Ok(HttpResponse::Ok()
+                    .insert_header(etag(updated_item.revision))
+                    .json(updated_item));
+            }
+            Err(StorageError::PreconditionFailed(_))
+                if !conditional && attempt < STOCK_UPDATE_ATTEMPTS =>
+            {
+                attempt += 1;
+            }
+            Err(e) => return Err(ApiErrorType::from(e)),
+        }
+    }
+}
+
+// Menu Categories Handlers
+
+pub async fn list_menu_categories(
+    storage: web::Data<dyn Storage>,
+) -> Result<impl Responder, ApiErrorType> {
+    let categories = storage
+        .get_menu_categories()
+        .map_err(ApiErrorType::Storage)?;
+    Ok(HttpResponse::Ok().json(categories))
+}
+
+pub async fn create_menu_category(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    category_data: web::Json<CreateMenuCategoryRequest>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let categories = storage
+        .get_menu_categories()
+        .map_err(ApiErrorType::Storage)?;
+    let display_order = category_data.display_order.unwrap_or_else(|| {
+        categories
+            .iter()
+            .map(|category| category.display_order)
+            .max()
+            .map_or(1, |last| last + 1)
+    });
+
+    let new_category = MenuCategory {
+        id: Uuid::new_v4(),
+        name: category_data.name.trim().to_string(),
+        icon: category_data.icon.trim().to_string(),
+        display_order,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        revision: 0,
+        trashed_at: None,
+    };
+    validate_category(&categories, &new_category)?;
+
+    storage
+        .add_menu_category(new_category.clone())
+        .map_err(ApiErrorType::from)?;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Create,
+        AuditEntity::MenuCategory,
+        new_category.id,
+        None,
+        Some(&new_category),
+    );
+
+    Ok(HttpResponse::Created()
+        .insert_header(etag(new_category.revision))
+        .json(new_category))
+}
+
+pub async fn get_menu_category(
+    storage: web::Data<dyn Storage>,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, ApiErrorType> {
+    let category_id = path.into_inner();
+
+    let categories = storage
+        .get_menu_categories()
+        .map_err(ApiErrorType::Storage)?;
+    let category = categories
+        .into_iter()
+        .find(|category| category.id == category_id)
+        .ok_or_else(|| {
+            ApiErrorType::NotFound(format!("Menu category with id {} not found", category_id))
+        })?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(category.revision))
+        .json(category))
+}
+
+pub async fn update_menu_category(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    update_data: web::Json<UpdateMenuCategoryRequest>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let category_id = path.into_inner();
+
+    // Get existing category
+    let categories = storage
+        .get_menu_categories()
+        .map_err(ApiErrorType::Storage)?;
+    let existing_category = categories
+        .iter()
+        .find(|category| category.id == category_id)
+        .ok_or_else(|| {
+            ApiErrorType::NotFound(format!("Menu category with id {} not found", category_id))
+        })?;
+    check_if_match(
+        &req,
+        "Menu category",
+        category_id,
+        existing_category.revision,
+    )?;
+
+    let mut updated_category = existing_category.clone();
+    if let Some(name) = &update_data.name {
+        updated_category.name = name.trim().to_string();
+    }
+    if let Some(icon) = &update_data.icon {
+        updated_category.icon = icon.trim().to_string();
+    }
+    if let Some(display_order) = update_data.display_order {
+        updated_category.display_order = display_order;
+    }
+    updated_category.updated_at = Utc::now();
+    validate_category(&categories, &updated_category)?;
+
+    storage
+        .update_menu_category(category_id, updated_category.clone())
+        .map_err(ApiErrorType::from)?;
+    // Storage bumped the stored revision on success
+    updated_category.revision += 1;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Update,
+        AuditEntity::MenuCategory,
+        category_id,
+        Some(existing_category),
+        Some(&updated_category),
+    );
+
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(updated_category.revision))
+        .json(updated_category))
+}
+
+/// Move a category to the trash. Refused while live menu items are still
+/// filed under it; move those to another category first.
+pub async fn delete_menu_category(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let category_id = path.into_inner();
+
+    // Honour If-Match if the category exists; a missing category is reported by storage
+    let categories = storage
+        .get_menu_categories()
+        .map_err(ApiErrorType::Storage)?;
+    let existing_category = categories
+        .iter()
+        .find(|category| category.id == category_id);
+    let expected_revision = match existing_category {
+        Some(category) => check_if_match(&req, "Menu category", category_id, category.revision)?,
+        None => None,
+    };
+
+    storage
+        .delete_menu_category(category_id, expected_revision)
+        .map_err(ApiErrorType::from)?;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Delete,
+        AuditEntity::MenuCategory,
+        category_id,
+        existing_category,
+        None,
+    );
+
+    Ok(HttpResponse::NoContent())
+}
+
+// Ingredients Handlers
+
+/// A saved ingredient, with the menu items whose hand-set allergens or tags
+/// now disagree with their recipe
+#[derive(Debug, Serialize)]
+pub struct IngredientResponse {
+    #[serde(flatten)]
+    pub ingredient: Ingredient,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+pub async fn list_ingredients(
+    storage: web::Data<dyn Storage>,
+) -> Result<impl Responder, ApiErrorType> {
+    let ingredients = storage.get_ingredients().map_err(ApiErrorType::Storage)?;
+    Ok(HttpResponse::Ok().json(ingredients))
+}
+
+pub async fn create_ingredient(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    ingredient_data: web::Json<CreateIngredientRequest>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let ingredients = storage.get_ingredients().map_err(ApiErrorType::Storage)?;
+    let new_ingredient = Ingredient {
+        id: Uuid::new_v4(),
+        name: ingredient_data.name.trim().to_string(),
+        allergens: parse_codes("allergens", &ingredient_data.allergens)?,
+        may_contain: parse_codes("may_contain", &ingredient_data.may_contain)?,
+        dietary_tags: with_implied_tags(parse_codes(
+            "dietary_tags",
+            &ingredient_data.dietary_tags,
+        )?),
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        revision: 0,
+        trashed_at: None,
+    };
+    validate_ingredient(&ingredients, &new_ingredient)?;
+
+    storage
+        .add_ingredient(new_ingredient.clone())
+        .map_err(ApiErrorType::from)?;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Create,
+        AuditEntity::Ingredient,
+        new_ingredient.id,
+        None,
+        Some(&new_ingredient),
+    );
+
+    Ok(HttpResponse::Created()
+        .insert_header(etag(new_ingredient.revision))
+        .json(new_ingredient))
+}
+
+pub async fn get_ingredient(
+    storage: web::Data<dyn Storage>,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, ApiErrorType> {
+    let ingredient_id = path.into_inner();
+
+    let ingredients = storage.get_ingredients().map_err(ApiErrorType::Storage)?;
+    let ingredient = ingredients
+        .into_iter()
+        .find(|ingredient| ingredient.id == ingredient_id)
+        .ok_or_else(|| {
+            ApiErrorType::NotFound(format!("Ingredient with id {} not found", ingredient_id))
+        })?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(ingredient.revision))
+        .json(ingredient))
+}
+
+/// Update an ingredient. Menu items made from it have their allergens and
+/// tags recomputed, except where someone set them by hand; those are listed
+/// in `warnings` if they now disagree with the recipe.
+pub async fn update_ingredient(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    update_data: web::Json<UpdateIngredientRequest>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let ingredient_id = path.into_inner();
+
+    // Get existing ingredient
+    let ingredients = storage.get_ingredients().map_err(ApiErrorType::Storage)?;
+    let existing_ingredient = ingredients
+        .iter()
+        .find(|ingredient| ingredient.id == ingredient_id)
+        .ok_or_else(|| {
+            ApiErrorType::NotFound(format!("Ingredient with id {} not found", ingredient_id))
+        })?;
+    check_if_match(
+        &req,
+        "Ingredient",
+        ingredient_id,
+        existing_ingredient.revision,
+    )?;
+
+    let mut updated_ingredient = existing_ingredient.clone();
+    if let Some(name) = &update_data.name {
+        updated_ingredient.name = name.trim().to_string();
+    }
+    if let Some(values) = &update_data.allergens {
+        updated_ingredient.allergens = parse_codes("allergens", values)?;
+    }
+    if let Some(values) = &update_data.may_contain {
+        updated_ingredient.may_contain = parse_codes("may_contain", values)?;
+    }
+    if let Some(values) = &update_data.dietary_tags {
+        updated_ingredient.dietary_tags = with_implied_tags(parse_codes("dietary_tags", values)?);
+    }
+    updated_ingredient.updated_at = Utc::now();
+    validate_ingredient(&ingredients, &updated_ingredient)?;
+
+    let updated_items = storage
+        .update_ingredient(ingredient_id, updated_ingredient.clone())
+        .map_err(ApiErrorType::from)?;
+    // Storage bumped the stored revision on success
+    updated_ingredient.revision += 1;
+    let actor = Actor::from_session(&session);
+    audit.record(
+        &actor,
+        AuditAction::Update,
+        AuditEntity::Ingredient,
+        ingredient_id,
+        Some(existing_ingredient),
+        Some(&updated_ingredient),
+    );
+    for (before, after) in &updated_items {
+        audit.record(
+            &actor,
+            AuditAction::Update,
+            AuditEntity::MenuItem,
+            after.id,
+            Some(before),
+            Some(after),
+        );
+    }
+
+    let ingredients = storage.get_ingredients().map_err(ApiErrorType::Storage)?;
+    let warnings = storage
+        .get_menu_items()
+        .map_err(ApiErrorType::Storage)?
+        .into_iter()
+        .filter(|item| {
+            item.recipe
+                .iter()
+                .any(|line| line.ingredient_id == ingredient_id)
+        })
+        .flat_map(|item| {
+            let response = MenuItemResponse::new(item, &ingredients);
+            let name = response.item.name;
+            response
+                .warnings
+                .into_iter()
+                .map(move |warning| format!("Menu item '{}': {}", name, warning))
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(updated_ingredient.revision))
+        .json(IngredientResponse {
+            ingredient: updated_ingredient,
+            warnings,
+        }))
+}
+
+/// Move an ingredient to the trash. Refused while live menu items still use
+/// it in their recipe; remove it from those recipes first.
+pub async fn delete_ingredient(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let ingredient_id = path.into_inner();
+
+    // Honour If-Match if the ingredient exists; a missing ingredient is reported by storage
+    let ingredients = storage.get_ingredients().map_err(ApiErrorType::Storage)?;
+    let existing_ingredient = ingredients
+        .iter()
+        .find(|ingredient| ingredient.id == ingredient_id);
+    let expected_revision = match existing_ingredient {
+        Some(ingredient) => check_if_match(&req, "Ingredient", ingredient_id, ingredient.revision)?,
+        None => None,
+    };
+
+    storage
+        .delete_ingredient(ingredient_id, expected_revision)
+        .map_err(ApiErrorType::from)?;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Delete,
+        AuditEntity::Ingredient,
+        ingredient_id,
+        existing_ingredient,
+        None,
+    );
+
+    Ok(HttpResponse::NoContent())
+}
+
+/// Menu items whose hand-set allergens or tags disagree with their recipe
+pub async fn list_recipe_mismatches(
+    storage: web::Data<dyn Storage>,
+    session: actix_session::Session,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let ingredients = storage.get_ingredients().map_err(ApiErrorType::Storage)?;
+    let items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
+    let report: Vec<serde_json::Value> = items
+        .into_iter()
+        .filter_map(|item| {
+            let warnings = item.recipe_mismatches(&ingredients);
+            let unresolved = item
+                .derived(&ingredients)
+                .map(|derived| derived.unresolved)
+                .unwrap_or_default();
+            (!warnings.is_empty()).then(|| {
+                serde_json::json!({
+                    "id": item.id,
+                    "name": item.name,
+                    "warnings": warnings,
+                    "unresolved_ingredients": unresolved,
+                })
+            })
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// Feedback Handlers
+
+/// Page to send a diner back to after the item page's rating form, with
+/// `outcome` telling the page what to say
+fn feedback_redirect(
+    locales: &Locales,
+    req: &HttpRequest,
+    item_id: Uuid,
+    outcome: &str,
+) -> HttpResponse {
+    let mut location = format!("/menu/item/{}?feedback={}", item_id, outcome);
+    if let Some(locale) = locales.requested(req) {
+        location.push_str(&format!("&lang={}", locale));
+    }
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+/// Rate a menu item. Takes JSON from scripts or the item page's form; the
+/// form is sent back to the item page instead of getting a JSON reply.
+/// Comments wait for a moderator before they are shown.
+pub async fn submit_feedback(
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
+    limiter: web::Data<RateLimiter>,
+    locales: web::Data<Locales>,
+    path: web::Path<Uuid>,
+    body: web::Either<web::Json<SubmitFeedbackRequest>, web::Form<SubmitFeedbackRequest>>,
+) -> Result<HttpResponse, ApiErrorType> {
+    let item_id = path.into_inner();
+    let (submission, from_form) = match body {
+        web::Either::Left(json) => (json.into_inner(), false),
+        web::Either::Right(form) => (form.into_inner(), true),
+    };
+
+    if !(MIN_RATING..=MAX_RATING).contains(&submission.rating) {
+        return Err(ApiErrorType::Validation(format!(
+            "rating must be between {} and {}",
+            MIN_RATING, MAX_RATING
+        )));
+    }
+    let comment = submission
+        .comment
+        .map(|comment| comment.trim().to_string())
+        .filter(|comment| !comment.is_empty());
+    if comment
+        .as_ref()
+        .is_some_and(|comment| comment.chars().count() > MAX_COMMENT_CHARS)
+    {
+        return Err(ApiErrorType::Validation(format!(
+            "comment must be at most {} characters",
+            MAX_COMMENT_CHARS
+        )));
+    }
+    let items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
+    if !items.iter().any(|item| item.id == item_id) {
+        return Err(ApiErrorType::NotFound(format!(
+            "Menu item with id {} not found",
+            item_id
+        )));
+    }
+
+    // Behind a reverse proxy every diner shares the proxy's address
+    let client = req
+        .peer_addr()
+        .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |addr| addr.ip());
+    if let Err(wait) = limiter.check(client) {
+        log::info!("Refused feedback on menu item {} from {}", item_id, client);
+        if from_form {
+            return Ok(feedback_redirect(&locales, &req, item_id, "limited"));
+        }
+        let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+        return Err(ApiErrorType::RateLimited(seconds));
+    }
+
+    let feedback = Feedback {
+        id: Uuid::new_v4(),
+        menu_item_id: item_id,
+        rating: submission.rating,
+        status: if comment.is_some() {
+            ModerationStatus::Pending
+        } else {
+            ModerationStatus::Approved
+        },
+        comment,
+        created_at: Utc::now(),
+        moderated_at: None,
+        revision: 0,
+        trashed_at: None,
+    };
+    storage
+        .add_feedback(feedback.clone())
+        .map_err(ApiErrorType::from)?;
+
+    if from_form {
+        Ok(feedback_redirect(&locales, &req, item_id, "sent"))
+    } else {
+        Ok(HttpResponse::Created()
+            .insert_header(etag(feedback.revision))
+            .json(feedback))
+    }
+}
+
+pub async fn list_feedback(
+    storage: web::Data<dyn Storage>,
+    session: actix_session::Session,
+    query: web::Query<FeedbackQuery>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let status = query
+        .status
+        .as_deref()
+        .map(|status| parse_code::<ModerationStatus>("status", status))
+        .transpose()?;
+    let feedback: Vec<Feedback> = storage
+        .get_feedback()
+        .map_err(ApiErrorType::Storage)?
+        .into_iter()
+        .filter(|entry| query.menu_item_id.is_none_or(|id| entry.menu_item_id == id))
+        .filter(|entry| status.is_none_or(|status| entry.status == status))
+        .collect();
+    Ok(HttpResponse::Ok().json(feedback))
+}
+
+/// Ratings of one menu item
+#[derive(Debug, Serialize)]
+pub struct ItemRatings {
+    pub menu_item_id: Uuid,
+    /// `None` once the item has been purged
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub ratings: RatingSummary,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FeedbackStats {
+    pub overall: RatingSummary,
+    /// Best rated first
+    pub items: Vec<ItemRatings>,
+    pub trend: Vec<TrendPoint>,
+}
+
+/// Average ratings overall and per item, and how the average moved over
+/// time. Rejected comments still count towards their rating.
+pub async fn feedback_stats(
+    storage: web::Data<dyn Storage>,
+    session: actix_session::Session,
+    query: web::Query<FeedbackStatsQuery>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let feedback = storage.get_feedback().map_err(ApiErrorType::Storage)?;
+    let counted: Vec<&Feedback> = feedback
+        .iter()
+        .filter(|entry| query.menu_item_id.is_none_or(|id| entry.menu_item_id == id))
+        .filter(|entry| query.since.is_none_or(|since| entry.created_at >= since))
+        .collect();
+
+    // Ratings of trashed items are still reported under their name
+    let mut names: std::collections::HashMap<Uuid, String> = storage
+        .get_trash()
+        .map_err(ApiErrorType::Storage)?
+        .menu_items
+        .into_iter()
+        .map(|item| (item.id, item.name))
+        .collect();
+    names.extend(
+        storage
+            .get_menu_items()
+            .map_err(ApiErrorType::Storage)?
+            .into_iter()
+            .map(|item| (item.id, item.name)),
+    );
+
+    let mut by_item: BTreeMap<Uuid, Vec<&Feedback>> = BTreeMap::new();
+    for entry in &counted {
+        by_item.entry(entry.menu_item_id).or_default().push(entry);
+    }
+    let mut items: Vec<ItemRatings> = by_item
+        .into_iter()
+        .map(|(menu_item_id, entries)| ItemRatings {
+            menu_item_id,
+            name: names.get(&menu_item_id).cloned(),
+            ratings: RatingSummary::of(entries),
+        })
+        .collect();
+    items.sort_by(|a, b| {
+        b.ratings
+            .average
+            .unwrap_or_default()
+            .total_cmp(&a.ratings.average.unwrap_or_default())
+            .then(b.ratings.count.cmp(&a.ratings.count))
+    });
+
+    Ok(HttpResponse::Ok().json(FeedbackStats {
+        overall: RatingSummary::of(counted.iter().copied()),
+        items,
+        trend: rating_trend(&counted, query.period),
+    }))
+}
+
+/// Comments waiting for a moderator, oldest first
+pub async fn feedback_queue(
+    storage: web::Data<dyn Storage>,
+    session: actix_session::Session,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let queue: Vec<Feedback> = storage
+        .get_feedback()
+        .map_err(ApiErrorType::Storage)?
+        .into_iter()
+        .filter(|entry| entry.status == ModerationStatus::Pending)
+        .collect();
+    Ok(HttpResponse::Ok().json(queue))
+}
+
+/// Approve or reject a comment, or put it back in the queue
+pub async fn moderate_feedback(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    moderation: web::Json<ModerateFeedbackRequest>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let feedback_id = path.into_inner();
+    let feedback = storage.get_feedback().map_err(ApiErrorType::Storage)?;
+    let existing_feedback = feedback
+        .iter()
+        .find(|entry| entry.id == feedback_id)
+        .ok_or_else(|| {
+            ApiErrorType::NotFound(format!("Feedback with id {} not found", feedback_id))
+        })?;
+    check_if_match(&req, "Feedback", feedback_id, existing_feedback.revision)?;
+    if existing_feedback.comment.is_none() {
+        return Err(ApiErrorType::Validation(format!(
+            "Feedback with id {} has no comment to moderate",
+            feedback_id
+        )));
+    }
+
+    let status = parse_code::<ModerationStatus>("status", &moderation.status)?;
+    let mut updated_feedback = existing_feedback.clone();
+    updated_feedback.status = status;
+    updated_feedback.moderated_at = (status != ModerationStatus::Pending).then(Utc::now);
+
+    storage
+        .update_feedback(feedback_id, updated_feedback.clone())
+        .map_err(ApiErrorType::from)?;
+    // Storage bumped the stored revision on success
+    updated_feedback.revision += 1;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Update,
+        AuditEntity::Feedback,
+        feedback_id,
+        Some(existing_feedback),
+        Some(&updated_feedback),
+    );
+
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(updated_feedback.revision))
+        .json(updated_feedback))
+}
+
+/// Move feedback to the trash, e.g. spam that slipped past the rate limit.
+/// Its rating stops counting.
+pub async fn delete_feedback(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let feedback_id = path.into_inner();
+
+    // Honour If-Match if the feedback exists; missing feedback is reported by storage
+    let feedback = storage.get_feedback().map_err(ApiErrorType::Storage)?;
+    let existing_feedback = feedback.iter().find(|entry| entry.id == feedback_id);
+    let expected_revision = match existing_feedback {
+        Some(entry) => check_if_match(&req, "Feedback", feedback_id, entry.revision)?,
+        None => None,
+    };
+
+    storage
+        .delete_feedback(feedback_id, expected_revision)
+        .map_err(ApiErrorType::from)?;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Delete,
+        AuditEntity::Feedback,
+        feedback_id,
+        existing_feedback,
+        None,
+    );
+
+    Ok(HttpResponse::NoContent())
+}
+
+// Notices Handlers
+
+/// A notice's publish window has to end after it starts, and only a
+/// critical notice can put the site in emergency mode
+fn validate_notice(notice: &Notice) -> Result<(), ApiErrorType> {
+    if let (Some(publish_at), Some(expire_at)) = (notice.publish_at, notice.expire_at)
+        && expire_at <= publish_at
+    {
+        return Err(ApiErrorType::Validation(format!(
+            "expire_at ({}) must be after publish_at ({})",
+            expire_at.to_rfc3339(),
+            publish_at.to_rfc3339()
+        )));
+    }
+    if notice.emergency && notice.severity != NoticeSeverity::Critical {
+        return Err(ApiErrorType::Validation(format!(
+            "Only Critical notices can turn on emergency mode, not {} ones",
+            notice.severity.code()
+        )));
+    }
+    Ok(())
+}
+
+pub async fn list_notices(
+    storage: web::Data<dyn Storage>,
+    locales: web::Data<Locales>,
+    req: HttpRequest,
+) -> Result<impl Responder, ApiErrorType> {
+    let mut notices = storage.get_notices().map_err(ApiErrorType::Storage)?;
+    notices.sort_by(Notice::display_order);
+    // Only an explicit ?lang= localizes, so editors always get the text they saved
+    if let Some(locale) = locales.requested(&req) {
+        notices = notices
+            .into_iter()
+            .map(|notice| notice.localized(locale))
+            .collect();
+    }
+    Ok(HttpResponse::Ok().json(notices))
+}
+
+pub async fn create_notice(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    notice_data: web::Json<CreateNoticeRequest>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    use chrono::Utc;
+
+    let new_notice = Notice {
+        id: Uuid::new_v4(),
+        title: notice_data.title.clone(),
+        content: notice_data.content.clone(),
+        translations: Translations::new(),
+        is_active: notice_data.is_active,
+        publish_at: notice_data.publish_at,
+        expire_at: notice_data.expire_at,
+        severity: notice_data
+            .severity
+            .as_deref()
+            .map(|severity| parse_code("severity", severity))
+            .transpose()?
+            .unwrap_or_default(),
+        pinned: notice_data.pinned,
+        emergency: notice_data.emergency,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        revision: 0,
+        trashed_at: None,
+    };
+    validate_notice(&new_notice)?;
+
+    storage
+        .add_notice(new_notice.clone())
+        .map_err(ApiErrorType::from)?;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Create,
+        AuditEntity::Notice,
+        new_notice.id,
+        None,
+        Some(&new_notice),
+    );
+
+    Ok(HttpResponse::Created()
+        .insert_header(etag(new_notice.revision))
+        .json(new_notice))
+}
+
+pub async fn get_notice(
+    storage: web::Data<dyn Storage>,
+    locales: web::Data<Locales>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, ApiErrorType> {
+    let notice_id = path.into_inner();
+
+    let notices = storage.get_notices().map_err(ApiErrorType::Storage)?;
+    let notice = notices
+        .into_iter()
+        .find(|notice| notice.id == notice_id)
+        .ok_or_else(|| ApiErrorType::NotFound(format!("Notice with id {} not found", notice_id)))?;
+    let notice = match locales.requested(&req) {
+        Some(locale) => notice.localized(locale),
+        None => notice,
+    };
+
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(notice.revision))
+        .json(notice))
+}
+
+pub async fn update_notice(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    req: HttpRequest,
     path: web::Path<Uuid>,
     update_data: web::Json<UpdateNoticeRequest>,
 ) -> Result<impl Responder, ApiErrorType> {
-    let notice_id = path.into_inner();
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let notice_id = path.into_inner();
+
+    // Get existing notice
+    let notices = storage.get_notices().map_err(ApiErrorType::Storage)?;
+    let existing_notice = notices
+        .iter()
+        .find(|notice| notice.id == notice_id)
+        .ok_or_else(|| ApiErrorType::NotFound(format!("Notice with id {} not found", notice_id)))?;
+    check_if_match(&req, "Notice", notice_id, existing_notice.revision)?;
+
+    use chrono::Utc;
+
+    let mut updated_notice = Notice {
+        id: notice_id,
+        title: update_data
+            .title
+            .clone()
+            .unwrap_or_else(|| existing_notice.title.clone()),
+        content: update_data
+            .content
+            .clone()
+            .unwrap_or_else(|| existing_notice.content.clone()),
+        translations: existing_notice.translations.clone(),
+        is_active: update_data.is_active.unwrap_or(existing_notice.is_active),
+        publish_at: update_data.publish_at.unwrap_or(existing_notice.publish_at),
+        expire_at: update_data.expire_at.unwrap_or(existing_notice.expire_at),
+        severity: match &update_data.severity {
+            Some(severity) => parse_code("severity", severity)?,
+            None => existing_notice.severity,
+        },
+        pinned: update_data.pinned.unwrap_or(existing_notice.pinned),
+        emergency: update_data.emergency.unwrap_or(existing_notice.emergency),
+        created_at: existing_notice.created_at,
+        updated_at: Utc::now(),
+        revision: existing_notice.revision,
+        trashed_at: existing_notice.trashed_at,
+    };
+    validate_notice(&updated_notice)?;
+
+    storage
+        .update_notice(notice_id, updated_notice.clone())
+        .map_err(ApiErrorType::from)?;
+    // Storage bumped the stored revision on success
+    updated_notice.revision += 1;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Update,
+        AuditEntity::Notice,
+        notice_id,
+        Some(existing_notice),
+        Some(&updated_notice),
+    );
+
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(updated_notice.revision))
+        .json(updated_notice))
+}
+
+pub async fn delete_notice(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let notice_id = path.into_inner();
+
+    // Honour If-Match if the notice exists; a missing notice is reported by storage
+    let notices = storage.get_notices().map_err(ApiErrorType::Storage)?;
+    let existing_notice = notices.iter().find(|notice| notice.id == notice_id);
+    let expected_revision = match existing_notice {
+        Some(notice) => check_if_match(&req, "Notice", notice_id, notice.revision)?,
+        None => None,
+    };
+
+    storage
+        .delete_notice(notice_id, expected_revision)
+        .map_err(ApiErrorType::from)?;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Delete,
+        AuditEntity::Notice,
+        notice_id,
+        existing_notice,
+        None,
+    );
+
+    Ok(HttpResponse::NoContent())
+}
+
+/// Render notice content the way diners will see it, without saving anything
+pub async fn preview_notice(
+    session: actix_session::Session,
+    preview: web::Json<PreviewNoticeRequest>,
+) -> Result<impl Responder, ApiErrorType> {
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "html": markdown::render(&preview.content),
+    })))
+}
+
+// Translation Handlers
+
+/// The locales pages can be shown in and the one records are written in
+pub async fn list_translation_locales(locales: web::Data<Locales>) -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({
+        "default_locale": locales.default_locale(),
+        "locales": locales.languages(),
+    }))
+}
+
+/// Locale a translation may be stored under: one with a UI catalog, other
+/// than the default, whose text lives in the record's own fields
+fn check_translation_locale(locales: &Locales, lang: &str) -> Result<String, ApiErrorType> {
+    let lang = lang.trim().to_ascii_lowercase();
+    if lang == locales.default_locale() {
+        return Err(ApiErrorType::Validation(format!(
+            "'{}' is the default locale; edit the record itself instead",
+            lang
+        )));
+    }
+    if !locales.is_supported(&lang) {
+        let supported: Vec<String> = locales
+            .languages()
+            .into_iter()
+            .map(|language| language.code)
+            .filter(|code| code != locales.default_locale())
+            .collect();
+        return Err(ApiErrorType::Validation(format!(
+            "Unsupported locale '{}'. Expected one of: {}",
+            lang,
+            supported.join(", ")
+        )));
+    }
+    Ok(lang)
+}
+
+/// Check the text given for one locale, by field name
+fn validate_translation(
+    text: &BTreeMap<String, String>,
+    fields: &[&str],
+) -> Result<(), ApiErrorType> {
+    if text.is_empty() {
+        return Err(ApiErrorType::Validation(format!(
+            "A translation must set at least one of: {}",
+            fields.join(", ")
+        )));
+    }
+    for (field, value) in text {
+        if !fields.contains(&field.as_str()) {
+            return Err(ApiErrorType::Validation(format!(
+                "Unknown field '{}'. Translatable fields are: {}",
+                field,
+                fields.join(", ")
+            )));
+        }
+        if value.trim().is_empty() {
+            return Err(ApiErrorType::Validation(format!(
+                "Translation of '{}' must not be empty",
+                field
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Set a menu item's text in one locale, replacing any earlier translation
+pub async fn put_menu_item_translation(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    locales: web::Data<Locales>,
+    session: actix_session::Session,
+    req: HttpRequest,
+    path: web::Path<(Uuid, String)>,
+    text: web::Json<BTreeMap<String, String>>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let (item_id, lang) = path.into_inner();
+    let lang = check_translation_locale(&locales, &lang)?;
+    validate_translation(&text, MENU_ITEM_FIELDS)?;
+
+    let items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
+    let existing_item = items
+        .iter()
+        .find(|item| item.id == item_id)
+        .ok_or_else(|| {
+            ApiErrorType::NotFound(format!("Menu item with id {} not found", item_id))
+        })?;
+    check_if_match(&req, "Menu item", item_id, existing_item.revision)?;
+
+    let mut updated_item = existing_item.clone();
+    updated_item.translations.insert(lang, text.into_inner());
+    storage
+        .update_menu_item(item_id, updated_item.clone())
+        .map_err(ApiErrorType::from)?;
+    // Storage bumped the stored revision on success
+    updated_item.revision += 1;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Update,
+        AuditEntity::MenuItem,
+        item_id,
+        Some(existing_item),
+        Some(&updated_item),
+    );
+
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(updated_item.revision))
+        .json(updated_item))
+}
+
+/// Remove a menu item's text in one locale, so it falls back to the default
+pub async fn delete_menu_item_translation(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    locales: web::Data<Locales>,
+    session: actix_session::Session,
+    req: HttpRequest,
+    path: web::Path<(Uuid, String)>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let (item_id, lang) = path.into_inner();
+    let lang = check_translation_locale(&locales, &lang)?;
+
+    let items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
+    let existing_item = items
+        .iter()
+        .find(|item| item.id == item_id)
+        .ok_or_else(|| {
+            ApiErrorType::NotFound(format!("Menu item with id {} not found", item_id))
+        })?;
+    check_if_match(&req, "Menu item", item_id, existing_item.revision)?;
+
+    let mut updated_item = existing_item.clone();
+    if updated_item.translations.remove(&lang).is_none() {
+        return Err(ApiErrorType::NotFound(format!(
+            "Menu item with id {} has no '{}' translation",
+            item_id, lang
+        )));
+    }
+    storage
+        .update_menu_item(item_id, updated_item.clone())
+        .map_err(ApiErrorType::from)?;
+    // Storage bumped the stored revision on success
+    updated_item.revision += 1;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Update,
+        AuditEntity::MenuItem,
+        item_id,
+        Some(existing_item),
+        Some(&updated_item),
+    );
+
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(updated_item.revision))
+        .json(updated_item))
+}
+
+/// Set a notice's text in one locale, replacing any earlier translation
+pub async fn put_notice_translation(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    locales: web::Data<Locales>,
+    session: actix_session::Session,
+    req: HttpRequest,
+    path: web::Path<(Uuid, String)>,
+    text: web::Json<BTreeMap<String, String>>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let (notice_id, lang) = path.into_inner();
+    let lang = check_translation_locale(&locales, &lang)?;
+    validate_translation(&text, NOTICE_FIELDS)?;
+
+    let notices = storage.get_notices().map_err(ApiErrorType::Storage)?;
+    let existing_notice = notices
+        .iter()
+        .find(|notice| notice.id == notice_id)
+        .ok_or_else(|| ApiErrorType::NotFound(format!("Notice with id {} not found", notice_id)))?;
+    check_if_match(&req, "Notice", notice_id, existing_notice.revision)?;
+
+    let mut updated_notice = existing_notice.clone();
+    updated_notice.translations.insert(lang, text.into_inner());
+    updated_notice.updated_at = Utc::now();
+    storage
+        .update_notice(notice_id, updated_notice.clone())
+        .map_err(ApiErrorType::from)?;
+    // Storage bumped the stored revision on success
+    updated_notice.revision += 1;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Update,
+        AuditEntity::Notice,
+        notice_id,
+        Some(existing_notice),
+        Some(&updated_notice),
+    );
+
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(updated_notice.revision))
+        .json(updated_notice))
+}
+
+/// Remove a notice's text in one locale, so it falls back to the default
+pub async fn delete_notice_translation(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    locales: web::Data<Locales>,
+    session: actix_session::Session,
+    req: HttpRequest,
+    path: web::Path<(Uuid, String)>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let (notice_id, lang) = path.into_inner();
+    let lang = check_translation_locale(&locales, &lang)?;
+
+    let notices = storage.get_notices().map_err(ApiErrorType::Storage)?;
+    let existing_notice = notices
+        .iter()
+        .find(|notice| notice.id == notice_id)
+        .ok_or_else(|| ApiErrorType::NotFound(format!("Notice with id {} not found", notice_id)))?;
+    check_if_match(&req, "Notice", notice_id, existing_notice.revision)?;
+
+    let mut updated_notice = existing_notice.clone();
+    if updated_notice.translations.remove(&lang).is_none() {
+        return Err(ApiErrorType::NotFound(format!(
+            "Notice with id {} has no '{}' translation",
+            notice_id, lang
+        )));
+    }
+    updated_notice.updated_at = Utc::now();
+    storage
+        .update_notice(notice_id, updated_notice.clone())
+        .map_err(ApiErrorType::from)?;
+    // Storage bumped the stored revision on success
+    updated_notice.revision += 1;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Update,
+        AuditEntity::Notice,
+        notice_id,
+        Some(existing_notice),
+        Some(&updated_notice),
+    );
+
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(updated_notice.revision))
+        .json(updated_notice))
+}
+
+/// What each locale other than the default still needs translating: UI
+/// strings its catalog leaves out, and menu items and notices with a field
+/// that would fall back to the default text
+pub async fn list_missing_translations(
+    storage: web::Data<dyn Storage>,
+    locales: web::Data<Locales>,
+    session: actix_session::Session,
+    req: HttpRequest,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
 
-    // Get existing notice
+    let requested = form_urlencoded::parse(req.query_string().as_bytes())
+        .find(|(key, _)| key == "lang")
+        .map(|(_, lang)| check_translation_locale(&locales, &lang))
+        .transpose()?;
+    let targets: Vec<String> = match requested {
+        Some(lang) => vec![lang],
+        None => locales
+            .languages()
+            .into_iter()
+            .map(|language| language.code)
+            .filter(|code| code != locales.default_locale())
+            .collect(),
+    };
+
+    let items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
     let notices = storage.get_notices().map_err(ApiErrorType::Storage)?;
-    let existing_notice = notices
+    let report: Vec<serde_json::Value> = targets
         .iter()
-        .find(|notice| notice.id == notice_id)
-        .ok_or_else(|| ApiErrorType::NotFound(format!("Notice with id {} not found", notice_id)))?;
-
-    use chrono::Utc;
+        .map(|lang| {
+            let menu_items: Vec<serde_json::Value> = items
+                .iter()
+                .filter_map(|item| {
+                    let fields = item.missing_translations(lang);
+                    (!fields.is_empty()).then(|| {
+                        serde_json::json!({
+                            "id": item.id,
+                            "name": item.name,
+                            "fields": fields,
+                        })
+                    })
+                })
+                .collect();
+            let notices: Vec<serde_json::Value> = notices
+                .iter()
+                .filter_map(|notice| {
+                    let fields = notice.missing_translations(lang);
+                    (!fields.is_empty()).then(|| {
+                        serde_json::json!({
+                            "id": notice.id,
+                            "title": notice.title,
+                            "fields": fields,
+                        })
+                    })
+                })
+                .collect();
+            serde_json::json!({
+                "locale": lang,
+                "ui_strings": locales.missing_keys(lang),
+                "menu_items": menu_items,
+                "notices": notices,
+            })
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(report))
+}
 
-    let updated_notice = Notice {
-        id: notice_id,
-        title: update_data
-            .title
-            .clone()
-            .unwrap_or_else(|| existing_notice.title.clone()),
-        content: update_data
-            .content
-            .clone()
-            .unwrap_or_else(|| existing_notice.content.clone()),
-        is_active: update_data.is_active.unwrap_or(existing_notice.is_active),
-        created_at: existing_notice.created_at,
-        updated_at: Utc::now(),
-    };
+/// Template context holding the UI strings of the locale the request
+/// negotiated, returned with that locale. An explicit `?lang=` is kept in
+/// `lang_query` so the page's links stay in the same language.
+fn page_context(locales: &Locales, req: &HttpRequest) -> (tera::Context, String) {
+    let locale = locales.negotiate(req).to_string();
+    let lang_query = locales
+        .requested(req)
+        .map(|requested| format!("?lang={}", requested))
+        .unwrap_or_default();
+    let mut context = tera::Context::new();
+    context.insert("lang", &locale);
+    context.insert("t", &locales.strings(&locale));
+    context.insert("languages", &locales.languages());
+    context.insert("lang_query", &lang_query);
+    (context, locale)
+}
 
-    storage
-        .update_notice(notice_id, updated_notice.clone())
-        .map_err(ApiErrorType::from)?;
+/// A notice as diners see it, with its Markdown content rendered to HTML
+#[derive(Serialize)]
+struct PublicNotice {
+    #[serde(flatten)]
+    notice: Notice,
+    content_html: String,
+}
 
-    Ok(HttpResponse::Ok().json(updated_notice))
+impl From<Notice> for PublicNotice {
+    fn from(notice: Notice) -> Self {
+        Self {
+            content_html: markdown::render(&notice.content),
+            notice,
+        }
+    }
 }
 
-pub async fn delete_notice(
-    storage: web::Data<JsonStorage>,
-    path: web::Path<Uuid>,
-) -> Result<impl Responder, ApiErrorType> {
-    let notice_id = path.into_inner();
+/// Notices diners see right now, in display order and in the page's locale
+struct PublicNotices {
+    /// Shown as a banner across every public page
+    critical: Vec<PublicNotice>,
+    /// Listed on the menu
+    others: Vec<PublicNotice>,
+    /// Whether a critical notice has put the site in emergency mode
+    emergency: bool,
+}
 
-    storage
-        .delete_notice(notice_id)
-        .map_err(ApiErrorType::from)?;
+impl PublicNotices {
+    fn load(storage: &dyn Storage, locale: &str) -> Result<Self, StorageError> {
+        let now = Utc::now();
+        let mut notices: Vec<Notice> = storage
+            .get_notices()?
+            .into_iter()
+            .filter(|notice| notice.is_visible(now))
+            .map(|notice| notice.localized(locale))
+            .collect();
+        notices.sort_by(Notice::display_order);
+        let emergency = notices.iter().any(|notice| notice.emergency);
+        let (critical, others) = notices
+            .into_iter()
+            .map(PublicNotice::from)
+            .partition(|public| public.notice.severity == NoticeSeverity::Critical);
+        Ok(Self {
+            critical,
+            others,
+            emergency,
+        })
+    }
+}
 
-    Ok(HttpResponse::NoContent())
+/// In emergency mode the menu is hidden and diners see only the critical
+/// notices, which `context` must already hold
+fn emergency_page(tera: &Tera, context: &tera::Context) -> Result<HttpResponse, ApiErrorType> {
+    let rendered = tera
+        .render("emergency.html", context)
+        .map_err(|e| ApiErrorType::Validation(format!("Template error: {}", e)))?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
 // Login page handler
 pub async fn login_page(
+    req: HttpRequest,
     tera: web::Data<Tera>,
+    locales: web::Data<Locales>,
     session: actix_session::Session,
 ) -> Result<HttpResponse, ApiErrorType> {
     println!("DEBUG: login_page handler called");
@@ -345,8 +3078,9 @@ pub async fn login_page(
     }
 
     // If not logged in, render the login page
+    let (context, _) = page_context(&locales, &req);
     let rendered = tera
-        .render("admin/login.html", &tera::Context::new())
+        .render("admin/login.html", &context)
         .map_err(|e| ApiErrorType::Validation(format!("Template error: {}", e)))?;
 
     Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
@@ -354,9 +3088,12 @@ pub async fn login_page(
 
 // Admin Dashboard Handler
 pub async fn admin_dashboard(
-    storage: web::Data<JsonStorage>,
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
     session: actix_session::Session,
     tera: web::Data<Tera>,
+    locales: web::Data<Locales>,
+    currency: web::Data<Currency>,
 ) -> Result<HttpResponse, ApiErrorType> {
     println!("DEBUG: admin_dashboard handler called");
 
@@ -368,12 +3105,29 @@ pub async fn admin_dashboard(
 
     // Get menu items and notices
     let menu_items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
-    let notices = storage.get_notices().map_err(ApiErrorType::Storage)?;
+    let mut notices = storage.get_notices().map_err(ApiErrorType::Storage)?;
+    notices.sort_by(Notice::display_order);
+    let categories = storage
+        .get_menu_categories()
+        .map_err(ApiErrorType::Storage)?;
+    let category_names: std::collections::HashMap<Uuid, &str> = categories
+        .iter()
+        .map(|category| (category.id, category.name.as_str()))
+        .collect();
+    let ingredients = storage.get_ingredients().map_err(ApiErrorType::Storage)?;
 
     // Prepare context for template
-    let mut context = tera::Context::new();
+    let (mut context, _) = page_context(&locales, &req);
     context.insert("menu_items", &menu_items);
     context.insert("notices", &notices);
+    context.insert("categories", &categories);
+    context.insert("category_names", &category_names);
+    context.insert("ingredients", &ingredients);
+    context.insert("allergens", &code_infos::<Allergen>());
+    context.insert("dietary_tags", &code_infos::<DietaryTag>());
+    context.insert("price_tiers", &code_infos::<PriceTier>());
+    context.insert("notice_severities", &code_infos::<NoticeSeverity>());
+    context.insert("currency", currency.get_ref());
 
     // Add session data to template context
     if let Ok(Some(username)) = session.get::<String>("username") {
@@ -397,7 +3151,7 @@ pub async fn admin_dashboard(
 // Menu Presets Handlers
 
 pub async fn list_menu_presets(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
     session: actix_session::Session,
 ) -> Result<impl Responder, ApiErrorType> {
     // Check authentication
@@ -410,7 +3164,8 @@ pub async fn list_menu_presets(
 }
 
 pub async fn create_menu_preset(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
     session: actix_session::Session,
     preset_data: web::Json<CreateMenuPresetRequest>,
 ) -> Result<impl Responder, ApiErrorType> {
@@ -433,24 +3188,42 @@ pub async fn create_menu_preset(
         }
     }
 
+    let price_overrides =
+        parse_price_overrides(&preset_data.menu_item_ids, &preset_data.price_overrides)?;
+    check_par_levels(&preset_data.menu_item_ids, &preset_data.par_levels)?;
+
     let new_preset = MenuPreset {
         id: Uuid::new_v4(),
         name: preset_data.name.clone(),
         description: preset_data.description.clone(),
         menu_item_ids: preset_data.menu_item_ids.clone(),
+        price_overrides,
+        par_levels: preset_data.par_levels.clone(),
         created_at: Utc::now(),
         updated_at: Utc::now(),
+        revision: 0,
+        trashed_at: None,
     };
 
     storage
         .add_menu_preset(new_preset.clone())
         .map_err(ApiErrorType::Storage)?;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Create,
+        AuditEntity::MenuPreset,
+        new_preset.id,
+        None,
+        Some(&new_preset),
+    );
 
-    Ok(HttpResponse::Created().json(new_preset))
+    Ok(HttpResponse::Created()
+        .insert_header(etag(new_preset.revision))
+        .json(new_preset))
 }
 
 pub async fn get_menu_preset(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
     session: actix_session::Session,
     path: web::Path<Uuid>,
 ) -> Result<impl Responder, ApiErrorType> {
@@ -470,12 +3243,16 @@ pub async fn get_menu_preset(
             ApiErrorType::NotFound(format!("Menu preset with id {} not found", preset_id))
         })?;
 
-    Ok(HttpResponse::Ok().json(preset))
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(preset.revision))
+        .json(preset))
 }
 
 pub async fn update_menu_preset(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
     session: actix_session::Session,
+    req: HttpRequest,
     path: web::Path<Uuid>,
     update_data: web::Json<UpdateMenuPresetRequest>,
 ) -> Result<impl Responder, ApiErrorType> {
@@ -497,6 +3274,8 @@ pub async fn update_menu_preset(
         .ok_or_else(|| {
             ApiErrorType::NotFound(format!("Menu preset with id {} not found", preset_id))
         })?;
+    check_if_match(&req, "Menu preset", preset_id, existing_preset.revision)?;
+    let previous_preset = existing_preset.clone();
 
     // Validate menu item IDs if provided
     if let Some(menu_item_ids) = &update_data.menu_item_ids {
@@ -512,6 +3291,31 @@ pub async fn update_menu_preset(
         }
         existing_preset.menu_item_ids = menu_item_ids.clone();
     }
+    match &update_data.price_overrides {
+        Some(values) => {
+            existing_preset.price_overrides =
+                parse_price_overrides(&existing_preset.menu_item_ids, values)?;
+        }
+        // Overrides only ever cover items that are still in the preset
+        None => {
+            let menu_item_ids = &existing_preset.menu_item_ids;
+            existing_preset
+                .price_overrides
+                .retain(|item_id, _| menu_item_ids.contains(item_id));
+        }
+    }
+    match &update_data.par_levels {
+        Some(par_levels) => {
+            check_par_levels(&existing_preset.menu_item_ids, par_levels)?;
+            existing_preset.par_levels = par_levels.clone();
+        }
+        None => {
+            let menu_item_ids = &existing_preset.menu_item_ids;
+            existing_preset
+                .par_levels
+                .retain(|item_id, _| menu_item_ids.contains(item_id));
+        }
+    }
 
     // Update fields
     if let Some(name) = &update_data.name {
@@ -525,14 +3329,29 @@ pub async fn update_menu_preset(
     storage
         .update_menu_preset(preset_id, existing_preset.clone())
         .map_err(ApiErrorType::Storage)?;
+    // Storage bumped the stored revision on success
+    existing_preset.revision += 1;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Update,
+        AuditEntity::MenuPreset,
+        preset_id,
+        Some(&previous_preset),
+        Some(&existing_preset),
+    );
 
-    Ok(HttpResponse::Ok().json(existing_preset))
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(existing_preset.revision))
+        .json(existing_preset))
 }
 
 pub async fn delete_menu_preset(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
     session: actix_session::Session,
+    req: HttpRequest,
     path: web::Path<Uuid>,
+    query: web::Query<DeleteQuery>,
 ) -> Result<impl Responder, AppError> {
     // Check authentication
     let _user_id = require_auth(&session)
@@ -541,9 +3360,42 @@ pub async fn delete_menu_preset(
 
     let preset_id = path.into_inner();
 
-    storage
-        .delete_menu_preset(preset_id)
-        .map_err(|e| AppError::from(e))?;
+    // Honour If-Match if the preset exists; a missing preset is reported by storage
+    let presets = storage.get_menu_presets().map_err(AppError::from)?;
+    let existing_preset = presets.iter().find(|preset| preset.id == preset_id);
+    let expected_revision = match existing_preset {
+        Some(preset) => check_if_match(&req, "Menu preset", preset_id, preset.revision)?,
+        None => None,
+    };
+
+    let trashed_schedules = if query.cascade {
+        storage.delete_menu_preset_cascade(preset_id, expected_revision)
+    } else {
+        storage
+            .delete_menu_preset(preset_id, expected_revision)
+            .map(|_| Vec::new())
+    }
+    .map_err(AppError::from)?;
+
+    let actor = Actor::from_session(&session);
+    for schedule in &trashed_schedules {
+        audit.record(
+            &actor,
+            AuditAction::Delete,
+            AuditEntity::MenuSchedule,
+            schedule.id,
+            Some(schedule),
+            None,
+        );
+    }
+    audit.record(
+        &actor,
+        AuditAction::Delete,
+        AuditEntity::MenuPreset,
+        preset_id,
+        existing_preset,
+        None,
+    );
 
     Ok(HttpResponse::NoContent())
 }
@@ -551,7 +3403,7 @@ pub async fn delete_menu_preset(
 // Menu Schedules Handlers
 
 pub async fn list_menu_schedules(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
     session: actix_session::Session,
 ) -> Result<impl Responder, AppError> {
     // Check authentication
@@ -559,14 +3411,13 @@ pub async fn list_menu_schedules(
         .await
         .map_err(|e| AppError::Validation(format!("Authentication required: {}", e)))?;
 
-    let schedules = storage
-        .get_menu_schedules()
-        .map_err(|e| AppError::from(e))?;
+    let schedules = storage.get_menu_schedules().map_err(AppError::from)?;
     Ok(HttpResponse::Ok().json(schedules))
 }
 
 pub async fn create_menu_schedule(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
     session: actix_session::Session,
     schedule_data: web::Json<CreateMenuScheduleRequest>,
 ) -> Result<impl Responder, AppError> {
@@ -578,7 +3429,7 @@ pub async fn create_menu_schedule(
     use chrono::Utc;
 
     // Validate that preset exists
-    let presets = storage.get_menu_presets().map_err(|e| AppError::from(e))?;
+    let presets = storage.get_menu_presets().map_err(AppError::from)?;
 
     if !presets
         .iter()
@@ -608,9 +3459,7 @@ pub async fn create_menu_schedule(
     };
 
     // Check for schedule conflicts
-    let existing_schedules = storage
-        .get_menu_schedules()
-        .map_err(|e| AppError::from(e))?;
+    let existing_schedules = storage.get_menu_schedules().map_err(AppError::from)?;
 
     // Create a temporary schedule for conflict check
     let temp_schedule = MenuSchedule {
@@ -625,6 +3474,8 @@ pub async fn create_menu_schedule(
         error_message: None,
         created_at: Utc::now(),
         updated_at: Utc::now(),
+        revision: 0,
+        trashed_at: None,
     };
 
     if let Some(conflicting) =
@@ -648,17 +3499,29 @@ pub async fn create_menu_schedule(
         error_message: None,
         created_at: Utc::now(),
         updated_at: Utc::now(),
+        revision: 0,
+        trashed_at: None,
     };
 
     storage
         .add_menu_schedule(new_schedule.clone())
-        .map_err(|e| AppError::from(e))?;
+        .map_err(AppError::from)?;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Create,
+        AuditEntity::MenuSchedule,
+        new_schedule.id,
+        None,
+        Some(&new_schedule),
+    );
 
-    Ok(HttpResponse::Created().json(new_schedule))
+    Ok(HttpResponse::Created()
+        .insert_header(etag(new_schedule.revision))
+        .json(new_schedule))
 }
 
 pub async fn get_menu_schedule(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
     session: actix_session::Session,
     path: web::Path<Uuid>,
 ) -> Result<impl Responder, ApiErrorType> {
@@ -680,12 +3543,16 @@ pub async fn get_menu_schedule(
             ApiErrorType::NotFound(format!("Menu schedule with id {} not found", schedule_id))
         })?;
 
-    Ok(HttpResponse::Ok().json(schedule))
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(schedule.revision))
+        .json(schedule))
 }
 
 pub async fn update_menu_schedule(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
     session: actix_session::Session,
+    req: HttpRequest,
     path: web::Path<Uuid>,
     update_data: web::Json<UpdateMenuScheduleRequest>,
 ) -> Result<impl Responder, AppError> {
@@ -705,6 +3572,13 @@ pub async fn update_menu_schedule(
         .ok_or_else(|| {
             AppError::NotFound(format!("Menu schedule with id {} not found", schedule_id))
         })?;
+    check_if_match(
+        &req,
+        "Menu schedule",
+        schedule_id,
+        existing_schedule.revision,
+    )?;
+    let previous_schedule = existing_schedule.clone();
 
     // Validate preset_id if provided
     if let Some(preset_id) = update_data.preset_id {
@@ -773,14 +3647,28 @@ pub async fn update_menu_schedule(
 
     storage
         .update_menu_schedule(schedule_id, existing_schedule.clone())
-        .map_storage_err()?;
+        .map_err(AppError::from)?;
+    // Storage bumped the stored revision on success
+    existing_schedule.revision += 1;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Update,
+        AuditEntity::MenuSchedule,
+        schedule_id,
+        Some(&previous_schedule),
+        Some(&existing_schedule),
+    );
 
-    Ok(HttpResponse::Ok().json(existing_schedule))
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(existing_schedule.revision))
+        .json(existing_schedule))
 }
 
 pub async fn delete_menu_schedule(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
     session: actix_session::Session,
+    req: HttpRequest,
     path: web::Path<Uuid>,
 ) -> Result<impl Responder, AppError> {
     // Check authentication
@@ -788,15 +3676,31 @@ pub async fn delete_menu_schedule(
 
     let schedule_id = path.into_inner();
 
+    // Honour If-Match if the schedule exists; a missing schedule is reported by storage
+    let schedules = storage.get_menu_schedules().map_err(AppError::from)?;
+    let existing_schedule = schedules.iter().find(|s| s.id == schedule_id);
+    let expected_revision = match existing_schedule {
+        Some(schedule) => check_if_match(&req, "Menu schedule", schedule_id, schedule.revision)?,
+        None => None,
+    };
+
     storage
-        .delete_menu_schedule(schedule_id)
-        .map_storage_err()?;
+        .delete_menu_schedule(schedule_id, expected_revision)
+        .map_err(AppError::from)?;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Delete,
+        AuditEntity::MenuSchedule,
+        schedule_id,
+        existing_schedule,
+        None,
+    );
 
     Ok(HttpResponse::NoContent())
 }
 
 pub async fn get_upcoming_schedules(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
     session: actix_session::Session,
 ) -> Result<impl Responder, AppError> {
     // Check authentication
@@ -816,7 +3720,7 @@ pub async fn get_upcoming_schedules(
 }
 
 pub async fn validate_schedule(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
     session: actix_session::Session,
     validation_data: web::Json<ValidateScheduleRequest>,
 ) -> Result<impl Responder, AppError> {
@@ -843,21 +3747,21 @@ pub async fn validate_schedule(
     }
 
     // Validate name if provided
-    if let Some(name) = &validation_data.name {
-        if name.trim().is_empty() {
-            return Err(AppError::Validation(
-                "Schedule name cannot be empty".to_string(),
-            ));
-        }
+    if let Some(name) = &validation_data.name
+        && name.trim().is_empty()
+    {
+        return Err(AppError::Validation(
+            "Schedule name cannot be empty".to_string(),
+        ));
     }
 
     // Validate description if provided
-    if let Some(description) = &validation_data.description {
-        if description.trim().is_empty() {
-            return Err(AppError::Validation(
-                "Schedule description cannot be empty".to_string(),
-            ));
-        }
+    if let Some(description) = &validation_data.description
+        && description.trim().is_empty()
+    {
+        return Err(AppError::Validation(
+            "Schedule description cannot be empty".to_string(),
+        ));
     }
 
     // Validate recurrence if provided
@@ -876,103 +3780,576 @@ pub async fn validate_schedule(
         }
     }
 
-    // Check for schedule conflicts
-    let existing_schedules = storage.get_menu_schedules().map_storage_err()?;
+    // Check for schedule conflicts
+    let existing_schedules = storage.get_menu_schedules().map_storage_err()?;
+
+    // Create a temporary schedule for conflict check
+    let recurrence = if let Some(rec) = &validation_data.recurrence {
+        match rec.as_str() {
+            "Daily" => ScheduleRecurrence::Daily,
+            "Weekly" => ScheduleRecurrence::Weekly,
+            "Monthly" => ScheduleRecurrence::Monthly,
+            "Custom" => ScheduleRecurrence::Custom,
+            _ => return Err(AppError::Validation("Invalid recurrence value".to_string())),
+        }
+    } else {
+        ScheduleRecurrence::Custom // default
+    };
+
+    let status = if let Some(stat) = &validation_data.status {
+        match stat.as_str() {
+            "Active" => ScheduleStatus::Active,
+            "Ended" => ScheduleStatus::Ended,
+            "Pending" => ScheduleStatus::Pending,
+            _ => return Err(AppError::Validation("Invalid status value".to_string())),
+        }
+    } else {
+        ScheduleStatus::Pending // default
+    };
+
+    let temp_schedule = MenuSchedule {
+        id: validation_data.schedule_id.unwrap_or(Uuid::new_v4()),
+        preset_id: validation_data.preset_id.unwrap_or(Uuid::new_v4()), // dummy if not provided
+        name: validation_data.name.clone().unwrap_or_default(),
+        description: validation_data.description.clone().unwrap_or_default(),
+        start_time: validation_data.start_time,
+        end_time: validation_data.end_time,
+        recurrence,
+        status,
+        error_message: None,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        revision: 0,
+        trashed_at: None,
+    };
+
+    let conflicting = crate::scheduler::has_schedule_conflict(&temp_schedule, &existing_schedules);
+    let conflicts = if let Some(conf) = conflicting {
+        vec![conf.id]
+    } else {
+        Vec::new()
+    };
+
+    #[derive(Debug, Serialize)]
+    struct ValidationResponse {
+        is_valid: bool,
+        conflicts: Vec<Uuid>,
+        message: Option<String>,
+    }
+
+    let has_conflicts = !conflicts.is_empty();
+    let response = ValidationResponse {
+        is_valid: !has_conflicts,
+        conflicts,
+        message: if has_conflicts {
+            Some("Schedule conflicts with existing schedules".to_string())
+        } else {
+            None
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+// Trash Handlers
+
+pub async fn list_trash(
+    storage: web::Data<dyn Storage>,
+    session: actix_session::Session,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let trash = storage.get_trash().map_err(ApiErrorType::Storage)?;
+    Ok(HttpResponse::Ok().json(trash))
+}
+
+/// Move a record out of the trash. `kind` is the collection segment used by
+/// the regular API: `items`, `notices`, `presets`, `schedules`,
+/// `categories`, `ingredients` or `feedback`.
+pub async fn restore_from_trash(
+    storage: web::Data<dyn Storage>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    req: HttpRequest,
+    path: web::Path<(String, Uuid)>,
+) -> Result<HttpResponse, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let (kind, id) = path.into_inner();
+    let trash = storage.get_trash().map_err(ApiErrorType::Storage)?;
+    let not_in_trash = |label: &str| {
+        ApiErrorType::NotFound(format!("{} with id {} is not in the trash", label, id))
+    };
+    let actor = Actor::from_session(&session);
+
+    // Restoring is an ordinary update that clears `trashed_at`, so it is
+    // subject to the same revision check as any other edit
+    match kind.as_str() {
+        "items" => {
+            let mut item = trash
+                .menu_items
+                .into_iter()
+                .find(|item| item.id == id)
+                .ok_or_else(|| not_in_trash("Menu item"))?;
+            check_if_match(&req, "Menu item", id, item.revision)?;
+            let trashed = item.clone();
+            item.trashed_at = None;
+            storage
+                .update_menu_item(id, item.clone())
+                .map_err(ApiErrorType::from)?;
+            item.revision += 1;
+            audit.record(
+                &actor,
+                AuditAction::Restore,
+                AuditEntity::MenuItem,
+                id,
+                Some(&trashed),
+                Some(&item),
+            );
+            Ok(HttpResponse::Ok()
+                .insert_header(etag(item.revision))
+                .json(item))
+        }
+        "notices" => {
+            let mut notice = trash
+                .notices
+                .into_iter()
+                .find(|notice| notice.id == id)
+                .ok_or_else(|| not_in_trash("Notice"))?;
+            check_if_match(&req, "Notice", id, notice.revision)?;
+            let trashed = notice.clone();
+            notice.trashed_at = None;
+            notice.updated_at = Utc::now();
+            storage
+                .update_notice(id, notice.clone())
+                .map_err(ApiErrorType::from)?;
+            notice.revision += 1;
+            audit.record(
+                &actor,
+                AuditAction::Restore,
+                AuditEntity::Notice,
+                id,
+                Some(&trashed),
+                Some(&notice),
+            );
+            Ok(HttpResponse::Ok()
+                .insert_header(etag(notice.revision))
+                .json(notice))
+        }
+        "presets" => {
+            let mut preset = trash
+                .menu_presets
+                .into_iter()
+                .find(|preset| preset.id == id)
+                .ok_or_else(|| not_in_trash("Menu preset"))?;
+            check_if_match(&req, "Menu preset", id, preset.revision)?;
+            let trashed = preset.clone();
+            preset.trashed_at = None;
+            preset.updated_at = Utc::now();
+            storage
+                .update_menu_preset(id, preset.clone())
+                .map_err(ApiErrorType::from)?;
+            preset.revision += 1;
+            audit.record(
+                &actor,
+                AuditAction::Restore,
+                AuditEntity::MenuPreset,
+                id,
+                Some(&trashed),
+                Some(&preset),
+            );
+            Ok(HttpResponse::Ok()
+                .insert_header(etag(preset.revision))
+                .json(preset))
+        }
+        "schedules" => {
+            let mut schedule = trash
+                .menu_schedules
+                .into_iter()
+                .find(|schedule| schedule.id == id)
+                .ok_or_else(|| not_in_trash("Menu schedule"))?;
+            check_if_match(&req, "Menu schedule", id, schedule.revision)?;
+            let trashed = schedule.clone();
+            schedule.trashed_at = None;
+            schedule.updated_at = Utc::now();
+            storage
+                .update_menu_schedule(id, schedule.clone())
+                .map_err(ApiErrorType::from)?;
+            schedule.revision += 1;
+            audit.record(
+                &actor,
+                AuditAction::Restore,
+                AuditEntity::MenuSchedule,
+                id,
+                Some(&trashed),
+                Some(&schedule),
+            );
+            Ok(HttpResponse::Ok()
+                .insert_header(etag(schedule.revision))
+                .json(schedule))
+        }
+        "categories" => {
+            let mut category = trash
+                .menu_categories
+                .into_iter()
+                .find(|category| category.id == id)
+                .ok_or_else(|| not_in_trash("Menu category"))?;
+            check_if_match(&req, "Menu category", id, category.revision)?;
+            // Another category may have taken its name in the meantime
+            let categories = storage
+                .get_menu_categories()
+                .map_err(ApiErrorType::Storage)?;
+            validate_category(&categories, &category)?;
+            let trashed = category.clone();
+            category.trashed_at = None;
+            category.updated_at = Utc::now();
+            storage
+                .update_menu_category(id, category.clone())
+                .map_err(ApiErrorType::from)?;
+            category.revision += 1;
+            audit.record(
+                &actor,
+                AuditAction::Restore,
+                AuditEntity::MenuCategory,
+                id,
+                Some(&trashed),
+                Some(&category),
+            );
+            Ok(HttpResponse::Ok()
+                .insert_header(etag(category.revision))
+                .json(category))
+        }
+        "ingredients" => {
+            let mut ingredient = trash
+                .ingredients
+                .into_iter()
+                .find(|ingredient| ingredient.id == id)
+                .ok_or_else(|| not_in_trash("Ingredient"))?;
+            check_if_match(&req, "Ingredient", id, ingredient.revision)?;
+            // Another ingredient may have taken its name in the meantime
+            let ingredients = storage.get_ingredients().map_err(ApiErrorType::Storage)?;
+            validate_ingredient(&ingredients, &ingredient)?;
+            let trashed = ingredient.clone();
+            ingredient.trashed_at = None;
+            ingredient.updated_at = Utc::now();
+            storage
+                .update_ingredient(id, ingredient.clone())
+                .map_err(ApiErrorType::from)?;
+            ingredient.revision += 1;
+            audit.record(
+                &actor,
+                AuditAction::Restore,
+                AuditEntity::Ingredient,
+                id,
+                Some(&trashed),
+                Some(&ingredient),
+            );
+            Ok(HttpResponse::Ok()
+                .insert_header(etag(ingredient.revision))
+                .json(ingredient))
+        }
+        "feedback" => {
+            let mut feedback = trash
+                .feedback
+                .into_iter()
+                .find(|entry| entry.id == id)
+                .ok_or_else(|| not_in_trash("Feedback"))?;
+            check_if_match(&req, "Feedback", id, feedback.revision)?;
+            let trashed = feedback.clone();
+            feedback.trashed_at = None;
+            storage
+                .update_feedback(id, feedback.clone())
+                .map_err(ApiErrorType::from)?;
+            feedback.revision += 1;
+            audit.record(
+                &actor,
+                AuditAction::Restore,
+                AuditEntity::Feedback,
+                id,
+                Some(&trashed),
+                Some(&feedback),
+            );
+            Ok(HttpResponse::Ok()
+                .insert_header(etag(feedback.revision))
+                .json(feedback))
+        }
+        other => Err(ApiErrorType::NotFound(format!(
+            "Unknown trash collection '{}'; expected items, notices, presets, schedules, categories, ingredients or feedback",
+            other
+        ))),
+    }
+}
+
+// Audit Log Handler
+
+pub async fn list_audit_entries(
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    query: web::Query<AuditQuery>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let entries = audit.query(&query).map_err(ApiErrorType::Storage)?;
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+// Allergen Handlers
+
+pub async fn list_allergens() -> impl Responder {
+    HttpResponse::Ok().json(code_infos::<Allergen>())
+}
+
+pub async fn list_dietary_tags() -> impl Responder {
+    HttpResponse::Ok().json(code_infos::<DietaryTag>())
+}
+
+pub async fn list_portion_sizes() -> impl Responder {
+    HttpResponse::Ok().json(code_infos::<PortionSize>())
+}
+
+/// The currency prices are held in and the tiers they can be set for
+pub async fn pricing_info(currency: web::Data<Currency>) -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({
+        "currency": currency.get_ref(),
+        "tiers": code_infos::<PriceTier>(),
+    }))
+}
+
+/// Menu items still carrying free-text allergens the migration could not map
+pub async fn list_unmapped_allergens(
+    storage: web::Data<dyn Storage>,
+    session: actix_session::Session,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
+    let report: Vec<serde_json::Value> = items
+        .into_iter()
+        .filter(|item| !item.unmapped_allergens.is_empty())
+        .map(|item| {
+            serde_json::json!({
+                "id": item.id,
+                "name": item.name,
+                "unmapped_allergens": item.unmapped_allergens,
+            })
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// Snapshot Handlers
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CreateSnapshotRequest {
+    pub label: Option<String>,
+}
+
+pub async fn list_snapshots(
+    snapshots: web::Data<SnapshotStore>,
+    session: actix_session::Session,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let list = snapshots.list().map_err(ApiErrorType::Storage)?;
+    Ok(HttpResponse::Ok().json(list))
+}
+
+pub async fn create_snapshot(
+    storage: web::Data<dyn Storage>,
+    snapshots: web::Data<SnapshotStore>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    body: Option<web::Json<CreateSnapshotRequest>>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let label = body
+        .and_then(|body| body.into_inner().label)
+        .map(|label| label.trim().to_string())
+        .filter(|label| !label.is_empty());
+    let info = snapshots
+        .create(storage.get_ref(), label)
+        .map_err(ApiErrorType::Storage)?;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Create,
+        AuditEntity::Snapshot,
+        info.id,
+        None,
+        Some(&info),
+    );
+    Ok(HttpResponse::Created().json(info))
+}
+
+/// Replace all data with a snapshot. The data as it was just before is kept
+/// as a new snapshot, which the response names.
+pub async fn restore_snapshot(
+    storage: web::Data<dyn Storage>,
+    snapshots: web::Data<SnapshotStore>,
+    audit: web::Data<AuditLog>,
+    session: actix_session::Session,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    let id = path.into_inner();
+    let outcome = snapshots
+        .restore(storage.get_ref(), id)
+        .map_err(ApiErrorType::Storage)?;
+    audit.record(
+        &Actor::from_session(&session),
+        AuditAction::Restore,
+        AuditEntity::Snapshot,
+        id,
+        None,
+        Some(&outcome),
+    );
+    Ok(HttpResponse::Ok().json(outcome))
+}
 
-    // Create a temporary schedule for conflict check
-    let recurrence = if let Some(rec) = &validation_data.recurrence {
-        match rec.as_str() {
-            "Daily" => ScheduleRecurrence::Daily,
-            "Weekly" => ScheduleRecurrence::Weekly,
-            "Monthly" => ScheduleRecurrence::Monthly,
-            "Custom" => ScheduleRecurrence::Custom,
-            _ => return Err(AppError::Validation("Invalid recurrence value".to_string())),
-        }
-    } else {
-        ScheduleRecurrence::Custom // default
-    };
+// Consistency Check Handler
 
-    let status = if let Some(stat) = &validation_data.status {
-        match stat.as_str() {
-            "Active" => ScheduleStatus::Active,
-            "Ended" => ScheduleStatus::Ended,
-            "Pending" => ScheduleStatus::Pending,
-            _ => return Err(AppError::Validation("Invalid status value".to_string())),
-        }
-    } else {
-        ScheduleStatus::Pending // default
-    };
+pub async fn check_integrity(
+    storage: web::Data<dyn Storage>,
+    session: actix_session::Session,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
 
-    let temp_schedule = MenuSchedule {
-        id: validation_data.schedule_id.unwrap_or(Uuid::new_v4()),
-        preset_id: validation_data.preset_id.unwrap_or(Uuid::new_v4()), // dummy if not provided
-        name: validation_data.name.clone().unwrap_or_default(),
-        description: validation_data.description.clone().unwrap_or_default(),
-        start_time: validation_data.start_time,
-        end_time: validation_data.end_time,
-        recurrence,
-        status,
-        error_message: None,
-        created_at: Utc::now(),
-        updated_at: Utc::now(),
-    };
+    let dangling = storage
+        .find_dangling_references()
+        .map_err(ApiErrorType::Storage)?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "consistent": dangling.is_empty(),
+        "dangling_references": dangling,
+    })))
+}
 
-    let conflicting = crate::scheduler::has_schedule_conflict(&temp_schedule, &existing_schedules);
-    let conflicts = if let Some(conf) = conflicting {
-        vec![conf.id]
-    } else {
-        Vec::new()
-    };
+/// Items listed under one category heading on the menu
+#[derive(Debug, Serialize)]
+pub struct MenuSection<'a> {
+    /// `None` for items whose category no longer exists
+    pub category: Option<&'a MenuCategory>,
+    pub items: Vec<&'a MenuItem>,
+}
 
-    #[derive(Debug, Serialize)]
-    struct ValidationResponse {
-        is_valid: bool,
-        conflicts: Vec<Uuid>,
-        message: Option<String>,
+/// Group items by category in display order, leaving out empty categories
+fn menu_sections<'a>(
+    categories: &'a [MenuCategory],
+    items: &[&'a MenuItem],
+) -> Vec<MenuSection<'a>> {
+    let mut sections: Vec<MenuSection> = categories
+        .iter()
+        .map(|category| MenuSection {
+            category: Some(category),
+            items: items
+                .iter()
+                .copied()
+                .filter(|item| item.category_id == category.id)
+                .collect(),
+        })
+        .filter(|section| !section.items.is_empty())
+        .collect();
+    let uncategorized: Vec<&MenuItem> = items
+        .iter()
+        .copied()
+        .filter(|item| !categories.iter().any(|c| c.id == item.category_id))
+        .collect();
+    if !uncategorized.is_empty() {
+        sections.push(MenuSection {
+            category: None,
+            items: uncategorized,
+        });
     }
-
-    let has_conflicts = !conflicts.is_empty();
-    let response = ValidationResponse {
-        is_valid: !has_conflicts,
-        conflicts,
-        message: if has_conflicts {
-            Some("Schedule conflicts with existing schedules".to_string())
-        } else {
-            None
-        },
-    };
-
-    Ok(HttpResponse::Ok().json(response))
+    sections
 }
 
 // Public Menu Display Handler
 pub async fn menu_page(
     req: HttpRequest,
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
     tera: web::Data<Tera>,
+    locales: web::Data<Locales>,
+    currency: web::Data<Currency>,
 ) -> Result<HttpResponse, ApiErrorType> {
     println!("DEBUG: menu_page handler called");
 
     // Log the referrer for analytics
-    if let Some(referrer) = req.headers().get("Referer") {
-        if let Ok(referrer_str) = referrer.to_str() {
-            println!("DEBUG: Referrer: {}", referrer_str);
-        }
+    if let Some(referrer) = req.headers().get("Referer")
+        && let Ok(referrer_str) = referrer.to_str()
+    {
+        println!("DEBUG: Referrer: {}", referrer_str);
     }
 
-    // Get menu items and filter for available ones
-    let menu_items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
-    let available_menu_items: Vec<&MenuItem> =
-        menu_items.iter().filter(|item| item.is_available).collect();
+    let filter = MenuFilter::from_query(req.query_string())?;
+    let (mut context, locale) = page_context(&locales, &req);
+    let notices = PublicNotices::load(storage.get_ref(), &locale).map_err(ApiErrorType::Storage)?;
+    context.insert("critical_notices", &notices.critical);
+    if notices.emergency {
+        return emergency_page(&tera, &context);
+    }
 
-    // Get notices and filter for active ones
-    let notices = storage.get_notices().map_err(ApiErrorType::Storage)?;
-    let active_notices: Vec<&Notice> = notices.iter().filter(|notice| notice.is_active).collect();
+    // Get menu items and filter for available ones the diner asked for
+    let menu_items: Vec<MenuItem> = storage
+        .get_menu_items()
+        .map_err(ApiErrorType::Storage)?
+        .into_iter()
+        .map(|item| item.localized(&locale))
+        .collect();
+    let available_menu_items: Vec<&MenuItem> = menu_items
+        .iter()
+        .filter(|item| item.is_available && filter.matches(item))
+        .collect();
+    let categories = storage
+        .get_menu_categories()
+        .map_err(ApiErrorType::Storage)?;
+    let sections = menu_sections(&categories, &available_menu_items);
+    let prices: std::collections::HashMap<Uuid, Vec<PriceLine>> = available_menu_items
+        .iter()
+        .map(|item| (item.id, price_lines(item, &currency)))
+        .collect();
+    // Diners only see the sizes they can order
+    let variants: std::collections::HashMap<Uuid, Vec<VariantLine>> = available_menu_items
+        .iter()
+        .map(|item| {
+            let mut lines = variant_lines(item, &currency);
+            lines.retain(|line| line.is_available);
+            (item.id, lines)
+        })
+        .collect();
 
     // Prepare context for template
-    let mut context = tera::Context::new();
-    context.insert("menu_items", &available_menu_items);
-    context.insert("notices", &active_notices);
+    context.insert("sections", &sections);
+    context.insert("prices", &prices);
+    context.insert("variants", &variants);
+    context.insert("notices", &notices.others);
+    context.insert("filter", &filter);
+    context.insert("filtering", &!filter.is_empty());
+    context.insert("allergens", &code_infos::<Allergen>());
+    context.insert("dietary_tags", &code_infos::<DietaryTag>());
 
     // Render the template
     let rendered = tera
@@ -982,11 +4359,16 @@ pub async fn menu_page(
     Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
+/// How many of the newest approved comments an item page shows
+const ITEM_PAGE_COMMENTS: usize = 5;
+
 // Individual Menu Item Page Handler
 pub async fn menu_item_page(
     req: HttpRequest,
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
     tera: web::Data<Tera>,
+    locales: web::Data<Locales>,
+    currency: web::Data<Currency>,
     path: web::Path<Uuid>,
 ) -> Result<HttpResponse, ApiErrorType> {
     let item_id = path.into_inner();
@@ -1004,12 +4386,66 @@ pub async fn menu_item_page(
     );
 
     let menu_items = storage.get_menu_items().map_err(ApiErrorType::Storage)?;
-    let item = menu_items.iter().find(|item| item.id == item_id);
+    let item = menu_items.into_iter().find(|item| item.id == item_id);
 
     if let Some(item) = item {
-        let mut context = tera::Context::new();
+        let categories = storage
+            .get_menu_categories()
+            .map_err(ApiErrorType::Storage)?;
+        let (mut context, locale) = page_context(&locales, &req);
+        let notices =
+            PublicNotices::load(storage.get_ref(), &locale).map_err(ApiErrorType::Storage)?;
+        context.insert("critical_notices", &notices.critical);
+        if notices.emergency {
+            return emergency_page(&tera, &context);
+        }
+        let item = item.localized(&locale);
         context.insert("item", &item);
+        context.insert(
+            "category",
+            &categories
+                .iter()
+                .find(|category| category.id == item.category_id),
+        );
         context.insert("referrer", &referrer);
+        context.insert("prices", &price_lines(&item, &currency));
+        context.insert("variants", &variant_lines(&item, &currency));
+
+        let feedback: Vec<Feedback> = storage
+            .get_feedback()
+            .map_err(ApiErrorType::Storage)?
+            .into_iter()
+            .filter(|entry| entry.menu_item_id == item_id)
+            .collect();
+        context.insert("ratings", &RatingSummary::of(&feedback));
+        let comments: Vec<&Feedback> = feedback
+            .iter()
+            .rev()
+            .filter(|entry| entry.status == ModerationStatus::Approved && entry.comment.is_some())
+            .take(ITEM_PAGE_COMMENTS)
+            .collect();
+        context.insert("comments", &comments);
+        context.insert(
+            "rating_choices",
+            &(MIN_RATING..=MAX_RATING).rev().collect::<Vec<_>>(),
+        );
+        context.insert("max_comment_chars", &MAX_COMMENT_CHARS);
+        // Set by the redirect after the rating form is sent
+        let outcome = form_urlencoded::parse(req.query_string().as_bytes())
+            .find(|(key, _)| key == "feedback")
+            .map(|(_, value)| value.into_owned());
+        context.insert("feedback_outcome", &outcome);
+
+        if let Some(nutrition) = &item.nutrition {
+            context.insert(
+                "nutrition",
+                &serde_json::json!({
+                    "serving_size_g": nutrition.serving_size_g,
+                    "per_serving": nutrition.per_serving(),
+                    "per_100g": nutrition.per_100g(),
+                }),
+            );
+        }
 
         let rendered = tera
             .render("item_detail.html", &context)
@@ -1026,9 +4462,11 @@ pub async fn menu_item_page(
 
 // Menu Schedules Page Handler
 pub async fn menu_schedules_page(
-    storage: web::Data<JsonStorage>,
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
     session: actix_session::Session,
     tera: web::Data<Tera>,
+    locales: web::Data<Locales>,
 ) -> Result<HttpResponse, ApiErrorType> {
     println!("DEBUG: menu_schedules_page handler called");
 
@@ -1047,7 +4485,7 @@ pub async fn menu_schedules_page(
         .map_err(ApiErrorType::Storage)?;
 
     // Prepare context for template
-    let mut context = tera::Context::new();
+    let (mut context, _) = page_context(&locales, &req);
     context.insert("presets", &presets);
     context.insert("schedules", &schedules);
 
@@ -1074,7 +4512,7 @@ pub async fn menu_schedules_page(
 
 // Reload Handlers
 pub async fn reload_menu_items(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
     session: actix_session::Session,
 ) -> Result<impl Responder, ApiErrorType> {
     // Check authentication
@@ -1092,7 +4530,7 @@ pub async fn reload_menu_items(
 }
 
 pub async fn reload_notices(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
     session: actix_session::Session,
 ) -> Result<impl Responder, ApiErrorType> {
     // Check authentication
@@ -1110,7 +4548,7 @@ pub async fn reload_notices(
 }
 
 pub async fn reload_admin_users(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
     session: actix_session::Session,
 ) -> Result<impl Responder, ApiErrorType> {
     // Check authentication
@@ -1128,7 +4566,7 @@ pub async fn reload_admin_users(
 }
 
 pub async fn reload_menu_presets(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
     session: actix_session::Session,
 ) -> Result<impl Responder, ApiErrorType> {
     // Check authentication
@@ -1146,7 +4584,7 @@ pub async fn reload_menu_presets(
 }
 
 pub async fn reload_menu_schedules(
-    storage: web::Data<JsonStorage>,
+    storage: web::Data<dyn Storage>,
     session: actix_session::Session,
 ) -> Result<impl Responder, ApiErrorType> {
     // Check authentication
@@ -1164,10 +4602,66 @@ pub async fn reload_menu_schedules(
         "message": "Menu schedules reloaded successfully"
     })))
 }
+
+pub async fn reload_menu_categories(
+    storage: web::Data<dyn Storage>,
+    session: actix_session::Session,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    // Reload menu categories from storage
+    storage
+        .load_menu_categories()
+        .map_err(ApiErrorType::Storage)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "message": "Menu categories reloaded successfully"
+    })))
+}
+pub async fn reload_ingredients(
+    storage: web::Data<dyn Storage>,
+    session: actix_session::Session,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    // Reload ingredients from storage
+    storage.load_ingredients().map_err(ApiErrorType::Storage)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "message": "Ingredients reloaded successfully"
+    })))
+}
+pub async fn reload_feedback(
+    storage: web::Data<dyn Storage>,
+    session: actix_session::Session,
+) -> Result<impl Responder, ApiErrorType> {
+    // Check authentication
+    let _user_id = require_auth(&session)
+        .await
+        .map_err(|e| ApiErrorType::Validation(format!("Authentication required: {}", e)))?;
+
+    // Reload feedback from storage
+    storage.load_feedback().map_err(ApiErrorType::Storage)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "message": "Feedback reloaded successfully"
+    })))
+}
 pub async fn menu_presets_page(
-    storage: web::Data<JsonStorage>,
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
     session: actix_session::Session,
     tera: web::Data<Tera>,
+    locales: web::Data<Locales>,
 ) -> Result<HttpResponse, ApiErrorType> {
     println!("DEBUG: menu_presets_page handler called");
 
@@ -1184,7 +4678,7 @@ pub async fn menu_presets_page(
     let presets = storage.get_menu_presets().map_err(ApiErrorType::Storage)?;
 
     // Prepare context for template
-    let mut context = tera::Context::new();
+    let (mut context, _) = page_context(&locales, &req);
     context.insert("menu_items", &menu_items);
     context.insert("presets", &presets);
 
@@ -1209,9 +4703,16 @@ pub async fn menu_presets_page(
 // 404 Not Found Page Handler
 pub async fn not_found_page(
     tmpl: web::Data<Tera>,
+    storage: web::Data<dyn Storage>,
+    locales: web::Data<Locales>,
     req: HttpRequest,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let mut context = tera::Context::new();
+    let (mut context, locale) = page_context(&locales, &req);
+    // A storage problem should not turn a 404 into a 500
+    match PublicNotices::load(storage.get_ref(), &locale) {
+        Ok(notices) => context.insert("critical_notices", &notices.critical),
+        Err(e) => log::error!("Failed to load notices for the 404 page: {}", e),
+    }
     let path = req.path().to_string();
     let referrer = req
         .headers()
@@ -1231,3 +4732,572 @@ pub async fn not_found_page(
 
     Ok(HttpResponse::NotFound().body(s))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use actix_session::SessionMiddleware;
+    use actix_session::storage::CookieSessionStore;
+    use actix_web::cookie::Key;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{App, ResponseError};
+
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn notice(title: &str) -> Notice {
+        let now = Utc::now();
+        Notice {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            content: String::new(),
+            is_active: true,
+            created_at: now,
+            updated_at: now,
+            revision: 0,
+            trashed_at: None,
+            translations: Translations::new(),
+            publish_at: None,
+            expire_at: None,
+            severity: NoticeSeverity::Info,
+            pinned: false,
+            emergency: false,
+        }
+    }
+
+    #[test]
+    fn if_match_is_optional() {
+        let req = TestRequest::default().to_http_request();
+
+        assert_eq!(
+            check_if_match(&req, "Notice", Uuid::new_v4(), 3).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn if_match_accepts_current_revision_or_any() {
+        for value in ["\"3\"", "\"2\", \"3\"", "*"] {
+            let req = TestRequest::default()
+                .insert_header((header::IF_MATCH, value))
+                .to_http_request();
+
+            assert_eq!(
+                check_if_match(&req, "Notice", Uuid::new_v4(), 3).unwrap(),
+                Some(3),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn if_match_rejects_stale_or_weak_tags() {
+        for value in ["\"2\"", "W/\"3\"", "garbage"] {
+            let req = TestRequest::default()
+                .insert_header((header::IF_MATCH, value))
+                .to_http_request();
+
+            assert!(
+                matches!(
+                    check_if_match(&req, "Notice", Uuid::new_v4(), 3),
+                    Err(StorageError::PreconditionFailed(_))
+                ),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn storage_errors_map_to_status_codes() {
+        let status = |error: StorageError| ApiErrorType::from(error).error_response().status();
+
+        assert_eq!(
+            status(StorageError::PreconditionFailed(String::new())),
+            StatusCode::PRECONDITION_FAILED
+        );
+        assert_eq!(
+            status(StorageError::Conflict(String::new())),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            status(StorageError::NotFound(String::new())),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    fn item(allergens: &[Allergen], may_contain: &[Allergen], tags: &[DietaryTag]) -> MenuItem {
+        MenuItem {
+            id: Uuid::new_v4(),
+            name: "Soup".to_string(),
+            category_id: Uuid::new_v4(),
+            description: String::new(),
+            allergens: allergens.to_vec(),
+            may_contain: may_contain.to_vec(),
+            unmapped_allergens: Vec::new(),
+            dietary_tags: tags.to_vec(),
+            is_available: true,
+            revision: 0,
+            trashed_at: None,
+            nutrition: None,
+            prices: Prices::new(),
+            preset_prices: Prices::new(),
+            image: None,
+            recipe: Vec::new(),
+            translations: Translations::new(),
+            stock: None,
+            variants: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn menu_filter_reads_repeated_and_comma_separated_values() {
+        let filter = MenuFilter::from_query(
+            "tags=vegan&tags=Gluten-free&exclude_allergens=Milk,%20sesame,&allergens=",
+        )
+        .unwrap();
+
+        assert_eq!(filter.tags, [DietaryTag::Vegan, DietaryTag::GlutenFree]);
+        assert_eq!(filter.exclude_allergens, [Allergen::Milk, Allergen::Sesame]);
+        assert!(filter.allergens.is_empty());
+        assert!(filter.exclude_tags.is_empty());
+        assert!(MenuFilter::from_query("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn menu_filter_rejects_unknown_codes() {
+        assert!(matches!(
+            MenuFilter::from_query("tags=Pescatarian"),
+            Err(ApiErrorType::Validation(_))
+        ));
+        assert!(matches!(
+            MenuFilter::from_query("exclude_allergens=Milk,Dairy"),
+            Err(ApiErrorType::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn menu_filter_excludes_traces_too() {
+        let filter = MenuFilter::from_query("exclude_allergens=Sesame").unwrap();
+
+        assert!(filter.matches(&item(&[Allergen::Milk], &[], &[])));
+        assert!(!filter.matches(&item(&[Allergen::Sesame], &[], &[])));
+        assert!(!filter.matches(&item(&[], &[Allergen::Sesame], &[])));
+    }
+
+    #[test]
+    fn menu_filter_needs_every_tag_and_allergen_asked_for() {
+        let filter = MenuFilter::from_query("tags=Vegan,Halal&exclude_tags=Kosher").unwrap();
+        let vegan_halal = [DietaryTag::Vegan, DietaryTag::Halal];
+
+        assert!(filter.matches(&item(&[], &[], &vegan_halal)));
+        assert!(!filter.matches(&item(&[], &[], &[DietaryTag::Vegan])));
+        assert!(!filter.matches(&item(
+            &[],
+            &[],
+            &[DietaryTag::Vegan, DietaryTag::Halal, DietaryTag::Kosher]
+        )));
+
+        let filter = MenuFilter::from_query("allergens=Milk,Eggs").unwrap();
+        assert!(filter.matches(&item(&[Allergen::Eggs, Allergen::Milk], &[], &[])));
+        assert!(!filter.matches(&item(&[Allergen::Milk], &[Allergen::Eggs], &[])));
+    }
+
+    #[test]
+    fn vegan_implies_vegetarian_and_excludes_animal_allergens() {
+        assert_eq!(
+            with_implied_tags(vec![DietaryTag::Vegan]),
+            [DietaryTag::Vegetarian, DietaryTag::Vegan]
+        );
+        assert!(check_dietary_tags(&[DietaryTag::Vegan], &[Allergen::Soya]).is_ok());
+        assert!(matches!(
+            check_dietary_tags(&[DietaryTag::Vegan], &[Allergen::Milk]),
+            Err(ApiErrorType::Validation(_))
+        ));
+    }
+
+    fn nutrition(serving_size_g: f64, energy_kcal: f64) -> Nutrition {
+        Nutrition {
+            serving_size_g,
+            energy_kcal,
+            fat_g: 10.0,
+            saturates_g: 4.0,
+            carbohydrate_g: 30.0,
+            sugars_g: 5.0,
+            protein_g: 12.0,
+            salt_g: 1.0,
+        }
+    }
+
+    #[test]
+    fn plausible_nutrition_is_accepted() {
+        assert!(validate_nutrition(&nutrition(250.0, 400.0)).is_ok());
+    }
+
+    #[test]
+    fn impossible_nutrition_is_rejected() {
+        let rejected = |change: fn(&mut Nutrition)| {
+            let mut facts = nutrition(250.0, 400.0);
+            change(&mut facts);
+            matches!(validate_nutrition(&facts), Err(ApiErrorType::Validation(_)))
+        };
+
+        assert!(rejected(|n| n.protein_g = -1.0));
+        assert!(rejected(|n| n.energy_kcal = f64::NAN));
+        assert!(rejected(|n| n.salt_g = f64::INFINITY));
+        assert!(rejected(|n| n.serving_size_g = 0.0));
+        assert!(rejected(|n| n.saturates_g = 11.0));
+        assert!(rejected(|n| n.sugars_g = 31.0));
+        // 10 + 30 + 12 + 1 = 53 g of nutrients in a 50 g serving
+        assert!(rejected(|n| n.serving_size_g = 50.0));
+    }
+
+    #[test]
+    fn calorie_filter_skips_items_without_nutrition() {
+        let filter = MenuFilter::from_query("min_calories=300&max_calories=500").unwrap();
+        let with_energy = |kcal: f64| {
+            let mut soup = item(&[], &[], &[]);
+            soup.nutrition = Some(nutrition(250.0, kcal));
+            soup
+        };
+
+        assert!(filter.matches(&with_energy(400.0)));
+        assert!(filter.matches(&with_energy(500.0)));
+        assert!(!filter.matches(&with_energy(250.0)));
+        assert!(!filter.matches(&with_energy(650.0)));
+        assert!(!filter.matches(&item(&[], &[], &[])));
+        assert!(
+            MenuFilter::from_query("")
+                .unwrap()
+                .matches(&item(&[], &[], &[]))
+        );
+    }
+
+    #[test]
+    fn calorie_filter_rejects_bad_bounds() {
+        for query in [
+            "max_calories=lots",
+            "min_calories=-5",
+            "min_calories=600&max_calories=400",
+        ] {
+            assert!(
+                matches!(
+                    MenuFilter::from_query(query),
+                    Err(ApiErrorType::Validation(_))
+                ),
+                "{}",
+                query
+            );
+        }
+    }
+
+    /// Stand-in for the login handler: puts an admin into the session
+    async fn log_in(session: actix_session::Session) -> HttpResponse {
+        session.insert("user_id", Uuid::new_v4()).unwrap();
+        session.insert("username", "admin").unwrap();
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_web::test]
+    async fn stale_update_is_refused_with_412() {
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let existing = notice("Closed Monday");
+        storage.add_notice(existing.clone()).unwrap();
+        let app = test::init_service(
+            App::new()
+                .wrap(SessionMiddleware::new(
+                    CookieSessionStore::default(),
+                    Key::generate(),
+                ))
+                .app_data(web::Data::from(storage.clone()))
+                .app_data(web::Data::new(AuditLog::in_memory()))
+                .route("/login", web::post().to(log_in))
+                .route("/api/notices/{id}", web::put().to(update_notice)),
+        )
+        .await;
+        let login = test::call_service(&app, TestRequest::post().uri("/login").to_request()).await;
+        let cookie = login.response().cookies().next().unwrap().into_owned();
+        let update = |if_match: &'static str, title: &'static str| {
+            TestRequest::put()
+                .uri(&format!("/api/notices/{}", existing.id))
+                .cookie(cookie.clone())
+                .insert_header((header::IF_MATCH, if_match))
+                .set_json(serde_json::json!({ "title": title }))
+                .to_request()
+        };
+
+        let response = test::call_service(&app, update("\"0\"", "Closed Tuesday")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(header::ETAG).unwrap(), "\"1\"");
+
+        let response = test::call_service(&app, update("\"0\"", "Closed Friday")).await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        let stored = &storage.get_notices().unwrap()[0];
+        assert_eq!(stored.title, "Closed Tuesday");
+        assert_eq!(stored.revision, 1);
+    }
+
+    #[actix_web::test]
+    async fn notice_changes_require_a_session() {
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let existing = notice("Closed Monday");
+        storage.add_notice(existing.clone()).unwrap();
+        let app = test::init_service(
+            App::new()
+                .wrap(SessionMiddleware::new(
+                    CookieSessionStore::default(),
+                    Key::generate(),
+                ))
+                .app_data(web::Data::from(storage.clone()))
+                .app_data(web::Data::new(AuditLog::in_memory()))
+                .route("/api/notices/{id}", web::put().to(update_notice))
+                .route("/api/notices/{id}", web::delete().to(delete_notice)),
+        )
+        .await;
+        let uri = format!("/api/notices/{}", existing.id);
+
+        let update = TestRequest::put()
+            .uri(&uri)
+            .set_json(serde_json::json!({ "title": "Closed Tuesday" }))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, update).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+        let delete = TestRequest::delete().uri(&uri).to_request();
+        assert_eq!(
+            test::call_service(&app, delete).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+
+        let stored = &storage.get_notices().unwrap()[0];
+        assert_eq!(stored.title, "Closed Monday");
+        assert_eq!(stored.revision, 0);
+    }
+
+    fn category(name: &str) -> MenuCategory {
+        let now = Utc::now();
+        MenuCategory {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            icon: String::new(),
+            display_order: 0,
+            created_at: now,
+            updated_at: now,
+            revision: 0,
+            trashed_at: None,
+        }
+    }
+
+    #[test]
+    fn category_names_must_be_unique_ignoring_case() {
+        let mains = category("Mains");
+        let existing = [mains.clone(), category("Sides")];
+
+        assert!(validate_category(&existing, &category("MAINS")).is_err());
+        assert!(validate_category(&existing, &category("")).is_err());
+        // Saving a category under its own name is not a clash
+        assert!(validate_category(&existing, &mains).is_ok());
+    }
+
+    #[test]
+    fn category_icons_are_kept_short() {
+        let mut bakes = category("Bakes");
+        bakes.icon = "🥐".repeat(MAX_ICON_CHARS);
+        assert!(validate_category(&[], &bakes).is_ok());
+
+        bakes.icon.push('🥐');
+        assert!(validate_category(&[], &bakes).is_err());
+    }
+
+    #[test]
+    fn menu_sections_follow_category_order_with_orphans_last() {
+        let categories = [category("Mains"), category("Sides"), category("Desserts")];
+        let mut soup = item(&[], &[], &[]);
+        soup.category_id = categories[2].id;
+        let mut pie = item(&[], &[], &[]);
+        pie.category_id = categories[0].id;
+        let orphan = item(&[], &[], &[]);
+
+        let sections = menu_sections(&categories, &[&soup, &orphan, &pie]);
+
+        let shape: Vec<(Option<&str>, usize)> = sections
+            .iter()
+            .map(|s| (s.category.map(|c| c.name.as_str()), s.items.len()))
+            .collect();
+        assert_eq!(
+            shape,
+            [(Some("Mains"), 1), (Some("Desserts"), 1), (None, 1)]
+        );
+    }
+
+    #[actix_web::test]
+    async fn stock_is_counted_down_to_sold_out() {
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let mut soup = item(&[], &[], &[]);
+        soup.category_id = storage.get_menu_categories().unwrap()[0].id;
+        soup.stock = Some(3);
+        let mut bread = item(&[], &[], &[]);
+        bread.category_id = soup.category_id;
+        storage.add_menu_item(soup.clone()).unwrap();
+        storage.add_menu_item(bread.clone()).unwrap();
+        let app = test::init_service(
+            App::new()
+                .wrap(SessionMiddleware::new(
+                    CookieSessionStore::default(),
+                    Key::generate(),
+                ))
+                .app_data(web::Data::from(storage.clone()))
+                .app_data(web::Data::new(AuditLog::in_memory()))
+                .route("/login", web::post().to(log_in))
+                .route(
+                    "/api/items/{id}/stock/decrement",
+                    web::post().to(decrement_menu_item_stock),
+                ),
+        )
+        .await;
+        let login = test::call_service(&app, TestRequest::post().uri("/login").to_request()).await;
+        let cookie = login.response().cookies().next().unwrap().into_owned();
+        let serve = |id: Uuid, portions: u32| {
+            TestRequest::post()
+                .uri(&format!(
+                    "/api/items/{}/stock/decrement?portions={}",
+                    id, portions
+                ))
+                .cookie(cookie.clone())
+                .to_request()
+        };
+
+        let response = test::call_service(&app, serve(soup.id, 4)).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = test::call_service(&app, serve(soup.id, 3)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(storage.get_menu_items().unwrap()[0].stock, Some(0));
+        let response = test::call_service(&app, serve(soup.id, 1)).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        // Items that don't count stock can't be counted down
+        let response = test::call_service(&app, serve(bread.id, 1)).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    fn variant_request(name: &str, size: &str) -> VariantRequest {
+        VariantRequest {
+            id: None,
+            name: name.to_string(),
+            size: size.to_string(),
+            prices: BTreeMap::new(),
+            nutrition: None,
+            is_available: true,
+        }
+    }
+
+    #[test]
+    fn variants_are_sorted_by_size_and_keep_their_ids() {
+        let existing = parse_variants(&[], &[variant_request("Regular", "regular")]).unwrap();
+        let mut regular = variant_request("Regular", "Regular");
+        regular.id = Some(existing[0].id);
+        let mut large = variant_request("Large", "large");
+        large.prices = BTreeMap::from([("staff".to_string(), 320)]);
+
+        let variants = parse_variants(
+            &existing,
+            &[large, variant_request("Small", "small"), regular],
+        )
+        .unwrap();
+
+        let names: Vec<&str> = variants.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["Small", "Regular", "Large"]);
+        assert_eq!(variants[1].id, existing[0].id);
+        assert_eq!(variants[2].prices, Prices::from([(PriceTier::Staff, 320)]));
+    }
+
+    #[test]
+    fn variants_must_have_distinct_names_and_known_ids() {
+        let existing = parse_variants(&[], &[variant_request("Regular", "Regular")]).unwrap();
+        let mut unknown = variant_request("Large", "Large");
+        unknown.id = Some(Uuid::new_v4());
+        let mut repeated = variant_request("Large", "Large");
+        repeated.id = Some(existing[0].id);
+        let mut again = variant_request("Small", "Small");
+        again.id = Some(existing[0].id);
+
+        for variants in [
+            vec![
+                variant_request("Cup", "Small"),
+                variant_request(" cup", "Large"),
+            ],
+            vec![variant_request(" ", "Small")],
+            vec![variant_request("Bucket", "Enormous")],
+            vec![unknown],
+            vec![repeated, again],
+        ] {
+            assert!(
+                matches!(
+                    parse_variants(&existing, &variants),
+                    Err(ApiErrorType::Validation(_))
+                ),
+                "{:?} should be refused",
+                variants.iter().map(|v| &v.name).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn variant_prices_fall_back_to_the_item() {
+        let mut coffee = item(&[], &[], &[]);
+        coffee.prices = Prices::from([(PriceTier::Student, 150), (PriceTier::Staff, 200)]);
+        coffee.preset_prices = Prices::from([(PriceTier::Staff, 180)]);
+        let mut large = parse_variants(&[], &[variant_request("Large", "Large")]).unwrap();
+        large[0].prices = Prices::from([(PriceTier::Student, 190)]);
+
+        assert_eq!(
+            coffee.variant_prices(&large[0]),
+            Prices::from([(PriceTier::Student, 190), (PriceTier::Staff, 180)])
+        );
+    }
+
+    #[test]
+    fn notice_window_must_end_after_it_starts() {
+        let mut closure = notice("Closed for the holidays");
+        let start = Utc::now();
+        closure.publish_at = Some(start);
+        closure.expire_at = Some(start);
+        assert!(validate_notice(&closure).is_err());
+
+        closure.expire_at = Some(start + chrono::Duration::days(1));
+        assert!(validate_notice(&closure).is_ok());
+        closure.publish_at = None;
+        assert!(validate_notice(&closure).is_ok());
+    }
+
+    #[test]
+    fn pinned_notices_come_first_then_the_most_severe() {
+        let mut older = notice("Older");
+        older.created_at -= chrono::Duration::days(1);
+        let newer = notice("Newer");
+        let mut warning = notice("Warning");
+        warning.severity = NoticeSeverity::Warning;
+        let mut pinned = notice("Pinned");
+        pinned.created_at -= chrono::Duration::days(2);
+        pinned.pinned = true;
+
+        let mut notices = [older, newer, warning, pinned];
+        notices.sort_by(Notice::display_order);
+        let titles: Vec<&str> = notices.iter().map(|n| n.title.as_str()).collect();
+        assert_eq!(titles, ["Pinned", "Warning", "Newer", "Older"]);
+    }
+
+    #[test]
+    fn only_critical_notices_can_be_emergencies() {
+        let mut flood = notice("Kitchen flooded");
+        flood.emergency = true;
+        flood.severity = NoticeSeverity::Warning;
+        assert!(validate_notice(&flood).is_err());
+
+        flood.severity = NoticeSeverity::Critical;
+        assert!(validate_notice(&flood).is_ok());
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use actix_web::HttpRequest;
+use actix_web::http::header::ACCEPT_LANGUAGE;
+use serde::Serialize;
+
+use crate::storage::StorageError;
+
+/// UI strings of one locale, keyed by the name templates use, e.g. `t.menu_title`
+pub type Catalog = BTreeMap<String, String>;
+
+/// A locale offered in the language switcher
+#[derive(Debug, Serialize, Clone)]
+pub struct Language {
+    pub code: String,
+    /// Name of the language in itself, from the catalog's `language_name`
+    pub name: String,
+}
+
+/// The locales the site is available in, one catalog file each
+pub struct Locales {
+    default: String,
+    catalogs: BTreeMap<String, Catalog>,
+}
+
+impl Locales {
+    /// Load every `<locale>.json` in `dir`. The default locale must have a
+    /// catalog, since other locales fall back to it for keys they leave out.
+    pub fn load(dir: &str, default: &str) -> Result<Self, StorageError> {
+        let mut catalogs = BTreeMap::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(locale) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let catalog: Catalog = serde_json::from_str(&fs::read_to_string(&path)?)?;
+            catalogs.insert(locale.to_ascii_lowercase(), catalog);
+        }
+
+        let default = default.trim().to_ascii_lowercase();
+        if !catalogs.contains_key(&default) {
+            return Err(StorageError::NotFound(format!(
+                "No catalog for the default locale '{}' in {}",
+                default,
+                Path::new(dir).display()
+            )));
+        }
+        Ok(Self { default, catalogs })
+    }
+
+    pub fn default_locale(&self) -> &str {
+        &self.default
+    }
+
+    /// Every locale with a catalog, the default included
+    pub fn languages(&self) -> Vec<Language> {
+        self.catalogs
+            .iter()
+            .map(|(code, catalog)| Language {
+                code: code.clone(),
+                name: catalog
+                    .get("language_name")
+                    .cloned()
+                    .unwrap_or_else(|| code.clone()),
+            })
+            .collect()
+    }
+
+    /// Whether there is a catalog for exactly this locale
+    pub fn is_supported(&self, locale: &str) -> bool {
+        self.catalogs.contains_key(locale)
+    }
+
+    /// The supported locale a language tag names, matching `fr-CA` to `fr`
+    /// when there is no catalog for the region
+    pub fn resolve(&self, tag: &str) -> Option<&str> {
+        let tag = tag.trim().to_ascii_lowercase();
+        let primary = tag.split('-').next().unwrap_or_default();
+        [tag.as_str(), primary]
+            .into_iter()
+            .find_map(|candidate| self.catalogs.get_key_value(candidate))
+            .map(|(locale, _)| locale.as_str())
+    }
+
+    /// Locale a request asked for with `?lang=`, if it is supported
+    pub fn requested(&self, req: &HttpRequest) -> Option<&str> {
+        form_urlencoded::parse(req.query_string().as_bytes())
+            .filter(|(key, _)| key == "lang")
+            .find_map(|(_, value)| self.resolve(&value))
+    }
+
+    /// Locale to show a page in: `?lang=` if supported, then the best
+    /// supported match in `Accept-Language`, then the default
+    pub fn negotiate(&self, req: &HttpRequest) -> &str {
+        if let Some(locale) = self.requested(req) {
+            return locale;
+        }
+        let accepted = req
+            .headers()
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let mut ranges: Vec<(&str, f32)> = accepted
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+                (!tag.is_empty() && tag != "*" && quality > 0.0).then_some((tag, quality))
+            })
+            .collect();
+        // Stable, so tags of equal quality keep the order the browser sent them in
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranges
+            .into_iter()
+            .find_map(|(tag, _)| self.resolve(tag))
+            .unwrap_or(&self.default)
+    }
+
+    /// UI strings for `locale`, with the default locale's text for any key
+    /// its catalog leaves out
+    pub fn strings(&self, locale: &str) -> Catalog {
+        let mut strings = self.catalogs[&self.default].clone();
+        if let Some(catalog) = self.catalogs.get(locale) {
+            strings.extend(catalog.clone());
+        }
+        strings
+    }
+
+    /// Keys of the default catalog that `locale` has no text for
+    pub fn missing_keys(&self, locale: &str) -> Vec<String> {
+        let catalog = self.catalogs.get(locale);
+        self.catalogs[&self.default]
+            .keys()
+            .filter(|key| catalog.is_none_or(|catalog| !catalog.contains_key(*key)))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use uuid::Uuid;
+
+    use super::*;
+
+    /// `en` with every key and `fr` with only some of them
+    fn locales() -> Locales {
+        let dir = std::env::temp_dir().join(format!("i18n-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("en.json"),
+            r#"{"language_name": "English", "menu_title": "Menu", "allergens": "Allergens"}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("FR.json"),
+            r#"{"language_name": "Français", "menu_title": "Carte"}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("README.txt"), "not a catalog").unwrap();
+        let locales = Locales::load(dir.to_str().unwrap(), "EN").unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        locales
+    }
+
+    fn negotiate(uri: &str, accept_language: Option<&str>) -> String {
+        let mut req = TestRequest::default().uri(uri);
+        if let Some(value) = accept_language {
+            req = req.insert_header((ACCEPT_LANGUAGE, value));
+        }
+        locales().negotiate(&req.to_http_request()).to_string()
+    }
+
+    #[test]
+    fn query_parameter_beats_the_browser() {
+        assert_eq!(negotiate("/?lang=fr-CA", Some("en")), "fr");
+        // An unsupported choice is ignored rather than shown in the default
+        assert_eq!(negotiate("/?lang=de", Some("fr")), "fr");
+    }
+
+    #[test]
+    fn browser_preferences_are_tried_by_quality() {
+        assert_eq!(negotiate("/", Some("de, en;q=0.5, fr-BE;q=0.8")), "fr");
+        assert_eq!(negotiate("/", Some("fr;q=0, *")), "en");
+        assert_eq!(negotiate("/", Some("de-AT")), "en");
+        assert_eq!(negotiate("/", None), "en");
+    }
+
+    #[test]
+    fn strings_fall_back_to_the_default_catalog() {
+        let locales = locales();
+
+        let strings = locales.strings("fr");
+
+        assert_eq!(strings["menu_title"], "Carte");
+        assert_eq!(strings["allergens"], "Allergens");
+        assert_eq!(locales.missing_keys("fr"), ["allergens"]);
+        assert_eq!(locales.strings("de")["menu_title"], "Menu");
+    }
+
+    #[test]
+    fn default_locale_needs_a_catalog() {
+        let dir = std::env::temp_dir().join(format!("i18n-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("fr.json"), "{}").unwrap();
+
+        let result = Locales::load(dir.to_str().unwrap(), "en");
+
+        assert!(matches!(result, Err(StorageError::NotFound(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
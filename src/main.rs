@@ -1,11 +1,18 @@
+mod audit;
 mod auth;
 mod error_handler;
 mod handlers;
+mod i18n;
+mod markdown;
+mod rate_limit;
 mod scheduler;
 mod storage;
 
+use crate::audit::{Actor, AuditAction, AuditEntity, AuditLog};
 use crate::auth::create_default_admin;
-use crate::scheduler::start_scheduler;
+use crate::i18n::Locales;
+use crate::rate_limit::RateLimiter;
+use crate::scheduler::{start_scheduler, start_trash_purge};
 use actix_cors::Cors;
 use actix_files::Files;
 use actix_session::SessionMiddleware;
@@ -14,10 +21,18 @@ use actix_web::cookie::Key;
 use actix_web::middleware::Logger;
 use actix_web::{App, HttpResponse, HttpServer, web};
 use std::error::Error;
-use storage::JsonStorage;
-use tera::Tera;
 use std::fs;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use storage::{
+    Currency, DataWatcher, JsonStorage, MediaStore, MemoryStorage, SnapshotStore, SqliteStorage,
+    Storage, StorageError,
+};
+use tera::Tera;
+
+/// Address the web server listens on
+const SERVER_ADDRESS: &str = "0.0.0.0:8080";
 
 fn check_data_directory_permissions() -> Result<(), Box<dyn Error>> {
     let data_dir = Path::new("data");
@@ -34,12 +49,10 @@ fn check_data_directory_permissions() -> Result<(), Box<dyn Error>> {
         }
         Err(e) => {
             if e.kind() == std::io::ErrorKind::PermissionDenied {
-                let error_message = format!(
-                    "Error: Insufficient permissions to write to the 'data' directory.\n\
+                let error_message = "Error: Insufficient permissions to write to the 'data' directory.\n\
                     Please ensure the application has write access to this directory.\n\
                     On Windows, you can grant access by running:\n\
-                    icacls \"data\" /grant \"%USERNAME%\":(F)\" /T"
-                );
+                    icacls \"data\" /grant \"%USERNAME%\":(F)\" /T";
                 Err(error_message.into())
             } else {
                 Err(e.into())
@@ -48,6 +61,224 @@ fn check_data_directory_permissions() -> Result<(), Box<dyn Error>> {
     }
 }
 
+/// Open the JSON file storage in the default data directory
+fn open_json_storage() -> Result<JsonStorage, StorageError> {
+    JsonStorage::new(
+        "data/menu_items.json",
+        "data/notices.json",
+        "data/admin_users.json",
+        "data/menu_presets.json",
+        "data/menu_schedules.json",
+        "data/menu_categories.json",
+        "data/ingredients.json",
+        "data/feedback.json",
+    )
+}
+
+/// Whether `WATCH_DATA_FILES` asks for the JSON files to be reloaded on change
+fn watch_data_files() -> bool {
+    matches!(
+        std::env::var("WATCH_DATA_FILES").as_deref(),
+        Ok("1") | Ok("true")
+    )
+}
+
+const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+
+/// How long deleted records stay in the trash, overridable with `TRASH_RETENTION_DAYS`
+fn trash_retention() -> chrono::Duration {
+    let days = match std::env::var("TRASH_RETENTION_DAYS") {
+        Ok(value) => value.parse::<u32>().unwrap_or_else(|_| {
+            log::warn!(
+                "Ignoring invalid TRASH_RETENTION_DAYS '{}'; using {} days",
+                value,
+                DEFAULT_TRASH_RETENTION_DAYS
+            );
+            DEFAULT_TRASH_RETENTION_DAYS
+        }),
+        Err(_) => DEFAULT_TRASH_RETENTION_DAYS,
+    };
+    chrono::Duration::days(days.into())
+}
+
+/// Path of the audit log, overridable with `AUDIT_LOG_PATH`
+fn audit_log_path() -> String {
+    std::env::var("AUDIT_LOG_PATH").unwrap_or_else(|_| "data/audit.jsonl".to_string())
+}
+
+/// Path of the SQLite database, overridable with `SQLITE_PATH`
+fn sqlite_path() -> String {
+    std::env::var("SQLITE_PATH").unwrap_or_else(|_| "data/dining_hall.db".to_string())
+}
+
+/// Directory holding dataset snapshots, overridable with `SNAPSHOT_DIR`
+fn snapshot_dir() -> String {
+    std::env::var("SNAPSHOT_DIR").unwrap_or_else(|_| "data/snapshots".to_string())
+}
+
+/// Directory holding uploaded item images, overridable with `MEDIA_DIR`
+fn media_dir() -> String {
+    std::env::var("MEDIA_DIR").unwrap_or_else(|_| "data/media".to_string())
+}
+
+/// Directory holding the UI string catalogs, overridable with `LOCALES_DIR`
+fn locales_dir() -> String {
+    std::env::var("LOCALES_DIR").unwrap_or_else(|_| "locales".to_string())
+}
+
+/// Locale shown when a request asks for none we support, overridable with `DEFAULT_LOCALE`
+fn default_locale() -> String {
+    std::env::var("DEFAULT_LOCALE").unwrap_or_else(|_| "en".to_string())
+}
+
+const DEFAULT_FEEDBACK_RATE_LIMIT: usize = 5;
+
+/// How many ratings one client may send per hour, overridable with `FEEDBACK_RATE_LIMIT`
+fn feedback_rate_limit() -> usize {
+    match std::env::var("FEEDBACK_RATE_LIMIT") {
+        Ok(value) => value
+            .parse::<usize>()
+            .ok()
+            .filter(|limit| *limit > 0)
+            .unwrap_or_else(|| {
+                log::warn!(
+                    "Ignoring invalid FEEDBACK_RATE_LIMIT '{}'; using {} per hour",
+                    value,
+                    DEFAULT_FEEDBACK_RATE_LIMIT
+                );
+                DEFAULT_FEEDBACK_RATE_LIMIT
+            }),
+        Err(_) => DEFAULT_FEEDBACK_RATE_LIMIT,
+    }
+}
+
+/// Currency every price is held in, overridable with `CURRENCY` (an ISO 4217 code)
+fn currency() -> Currency {
+    match std::env::var("CURRENCY") {
+        Ok(code) => Currency::from_code(&code).unwrap_or_else(|| {
+            log::warn!(
+                "Ignoring invalid CURRENCY '{}'; using {}",
+                code,
+                Currency::default().code
+            );
+            Currency::default()
+        }),
+        Err(_) => Currency::default(),
+    }
+}
+
+/// Open the persistent backend named by `STORAGE_BACKEND` for a maintenance command
+fn open_configured_storage() -> Result<Box<dyn Storage>, Box<dyn Error>> {
+    let backend = std::env::var("STORAGE_BACKEND").unwrap_or_else(|_| "json".to_string());
+    match backend.as_str() {
+        "json" => Ok(Box::new(open_json_storage()?)),
+        "sqlite" => Ok(Box::new(SqliteStorage::open(&sqlite_path())?)),
+        other => Err(format!(
+            "STORAGE_BACKEND '{}' has no data on disk to snapshot; use 'json' or 'sqlite'",
+            other
+        )
+        .into()),
+    }
+}
+
+/// `snapshot [label]`: archive the whole dataset
+fn create_snapshot(label: Option<String>) -> Result<(), Box<dyn Error>> {
+    let storage = open_configured_storage()?;
+    let info = SnapshotStore::new(&snapshot_dir()).create(storage.as_ref(), label)?;
+    AuditLog::open(&audit_log_path())?.record(
+        &Actor::cli(),
+        AuditAction::Create,
+        AuditEntity::Snapshot,
+        info.id,
+        None,
+        Some(&info),
+    );
+    println!("Created snapshot {} ({})", info.id, info.file);
+    Ok(())
+}
+
+/// `snapshots`: list the available snapshots, newest first
+fn list_snapshots() -> Result<(), Box<dyn Error>> {
+    for info in SnapshotStore::new(&snapshot_dir()).list()? {
+        println!(
+            "{}  {}  {} categories, {} ingredients, {} items, {} notices, {} presets, {} schedules, {} feedback  {}",
+            info.id,
+            info.created_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            info.counts.menu_categories,
+            info.counts.ingredients,
+            info.counts.menu_items,
+            info.counts.notices,
+            info.counts.menu_presets,
+            info.counts.menu_schedules,
+            info.counts.feedback,
+            info.label.as_deref().unwrap_or("")
+        );
+    }
+    Ok(())
+}
+
+/// `restore <id>`: replace the whole dataset with a snapshot.
+///
+/// Only safe while the server is stopped: a running server keeps serving and
+/// saving the data it already has in memory, which would undo the restore.
+fn restore_snapshot(id: Option<String>) -> Result<(), Box<dyn Error>> {
+    let id = id
+        .ok_or("missing snapshot id; run the 'snapshots' command to list them")?
+        .parse::<uuid::Uuid>()?;
+    if std::env::var("STORAGE_BACKEND").as_deref() == Ok("memory") {
+        return Err("the 'memory' backend keeps no data between runs, so there is nothing to restore into; use the /api/snapshots endpoints of the running server instead".into());
+    }
+    // If the server's port is taken, the server is most likely running
+    if let Err(e) = std::net::TcpListener::bind(SERVER_ADDRESS)
+        && e.kind() == std::io::ErrorKind::AddrInUse
+    {
+        return Err(format!(
+            "{} is in use, so the server appears to be running; stop it first or restore through /api/snapshots/{{id}}/restore",
+            SERVER_ADDRESS
+        )
+        .into());
+    }
+    let storage = open_configured_storage()?;
+    let outcome = SnapshotStore::new(&snapshot_dir()).restore(storage.as_ref(), id)?;
+    AuditLog::open(&audit_log_path())?.record(
+        &Actor::cli(),
+        AuditAction::Restore,
+        AuditEntity::Snapshot,
+        id,
+        None,
+        Some(&outcome),
+    );
+    println!(
+        "Restored snapshot {}; the previous data was saved as snapshot {}",
+        id, outcome.safety_snapshot.id
+    );
+    Ok(())
+}
+
+/// One-shot import of the JSON data files into the SQLite database
+fn import_json_to_sqlite() -> Result<(), Box<dyn Error>> {
+    let path = sqlite_path();
+    log::info!("Importing data/*.json into SQLite database at {}", path);
+
+    let source = open_json_storage()?;
+    let target = SqliteStorage::open(&path)?;
+    let summary = target.import_from(&source)?;
+
+    println!(
+        "Imported {} categories, {} ingredients, {} menu items, {} notices, {} admin users, {} presets, {} schedules and {} feedback entries into {}",
+        summary.menu_categories,
+        summary.ingredients,
+        summary.menu_items,
+        summary.notices,
+        summary.admin_users,
+        summary.menu_presets,
+        summary.menu_schedules,
+        summary.feedback,
+        path
+    );
+    Ok(())
+}
+
 #[actix_web::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     // Initialize logging
@@ -61,38 +292,131 @@ async fn main() -> Result<(), Box<dyn Error>> {
         std::process::exit(1);
     }
 
-    log::info!("Initializing JSON storage system...");
-    log::debug!("About to call JsonStorage::new()");
+    // Handle one-shot maintenance commands
+    if let Some(command) = std::env::args().nth(1) {
+        let argument = std::env::args().nth(2);
+        let result = match command.as_str() {
+            "import-json" => import_json_to_sqlite(),
+            "snapshot" => create_snapshot(argument),
+            "snapshots" => list_snapshots(),
+            "restore" => restore_snapshot(argument),
+            other => {
+                eprintln!(
+                    "Error: Unknown command '{}'. Available: import-json, snapshot [label], snapshots, restore <id>",
+                    other
+                );
+                std::process::exit(1);
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("Error: {} failed: {}", command, e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-    // Initialize storage with file paths
-    let storage = JsonStorage::new(
-        "data/menu_items.json",
-        "data/notices.json",
-        "data/admin_users.json",
-        "data/menu_presets.json",
-        "data/menu_schedules.json",
-    )?;
-    log::debug!("JsonStorage::new() completed successfully");
+    // Select the storage backend (defaults to the JSON files in data/)
+    let backend = std::env::var("STORAGE_BACKEND").unwrap_or_else(|_| "json".to_string());
+    // Keeps the data file watcher alive for as long as the server runs
+    let mut _data_watcher: Option<DataWatcher> = None;
+    let storage: Arc<dyn Storage> = match backend.as_str() {
+        "json" => {
+            log::info!("Initializing JSON storage system...");
+            log::debug!("About to call JsonStorage::new()");
+
+            // Initialize storage with file paths
+            let storage = Arc::new(open_json_storage()?);
+            log::debug!("JsonStorage::new() completed successfully");
+
+            if watch_data_files() {
+                _data_watcher = Some(storage.watch()?);
+                log::info!("Hot reload of data files enabled");
+            }
+            storage
+        }
+        "sqlite" => {
+            let path = sqlite_path();
+            log::info!("Initializing SQLite storage at {}...", path);
+            let storage = SqliteStorage::open(&path)?;
+            log::info!("SQLite schema is at version {}", storage.schema_version()?);
+            Arc::new(storage)
+        }
+        "memory" => {
+            log::warn!("Using in-memory storage; changes will be lost on shutdown");
+            Arc::new(MemoryStorage::new())
+        }
+        other => {
+            eprintln!(
+                "Error: Unknown STORAGE_BACKEND '{}'. Expected 'json', 'sqlite' or 'memory'.",
+                other
+            );
+            std::process::exit(1);
+        }
+    };
     log::info!("Storage initialized successfully!");
 
+    // The in-memory backend keeps its audit trail in memory too
+    let audit = if backend == "memory" {
+        AuditLog::in_memory()
+    } else {
+        let path = audit_log_path();
+        log::info!("Recording admin changes in {}", path);
+        AuditLog::open(&path)?
+    };
+    let audit_data = web::Data::new(audit);
+    let snapshot_data = web::Data::new(SnapshotStore::new(&snapshot_dir()));
+    let media_data = web::Data::new(MediaStore::new(&media_dir()));
+    let currency = currency();
+    log::info!("Showing prices in {}", currency.code);
+    let currency_data = web::Data::new(currency);
+    let locales_dir = locales_dir();
+    let locales = Locales::load(&locales_dir, &default_locale())
+        .map_err(|e| format!("Failed to load UI strings from {}: {}", locales_dir, e))?;
+    log::info!(
+        "Serving pages in {} (default {})",
+        locales
+            .languages()
+            .iter()
+            .map(|language| language.code.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+        locales.default_locale()
+    );
+    let locales_data = web::Data::new(locales);
+    let feedback_limit = feedback_rate_limit();
+    log::info!(
+        "Accepting up to {} ratings per client per hour",
+        feedback_limit
+    );
+    let feedback_limiter_data = web::Data::new(RateLimiter::new(
+        feedback_limit,
+        Duration::from_secs(60 * 60),
+    ));
+    if watch_data_files() && _data_watcher.is_none() {
+        log::warn!("WATCH_DATA_FILES only applies to the JSON backend; ignoring");
+    }
+
     // Wrap storage in web::Data for Actix-web
     log::debug!("Wrapping storage in web::Data");
-    let storage_data = web::Data::new(storage);
+    let storage_data: web::Data<dyn Storage> = web::Data::from(storage);
     log::debug!("Storage wrapped successfully");
 
     // Create default admin user if none exists
     log::debug!("About to call create_default_admin()");
-    create_default_admin(storage_data.clone()).await?;
+    create_default_admin(storage_data.clone(), audit_data.clone()).await?;
     log::debug!("create_default_admin() completed successfully");
 
     // Start the scheduler service
     log::debug!("Starting scheduler service");
     start_scheduler(storage_data.clone()).await;
+    start_trash_purge(storage_data.clone(), media_data.clone(), trash_retention()).await;
     log::debug!("Scheduler service started");
 
     // Initialize Tera templates
     log::debug!("Initializing Tera templates");
-    let tera = Tera::new("templates/**/*").expect("Failed to initialize Tera templates");
+    let mut tera = Tera::new("templates/**/*").expect("Failed to initialize Tera templates");
+    tera.register_filter("allergen_labels", handlers::allergen_labels_filter);
+    tera.register_filter("dietary_labels", handlers::dietary_labels_filter);
     let tera_data = web::Data::new(tera);
     log::debug!("Tera templates initialized");
 
@@ -109,6 +433,12 @@ async fn main() -> Result<(), Box<dyn Error>> {
         App::new()
             .app_data(storage_data.clone())
             .app_data(tera_data.clone())
+            .app_data(audit_data.clone())
+            .app_data(snapshot_data.clone())
+            .app_data(media_data.clone())
+            .app_data(currency_data.clone())
+            .app_data(locales_data.clone())
+            .app_data(feedback_limiter_data.clone())
             .wrap(Logger::default())
             .wrap(
                 SessionMiddleware::builder(CookieSessionStore::default(), secret_key.clone())
@@ -125,28 +455,143 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     .allow_any_origin()
                     .allow_any_method()
                     .allow_any_header()
+                    .expose_headers(vec![actix_web::http::header::ETAG])
                     .supports_credentials(),
             )
             // Menu items routes
             .route("/api/items", web::get().to(handlers::list_menu_items))
             .route("/api/items", web::post().to(handlers::create_menu_item))
+            .route("/api/items/{id}", web::get().to(handlers::get_menu_item))
             .route("/api/items/{id}", web::put().to(handlers::update_menu_item))
             .route(
                 "/api/items/{id}",
                 web::delete().to(handlers::delete_menu_item),
             )
+            .route(
+                "/api/items/{id}/image",
+                web::post().to(handlers::upload_menu_item_image),
+            )
+            .route(
+                "/api/items/{id}/image",
+                web::delete().to(handlers::delete_menu_item_image),
+            )
+            .route(
+                "/api/items/{id}/stock/decrement",
+                web::post().to(handlers::decrement_menu_item_stock),
+            )
+            .route(
+                "/api/items/{id}/translations/{lang}",
+                web::put().to(handlers::put_menu_item_translation),
+            )
+            .route(
+                "/api/items/{id}/translations/{lang}",
+                web::delete().to(handlers::delete_menu_item_translation),
+            )
+            .route(
+                "/api/items/bulk",
+                web::post().to(handlers::bulk_update_menu_items),
+            )
             .route(
                 "/api/items/reload",
                 web::post().to(handlers::reload_menu_items),
             )
+            // Menu categories routes
+            .route(
+                "/api/categories",
+                web::get().to(handlers::list_menu_categories),
+            )
+            .route(
+                "/api/categories",
+                web::post().to(handlers::create_menu_category),
+            )
+            .route(
+                "/api/categories/{id}",
+                web::get().to(handlers::get_menu_category),
+            )
+            .route(
+                "/api/categories/{id}",
+                web::put().to(handlers::update_menu_category),
+            )
+            .route(
+                "/api/categories/{id}",
+                web::delete().to(handlers::delete_menu_category),
+            )
+            .route(
+                "/api/categories/reload",
+                web::post().to(handlers::reload_menu_categories),
+            )
+            // Ingredients routes
+            .route(
+                "/api/ingredients",
+                web::get().to(handlers::list_ingredients),
+            )
+            .route(
+                "/api/ingredients",
+                web::post().to(handlers::create_ingredient),
+            )
+            .route(
+                "/api/ingredients/{id}",
+                web::get().to(handlers::get_ingredient),
+            )
+            .route(
+                "/api/ingredients/{id}",
+                web::put().to(handlers::update_ingredient),
+            )
+            .route(
+                "/api/ingredients/{id}",
+                web::delete().to(handlers::delete_ingredient),
+            )
+            .route(
+                "/api/ingredients/reload",
+                web::post().to(handlers::reload_ingredients),
+            )
+            .route(
+                "/api/recipes/mismatches",
+                web::get().to(handlers::list_recipe_mismatches),
+            )
+            // Feedback routes
+            .route("/api/feedback", web::get().to(handlers::list_feedback))
+            .route(
+                "/api/feedback/stats",
+                web::get().to(handlers::feedback_stats),
+            )
+            .route(
+                "/api/feedback/queue",
+                web::get().to(handlers::feedback_queue),
+            )
+            .route(
+                "/api/feedback/reload",
+                web::post().to(handlers::reload_feedback),
+            )
+            .route(
+                "/api/feedback/{id}/moderation",
+                web::put().to(handlers::moderate_feedback),
+            )
+            .route(
+                "/api/feedback/{id}",
+                web::delete().to(handlers::delete_feedback),
+            )
             // Notices routes
             .route("/api/notices", web::get().to(handlers::list_notices))
             .route("/api/notices", web::post().to(handlers::create_notice))
+            .route("/api/notices/{id}", web::get().to(handlers::get_notice))
             .route("/api/notices/{id}", web::put().to(handlers::update_notice))
             .route(
                 "/api/notices/{id}",
                 web::delete().to(handlers::delete_notice),
             )
+            .route(
+                "/api/notices/{id}/translations/{lang}",
+                web::put().to(handlers::put_notice_translation),
+            )
+            .route(
+                "/api/notices/{id}/translations/{lang}",
+                web::delete().to(handlers::delete_notice_translation),
+            )
+            .route(
+                "/api/notices/preview",
+                web::post().to(handlers::preview_notice),
+            )
             .route(
                 "/api/notices/reload",
                 web::post().to(handlers::reload_notices),
@@ -213,6 +658,47 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 "/api/schedules/reload",
                 web::post().to(handlers::reload_menu_schedules),
             )
+            // Allergen routes
+            .route("/api/allergens", web::get().to(handlers::list_allergens))
+            .route(
+                "/api/allergens/unmapped",
+                web::get().to(handlers::list_unmapped_allergens),
+            )
+            .route("/api/pricing", web::get().to(handlers::pricing_info))
+            .route(
+                "/api/dietary-tags",
+                web::get().to(handlers::list_dietary_tags),
+            )
+            .route(
+                "/api/portion-sizes",
+                web::get().to(handlers::list_portion_sizes),
+            )
+            // Translation routes
+            .route(
+                "/api/translations",
+                web::get().to(handlers::list_translation_locales),
+            )
+            .route(
+                "/api/translations/missing",
+                web::get().to(handlers::list_missing_translations),
+            )
+            // Trash routes
+            .route("/api/trash", web::get().to(handlers::list_trash))
+            .route(
+                "/api/trash/{kind}/{id}/restore",
+                web::post().to(handlers::restore_from_trash),
+            )
+            // Audit log
+            .route("/api/audit", web::get().to(handlers::list_audit_entries))
+            // Snapshot routes
+            .route("/api/snapshots", web::get().to(handlers::list_snapshots))
+            .route("/api/snapshots", web::post().to(handlers::create_snapshot))
+            .route(
+                "/api/snapshots/{id}/restore",
+                web::post().to(handlers::restore_snapshot),
+            )
+            // Consistency check
+            .route("/api/integrity", web::get().to(handlers::check_integrity))
             // Menu schedules page
             .route(
                 "/admin/schedules",
@@ -222,9 +708,19 @@ async fn main() -> Result<(), Box<dyn Error>> {
             .route("/admin/presets", web::get().to(handlers::menu_presets_page))
             // Serve static files
             .service(Files::new("/static", "./static").show_files_listing())
+            // Uploaded item images
+            .route("/media/{id}", web::get().to(handlers::menu_item_image))
+            .route(
+                "/media/{id}/thumb",
+                web::get().to(handlers::menu_item_thumbnail),
+            )
             // Public menu page
             .route("/menu", web::get().to(handlers::menu_page))
             .route("/menu/item/{id}", web::get().to(handlers::menu_item_page))
+            .route(
+                "/menu/item/{id}/feedback",
+                web::post().to(handlers::submit_feedback),
+            )
             // Add a redirect from / to /menu
             .route(
                 "/",
@@ -236,7 +732,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
             )
             .default_service(web::to(handlers::not_found_page))
     })
-    .bind("0.0.0.0:8080")?
+    .bind(SERVER_ADDRESS)?
     .run()
     .await?;
     log::debug!("Server started successfully");
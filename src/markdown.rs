@@ -0,0 +1,41 @@
+use pulldown_cmark::{Options, Parser, html};
+
+/// Render Markdown to HTML that is safe to put on a public page. Raw HTML in
+/// the source is kept only as far as the sanitizer allows, so scripts, event
+/// handler attributes and `javascript:` links are stripped.
+pub fn render(markdown: &str) -> String {
+    let parser = Parser::new_ext(markdown, Options::ENABLE_STRIKETHROUGH);
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, parser);
+    ammonia::clean(&unsafe_html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatting_is_rendered() {
+        let html = render("**Closed** ~~Monday~~ see [hours](https://example.com/hours)");
+        assert!(html.contains("<strong>Closed</strong>"));
+        assert!(html.contains("<del>Monday</del>"));
+        assert!(html.contains("href=\"https://example.com/hours\""));
+    }
+
+    #[test]
+    fn scripts_and_handlers_are_stripped() {
+        let html = render(
+            "<script>alert(1)</script><img src=\"a.png\" onerror=\"alert(2)\">\n\n[x](javascript:alert(3))",
+        );
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("onerror"));
+        assert!(!html.contains("javascript:"));
+    }
+
+    #[test]
+    fn javascript_anchors_keep_their_text() {
+        let html = render(r#"<a href="javascript:alert('x')">click</a>"#);
+        assert!(!html.contains("javascript:"));
+        assert!(html.contains("click"));
+    }
+}
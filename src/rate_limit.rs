@@ -0,0 +1,82 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Caps how many requests each client may make within a sliding window.
+/// Clients are told apart by IP address, so diners behind one NAT share a
+/// budget.
+pub struct RateLimiter {
+    limit: usize,
+    window: Duration,
+    hits: Mutex<HashMap<IpAddr, VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: usize, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            hits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count a request from `client`, or return how long it must wait when
+    /// it has used up its budget. Refused requests are not counted.
+    pub fn check(&self, client: IpAddr) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut hits = self.hits.lock().unwrap_or_else(|e| e.into_inner());
+        // Forget clients whose requests have all left the window
+        hits.retain(|_, times| {
+            while times
+                .front()
+                .is_some_and(|time| now.duration_since(*time) >= self.window)
+            {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+
+        let times = hits.entry(client).or_default();
+        if times.len() >= self.limit {
+            let oldest = times.front().copied().unwrap_or(now);
+            return Err(self.window.saturating_sub(now.duration_since(oldest)));
+        }
+        times.push_back(now);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::thread;
+
+    use super::*;
+
+    const DINER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const NEIGHBOUR: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+    #[test]
+    fn budget_is_per_client() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+
+        assert!(limiter.check(DINER).is_ok());
+        assert!(limiter.check(DINER).is_ok());
+        let wait = limiter.check(DINER).unwrap_err();
+
+        assert!(wait > Duration::from_secs(59) && wait <= Duration::from_secs(60));
+        assert!(limiter.check(NEIGHBOUR).is_ok());
+    }
+
+    #[test]
+    fn refused_requests_do_not_extend_the_wait() {
+        let limiter = RateLimiter::new(1, Duration::from_millis(50));
+        limiter.check(DINER).unwrap();
+        assert!(limiter.check(DINER).is_err());
+
+        thread::sleep(Duration::from_millis(60));
+
+        assert!(limiter.check(DINER).is_ok());
+    }
+}
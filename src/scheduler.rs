@@ -3,7 +3,11 @@ use chrono::Utc;
 use log::{error, info, warn};
 use tokio::time::{Duration, interval};
 
-use crate::storage::{JsonStorage, MenuSchedule, ScheduleRecurrence, ScheduleStatus};
+use crate::handlers::STOCK_UPDATE_ATTEMPTS;
+use crate::storage::{
+    BatchOp, MediaStore, MenuItem, MenuPreset, MenuSchedule, Notice, ScheduleRecurrence,
+    ScheduleStatus, Storage, StorageError,
+};
 
 /// Check if a schedule conflicts with any existing schedules
 /// A conflict occurs if the time ranges overlap
@@ -27,7 +31,7 @@ pub fn has_schedule_conflict(
 
 /// Starts the scheduler service that runs in the background
 /// checking for due menu schedules and executing them
-pub async fn start_scheduler(storage: Data<JsonStorage>) {
+pub async fn start_scheduler(storage: Data<dyn Storage>) {
     info!("Starting scheduler service");
 
     // Spawn the scheduler task as a background process
@@ -37,7 +41,7 @@ pub async fn start_scheduler(storage: Data<JsonStorage>) {
 }
 
 /// Main scheduler loop that runs every minute
-async fn run_scheduler(storage: Data<JsonStorage>) {
+async fn run_scheduler(storage: Data<dyn Storage>) {
     // Check every minute
     let mut interval = interval(Duration::from_secs(60));
 
@@ -52,12 +56,49 @@ async fn run_scheduler(storage: Data<JsonStorage>) {
         if let Err(e) = check_and_execute_schedules(&storage).await {
             error!("Error checking and executing schedules: {}", e);
         }
+
+        // Turn notices on and off as their publish windows open and close
+        if let Err(e) = update_notice_windows(&storage) {
+            error!("Error updating notice publish windows: {}", e);
+        }
     }
 }
 
+/// Starts the background task that permanently removes records which have
+/// been in the trash for longer than `retention`, along with image files no
+/// remaining item refers to
+pub async fn start_trash_purge(
+    storage: Data<dyn Storage>,
+    media: Data<MediaStore>,
+    retention: chrono::Duration,
+) {
+    info!(
+        "Starting trash purge; deleted records are kept for {} days",
+        retention.num_days()
+    );
+
+    tokio::spawn(async move {
+        // Check every hour; the first tick fires immediately
+        let mut interval = interval(Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            match storage.purge_trash(Utc::now() - retention) {
+                Ok(0) => {}
+                Ok(purged) => info!("Purged {} records from the trash", purged),
+                Err(e) => error!("Error purging the trash: {}", e),
+            }
+            match media.prune(storage.get_ref()) {
+                Ok(0) => {}
+                Ok(removed) => info!("Removed {} unused image files", removed),
+                Err(e) => error!("Error removing unused image files: {}", e),
+            }
+        }
+    });
+}
+
 /// Check all schedules and execute any that are due
 async fn check_and_execute_schedules(
-    storage: &Data<JsonStorage>,
+    storage: &Data<dyn Storage>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Get all schedules
     let schedules = storage.get_menu_schedules()?;
@@ -138,6 +179,45 @@ async fn check_and_execute_schedules(
     Ok(())
 }
 
+/// Turn on notices whose `publish_at` has passed and turn off those whose
+/// `expire_at` has, all in one batch
+fn update_notice_windows(
+    storage: &Data<dyn Storage>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let now = Utc::now();
+    let ops: Vec<BatchOp> = storage
+        .get_notices()?
+        .into_iter()
+        .filter_map(|mut notice| {
+            let is_active = notice_state_due(&notice, now)?;
+            info!(
+                "Turning notice '{}' ({}) {}",
+                notice.title,
+                notice.id,
+                if is_active { "on" } else { "off" }
+            );
+            notice.is_active = is_active;
+            notice.updated_at = now;
+            Some(BatchOp::UpdateNotice(notice.id, notice))
+        })
+        .collect();
+    if !ops.is_empty() {
+        storage.apply_batch(ops)?;
+    }
+    Ok(())
+}
+
+/// Whether a notice should be switched on or off at `now`, if at all.
+/// A notice is only switched on if nobody has edited it since `publish_at`,
+/// so one an admin turned off after it was published stays off.
+fn notice_state_due(notice: &Notice, now: chrono::DateTime<Utc>) -> Option<bool> {
+    if notice.is_active && notice.expire_at.is_some_and(|expire_at| expire_at <= now) {
+        return Some(false);
+    }
+    let publish_at = notice.publish_at?;
+    (!notice.is_active && notice.in_window(now) && notice.updated_at < publish_at).then_some(true)
+}
+
 /// Check if a schedule is due to run
 fn is_schedule_due(schedule: &MenuSchedule, now: chrono::DateTime<Utc>) -> bool {
     schedule.start_time <= now
@@ -145,14 +225,9 @@ fn is_schedule_due(schedule: &MenuSchedule, now: chrono::DateTime<Utc>) -> bool
 
 /// Execute a schedule by updating menu items based on the associated preset
 async fn execute_schedule(
-    storage: &Data<JsonStorage>,
+    storage: &Data<dyn Storage>,
     mut schedule: MenuSchedule,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    // Set status to Active during execution
-    schedule.status = ScheduleStatus::Active;
-    schedule.updated_at = Utc::now();
-    storage.update_menu_schedule(schedule.id, schedule.clone())?;
-
     // Get the associated preset
     let presets = storage.get_menu_presets()?;
     let preset = presets
@@ -165,21 +240,6 @@ async fn execute_schedule(
             )
         })?;
 
-    // Get all menu items
-    let menu_items = storage.get_menu_items()?;
-
-    // Update menu items based on the preset
-    // Set is_available = true for items in the preset
-    // Set is_available = false for items not in the preset
-    for mut item in menu_items {
-        if preset.menu_item_ids.contains(&item.id) {
-            item.is_available = true;
-        } else {
-            item.is_available = false;
-        }
-        storage.update_menu_item(item.id, item)?;
-    }
-
     // Update schedule status based on recurrence and end time
     let now = Utc::now();
 
@@ -226,8 +286,21 @@ async fn execute_schedule(
         }
     }
 
-    // Update the schedule in storage
-    storage.update_menu_schedule(schedule.id, schedule.clone())?;
+    // Persist the item changes and the new schedule state together. Portions
+    // counted between reading the items and writing them make the batch
+    // stale, so read them again and retry as the stock handler does
+    let mut attempt = 1;
+    loop {
+        let mut ops = menu_item_changes(storage.get_menu_items()?, &preset);
+        ops.push(BatchOp::UpdateMenuSchedule(schedule.id, schedule.clone()));
+        match storage.apply_batch(ops) {
+            Ok(()) => break,
+            Err(StorageError::PreconditionFailed(_)) if attempt < STOCK_UPDATE_ATTEMPTS => {
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
 
     info!(
         "Successfully executed schedule: {} ({})",
@@ -236,6 +309,40 @@ async fn execute_schedule(
     Ok(())
 }
 
+/// Updates that switch the menu over to a preset
+fn menu_item_changes(menu_items: Vec<MenuItem>, preset: &MenuPreset) -> Vec<BatchOp> {
+    // Set is_available = true for items in the preset
+    // Set is_available = false for items not in the preset
+    // Replace any earlier preset's price overrides with this preset's
+    // Restock items in the preset to its par levels; those without one stop counting
+    // Only items where any of that changes are written
+    menu_items
+        .into_iter()
+        .filter_map(|mut item| {
+            let is_available = preset.menu_item_ids.contains(&item.id);
+            let preset_prices = preset
+                .price_overrides
+                .get(&item.id)
+                .cloned()
+                .unwrap_or_default();
+            let stock = if is_available {
+                preset.par_levels.get(&item.id).copied()
+            } else {
+                item.stock
+            };
+            let changed = item.is_available != is_available
+                || item.preset_prices != preset_prices
+                || item.stock != stock;
+            changed.then(|| {
+                item.is_available = is_available;
+                item.preset_prices = preset_prices;
+                item.stock = stock;
+                BatchOp::UpdateMenuItem(item.id, item)
+            })
+        })
+        .collect()
+}
+
 /// Calculate the next occurrence of a recurring schedule
 fn calculate_next_occurrence(
     schedule: &MenuSchedule,
@@ -252,16 +359,265 @@ fn calculate_next_occurrence(
         }
         ScheduleRecurrence::Monthly => {
             // For monthly, we add one month
-            if let Some(next_month) = schedule
+            schedule
                 .start_time
                 .date_naive()
                 .checked_add_months(chrono::Months::new(1))
-            {
-                Some(next_month.and_time(schedule.start_time.time()).and_utc())
-            } else {
-                None
-            }
+                .map(|next_month| next_month.and_time(schedule.start_time.time()).and_utc())
         }
         ScheduleRecurrence::Custom => None, // Custom recurrence not implemented yet
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+
+    use chrono::Duration;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::storage::{
+        MemoryStorage, MenuItem, MenuPreset, NoticeSeverity, PriceTier, Prices, Translations,
+    };
+
+    fn item(name: &str, is_available: bool) -> MenuItem {
+        MenuItem {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            // Every new storage starts out with the default categories
+            category_id: MemoryStorage::new().get_menu_categories().unwrap()[0].id,
+            description: String::new(),
+            allergens: Vec::new(),
+            is_available,
+            revision: 0,
+            trashed_at: None,
+            may_contain: Vec::new(),
+            unmapped_allergens: Vec::new(),
+            dietary_tags: Vec::new(),
+            nutrition: None,
+            prices: Prices::new(),
+            preset_prices: Prices::new(),
+            image: None,
+            recipe: Vec::new(),
+            translations: Translations::new(),
+            stock: None,
+            variants: Vec::new(),
+        }
+    }
+
+    fn schedule(preset: &MenuPreset) -> MenuSchedule {
+        let now = Utc::now();
+        MenuSchedule {
+            id: Uuid::new_v4(),
+            preset_id: preset.id,
+            name: "Lunch".to_string(),
+            description: String::new(),
+            start_time: now - Duration::minutes(1),
+            end_time: now + Duration::hours(2),
+            recurrence: ScheduleRecurrence::Custom,
+            status: ScheduleStatus::Pending,
+            error_message: None,
+            created_at: now,
+            updated_at: now,
+            revision: 0,
+            trashed_at: None,
+        }
+    }
+
+    #[actix_web::test]
+    async fn schedule_switches_the_menu_to_its_preset() {
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let soup = item("Soup", false);
+        let pie = item("Pie", true);
+        storage.add_menu_item(soup.clone()).unwrap();
+        storage.add_menu_item(pie.clone()).unwrap();
+        let now = Utc::now();
+        let lunch = MenuPreset {
+            id: Uuid::new_v4(),
+            name: "Lunch".to_string(),
+            description: String::new(),
+            menu_item_ids: vec![soup.id],
+            created_at: now,
+            updated_at: now,
+            revision: 0,
+            trashed_at: None,
+            price_overrides: BTreeMap::new(),
+            par_levels: BTreeMap::new(),
+        };
+        storage.add_menu_preset(lunch.clone()).unwrap();
+        let schedule = schedule(&lunch);
+        storage.add_menu_schedule(schedule.clone()).unwrap();
+
+        execute_schedule(&Data::from(storage.clone()), schedule)
+            .await
+            .unwrap();
+
+        let items = storage.get_menu_items().unwrap();
+        let available = |id| items.iter().find(|i| i.id == id).unwrap().is_available;
+        assert!(available(soup.id));
+        assert!(!available(pie.id));
+        assert!(matches!(
+            storage.get_menu_schedules().unwrap()[0].status,
+            ScheduleStatus::Ended
+        ));
+    }
+
+    #[actix_web::test]
+    async fn preset_prices_replace_earlier_ones_and_unchanged_items_are_left_alone() {
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let mut soup = item("Soup", true);
+        soup.preset_prices.insert(PriceTier::Visitor, 500);
+        let pie = item("Pie", true);
+        storage.add_menu_item(soup.clone()).unwrap();
+        storage.add_menu_item(pie.clone()).unwrap();
+        let now = Utc::now();
+        let lunch = MenuPreset {
+            id: Uuid::new_v4(),
+            name: "Lunch".to_string(),
+            description: String::new(),
+            menu_item_ids: vec![soup.id, pie.id],
+            created_at: now,
+            updated_at: now,
+            revision: 0,
+            trashed_at: None,
+            price_overrides: BTreeMap::from([(soup.id, Prices::from([(PriceTier::Student, 250)]))]),
+            par_levels: BTreeMap::new(),
+        };
+        storage.add_menu_preset(lunch.clone()).unwrap();
+        let schedule = schedule(&lunch);
+        storage.add_menu_schedule(schedule.clone()).unwrap();
+
+        execute_schedule(&Data::from(storage.clone()), schedule)
+            .await
+            .unwrap();
+
+        let items = storage.get_menu_items().unwrap();
+        let find = |id| items.iter().find(|i: &&MenuItem| i.id == id).unwrap();
+        assert_eq!(
+            find(soup.id).preset_prices,
+            Prices::from([(PriceTier::Student, 250)])
+        );
+        assert_eq!(find(pie.id).revision, pie.revision);
+    }
+
+    #[test]
+    fn switching_presets_restocks_its_items_to_par() {
+        let mut soup = item("Soup", false);
+        soup.stock = Some(2);
+        let mut pie = item("Pie", true);
+        pie.stock = Some(5);
+        let mut bread = item("Bread", true);
+        bread.stock = Some(1);
+        let now = Utc::now();
+        let lunch = MenuPreset {
+            id: Uuid::new_v4(),
+            name: "Lunch".to_string(),
+            description: String::new(),
+            menu_item_ids: vec![soup.id, bread.id],
+            created_at: now,
+            updated_at: now,
+            revision: 0,
+            trashed_at: None,
+            price_overrides: BTreeMap::new(),
+            par_levels: BTreeMap::from([(soup.id, 20)]),
+        };
+
+        let ops = menu_item_changes(vec![soup.clone(), pie.clone(), bread.clone()], &lunch);
+
+        let stock: Vec<(Uuid, bool, Option<u32>)> = ops
+            .iter()
+            .map(|op| match op {
+                BatchOp::UpdateMenuItem(id, item) => (*id, item.is_available, item.stock),
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        // Pie leaves the menu with its count kept; bread has no par level
+        assert_eq!(
+            stock,
+            [
+                (soup.id, true, Some(20)),
+                (pie.id, false, Some(5)),
+                (bread.id, true, None)
+            ]
+        );
+    }
+
+    fn notice(is_active: bool, publish_in: Option<i64>, expire_in: Option<i64>) -> Notice {
+        let now = Utc::now();
+        Notice {
+            id: Uuid::new_v4(),
+            title: "Closed Monday".to_string(),
+            content: String::new(),
+            is_active,
+            created_at: now - Duration::days(1),
+            updated_at: now - Duration::days(1),
+            revision: 0,
+            trashed_at: None,
+            translations: Translations::new(),
+            publish_at: publish_in.map(|hours| now + Duration::hours(hours)),
+            expire_at: expire_in.map(|hours| now + Duration::hours(hours)),
+            severity: NoticeSeverity::Info,
+            pinned: false,
+            emergency: false,
+        }
+    }
+
+    #[test]
+    fn notice_window_includes_its_start_but_not_its_end() {
+        let notice = notice(true, Some(1), Some(2));
+        let publish_at = notice.publish_at.unwrap();
+        let expire_at = notice.expire_at.unwrap();
+
+        assert!(!notice.in_window(publish_at - Duration::seconds(1)));
+        assert!(notice.in_window(publish_at));
+        assert!(!notice.in_window(expire_at));
+        assert!(!notice.is_visible(publish_at - Duration::seconds(1)));
+    }
+
+    #[test]
+    fn notices_are_switched_only_when_their_window_says_so() {
+        let now = Utc::now();
+        // Published an hour ago and not yet switched on
+        assert_eq!(
+            notice_state_due(&notice(false, Some(-1), None), now),
+            Some(true)
+        );
+        // Expired an hour ago but still on
+        assert_eq!(
+            notice_state_due(&notice(true, None, Some(-1)), now),
+            Some(false)
+        );
+        // Not published yet, or already in the state its window calls for
+        assert_eq!(notice_state_due(&notice(false, Some(1), None), now), None);
+        assert_eq!(
+            notice_state_due(&notice(true, Some(-1), Some(1)), now),
+            None
+        );
+        assert_eq!(notice_state_due(&notice(false, None, None), now), None);
+
+        // Turned off by an admin after it was published, so it stays off
+        let mut edited = notice(false, Some(-2), None);
+        edited.updated_at = now - Duration::hours(1);
+        assert_eq!(notice_state_due(&edited, now), None);
+    }
+
+    #[test]
+    fn update_notice_windows_writes_only_due_notices() {
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let due = notice(false, Some(-1), Some(1));
+        let upcoming = notice(false, Some(1), None);
+        storage.add_notice(due.clone()).unwrap();
+        storage.add_notice(upcoming.clone()).unwrap();
+
+        update_notice_windows(&Data::from(storage.clone())).unwrap();
+
+        let notices = storage.get_notices().unwrap();
+        let find = |id| notices.iter().find(|n: &&Notice| n.id == id).unwrap();
+        assert!(find(due.id).is_active);
+        assert_eq!(find(due.id).revision, 1);
+        assert!(!find(upcoming.id).is_active);
+        assert_eq!(find(upcoming.id).revision, 0);
+    }
+}
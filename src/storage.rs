@@ -1,3 +1,49 @@
+mod allergen;
+mod batch;
+mod category;
+mod coded;
+mod dietary;
+mod feedback;
+mod integrity;
+mod journal;
+mod media;
+mod memory;
+mod nutrition;
+mod pricing;
+mod recipe;
+mod schema;
+mod severity;
+mod snapshot;
+mod sqlite;
+mod translation;
+mod variant;
+mod watch;
+
+pub use allergen::Allergen;
+pub use batch::BatchOp;
+pub use coded::Coded;
+pub use dietary::DietaryTag;
+pub use feedback::{
+    Feedback, MAX_COMMENT_CHARS, MAX_RATING, MIN_RATING, ModerationStatus, RatingSummary,
+    TrendPeriod, TrendPoint, rating_trend,
+};
+pub use integrity::DanglingReference;
+pub use media::{ImageKind, ItemImage, MAX_IMAGE_BYTES, MediaStore, process_image};
+pub use memory::MemoryStorage;
+pub use nutrition::Nutrition;
+pub use pricing::{Currency, PriceTier, Prices};
+pub use recipe::{Derived, RecipeIngredient};
+pub use severity::NoticeSeverity;
+pub use snapshot::SnapshotStore;
+pub use sqlite::SqliteStorage;
+pub use translation::{MENU_ITEM_FIELDS, NOTICE_FIELDS, Translations};
+pub use variant::{PortionSize, Variant};
+pub use watch::DataWatcher;
+
+use batch::{BatchTarget, apply_ops, live, trashed};
+use journal::{Journal, JournalEntry, write_atomic};
+
+use std::collections::BTreeMap;
 use std::fs;
 use std::io;
 use std::path::Path;
@@ -13,18 +59,130 @@ use uuid::Uuid;
 pub struct MenuItem {
     pub id: Uuid,
     pub name: String,
-    pub category: MenuCategory,
+    pub category_id: Uuid,
     pub description: String,
-    pub allergens: Vec<String>,
+    /// Name and description in other locales
+    #[serde(default)]
+    pub translations: Translations,
+    /// Allergens the item contains
+    pub allergens: Vec<Allergen>,
+    /// Allergens the item may contain traces of
+    #[serde(default)]
+    pub may_contain: Vec<Allergen>,
+    /// Legacy free-text allergens that could not be mapped when upgrading
+    #[serde(default)]
+    pub unmapped_allergens: Vec<String>,
+    #[serde(default)]
+    pub dietary_tags: Vec<DietaryTag>,
+    /// Ingredients the item is made from. Unless set by hand, its allergens
+    /// and dietary tags are worked out from these.
+    #[serde(default)]
+    pub recipe: Vec<RecipeIngredient>,
+    #[serde(default)]
+    pub nutrition: Option<Nutrition>,
+    #[serde(default)]
+    pub prices: Prices,
+    /// Overrides set by the preset a schedule last applied; they take
+    /// precedence over `prices`
+    #[serde(default)]
+    pub preset_prices: Prices,
+    /// Photo uploaded through `/api/items/{id}/image`
+    #[serde(default)]
+    pub image: Option<ItemImage>,
+    /// Portions left, or `None` when stock is not counted. At zero the item
+    /// shows as sold out but keeps the availability a schedule gave it.
+    #[serde(default)]
+    pub stock: Option<u32>,
+    /// Sizes the item comes in, smallest first; empty for an item served one way
+    #[serde(default)]
+    pub variants: Vec<Variant>,
     pub is_available: bool,
+    #[serde(default)]
+    pub revision: u64,
+    #[serde(default)]
+    pub trashed_at: Option<DateTime<Utc>>,
 }
 
+impl MenuItem {
+    /// Prices diners are charged right now, preset overrides included
+    pub fn current_prices(&self) -> Prices {
+        let mut prices = self.prices.clone();
+        prices.extend(&self.preset_prices);
+        prices
+    }
+
+    /// Prices diners are charged for one of the item's variants
+    pub fn variant_prices(&self, variant: &Variant) -> Prices {
+        let mut prices = self.current_prices();
+        prices.extend(&variant.prices);
+        prices
+    }
+
+    /// Allergens and dietary tags the recipe calls for, or `None` without a recipe
+    pub fn derived(&self, ingredients: &[Ingredient]) -> Option<Derived> {
+        Derived::from_recipe(&self.recipe, ingredients)
+    }
+
+    /// Where the item's allergens and tags disagree with its recipe
+    pub fn recipe_mismatches(&self, ingredients: &[Ingredient]) -> Vec<String> {
+        self.derived(ingredients)
+            .map(|derived| recipe::mismatches(self, &derived))
+            .unwrap_or_default()
+    }
+
+    /// The item with its name and description in `locale`, where translated
+    pub fn localized(mut self, locale: &str) -> Self {
+        if let Some(name) = translation::lookup(&self.translations, locale, "name") {
+            self.name = name.to_string();
+        }
+        if let Some(description) = translation::lookup(&self.translations, locale, "description") {
+            self.description = description.to_string();
+        }
+        self
+    }
+
+    /// Translatable fields with no text in `locale`
+    pub fn missing_translations(&self, locale: &str) -> Vec<&'static str> {
+        translation::missing(&self.translations, locale, MENU_ITEM_FIELDS)
+    }
+}
+
+/// Something menu items are made from, with the allergens and diets that
+/// carry over to every item whose recipe uses it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Ingredient {
+    pub id: Uuid,
+    pub name: String,
+    pub allergens: Vec<Allergen>,
+    #[serde(default)]
+    pub may_contain: Vec<Allergen>,
+    /// Diets the ingredient is suitable for
+    #[serde(default)]
+    pub dietary_tags: Vec<DietaryTag>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub revision: u64,
+    #[serde(default)]
+    pub trashed_at: Option<DateTime<Utc>>,
+}
+
+/// A section of the menu that items are listed under
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub enum MenuCategory {
-    Mains,
-    Sides,
-    Desserts,
-    Beverages,
+pub struct MenuCategory {
+    pub id: Uuid,
+    pub name: String,
+    /// Emoji or short text shown next to the name
+    #[serde(default)]
+    pub icon: String,
+    /// Categories are listed in ascending order, then by name
+    pub display_order: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub revision: u64,
+    #[serde(default)]
+    pub trashed_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -32,9 +190,70 @@ pub struct Notice {
     pub id: Uuid,
     pub title: String,
     pub content: String,
+    /// Title and content in other locales
+    #[serde(default)]
+    pub translations: Translations,
     pub is_active: bool,
+    /// When the scheduler turns the notice on; it is never shown before then
+    #[serde(default)]
+    pub publish_at: Option<DateTime<Utc>>,
+    /// When the scheduler turns the notice off; it is never shown from then on
+    #[serde(default)]
+    pub expire_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub severity: NoticeSeverity,
+    /// Listed before unpinned notices
+    #[serde(default)]
+    pub pinned: bool,
+    /// While shown, the public menu is replaced by the critical notices.
+    /// Only critical notices can set this.
+    #[serde(default)]
+    pub emergency: bool,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub revision: u64,
+    #[serde(default)]
+    pub trashed_at: Option<DateTime<Utc>>,
+}
+
+impl Notice {
+    /// Whether `now` falls between `publish_at` and `expire_at`
+    pub fn in_window(&self, now: DateTime<Utc>) -> bool {
+        self.publish_at.is_none_or(|publish_at| publish_at <= now)
+            && self.expire_at.is_none_or(|expire_at| now < expire_at)
+    }
+
+    /// Whether diners see the notice at `now`. The window is checked as well
+    /// as `is_active`, so a notice never shows outside it while the
+    /// scheduler has yet to catch up.
+    pub fn is_visible(&self, now: DateTime<Utc>) -> bool {
+        self.is_active && self.in_window(now)
+    }
+
+    /// Order notices are listed in: pinned first, then most severe, then newest
+    pub fn display_order(a: &Notice, b: &Notice) -> std::cmp::Ordering {
+        b.pinned
+            .cmp(&a.pinned)
+            .then(b.severity.cmp(&a.severity))
+            .then(b.created_at.cmp(&a.created_at))
+    }
+
+    /// The notice with its title and content in `locale`, where translated
+    pub fn localized(mut self, locale: &str) -> Self {
+        if let Some(title) = translation::lookup(&self.translations, locale, "title") {
+            self.title = title.to_string();
+        }
+        if let Some(content) = translation::lookup(&self.translations, locale, "content") {
+            self.content = content.to_string();
+        }
+        self
+    }
+
+    /// Translatable fields with no text in `locale`
+    pub fn missing_translations(&self, locale: &str) -> Vec<&'static str> {
+        translation::missing(&self.translations, locale, NOTICE_FIELDS)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -67,8 +286,19 @@ pub struct MenuPreset {
     pub name: String,
     pub description: String,
     pub menu_item_ids: Vec<Uuid>,
+    /// Prices to charge for some of the preset's items while it is applied
+    #[serde(default)]
+    pub price_overrides: BTreeMap<Uuid, Prices>,
+    /// Portions of some of the preset's items to start with each time a
+    /// schedule applies it
+    #[serde(default)]
+    pub par_levels: BTreeMap<Uuid, u32>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub revision: u64,
+    #[serde(default)]
+    pub trashed_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -84,6 +314,35 @@ pub struct MenuSchedule {
     pub error_message: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub revision: u64,
+    #[serde(default)]
+    pub trashed_at: Option<DateTime<Utc>>,
+}
+
+/// Deleted records that can still be restored until they are purged
+#[derive(Debug, Default, Serialize, Clone)]
+pub struct Trash {
+    pub menu_items: Vec<MenuItem>,
+    pub notices: Vec<Notice>,
+    pub menu_presets: Vec<MenuPreset>,
+    pub menu_schedules: Vec<MenuSchedule>,
+    pub menu_categories: Vec<MenuCategory>,
+    pub ingredients: Vec<Ingredient>,
+    pub feedback: Vec<Feedback>,
+}
+
+/// Every record of every collection, trashed ones included
+#[derive(Debug, Default, Clone)]
+pub struct Dataset {
+    pub menu_items: Vec<MenuItem>,
+    pub notices: Vec<Notice>,
+    pub admin_users: Vec<AdminUser>,
+    pub menu_presets: Vec<MenuPreset>,
+    pub menu_schedules: Vec<MenuSchedule>,
+    pub menu_categories: Vec<MenuCategory>,
+    pub ingredients: Vec<Ingredient>,
+    pub feedback: Vec<Feedback>,
 }
 
 #[derive(Error, Debug)]
@@ -94,8 +353,22 @@ pub enum StorageError {
     Json(#[from] serde_json::Error),
     #[error("Mutex poison error")]
     PoisonError,
-    #[error("Permission denied: {0}. Please ensure the application has write access to the data directory.")]
+    #[error(
+        "Permission denied: {0}. Please ensure the application has write access to the data directory."
+    )]
     PermissionDenied(String),
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("File watcher error: {0}")]
+    Watch(#[from] notify::Error),
+    #[error("Data file schema error: {0}")]
+    Schema(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    PreconditionFailed(String),
 }
 
 impl From<io::Error> for StorageError {
@@ -115,30 +388,378 @@ impl From<StorageError> for AppError {
             StorageError::Json(json_error) => AppError::Storage(json_error.to_string()),
             StorageError::PoisonError => AppError::Storage("Mutex poison error".to_string()),
             StorageError::PermissionDenied(msg) => AppError::Storage(msg),
+            StorageError::Database(db_error) => AppError::Storage(db_error.to_string()),
+            StorageError::Watch(watch_error) => AppError::Storage(watch_error.to_string()),
+            StorageError::Schema(msg) => AppError::Storage(msg),
+            StorageError::NotFound(msg) => AppError::NotFound(msg),
+            StorageError::Conflict(msg) => AppError::Conflict(msg),
+            StorageError::PreconditionFailed(msg) => AppError::PreconditionFailed(msg),
         }
     }
 }
 
+/// Persistence operations shared by every storage backend.
+///
+/// Handlers, authentication and the scheduler only ever talk to this trait,
+/// so the backend can be swapped without touching request handling.
+///
+/// Items, notices, presets and schedules carry a `revision` for optimistic
+/// concurrency. An update is rejected with [`StorageError::PreconditionFailed`]
+/// unless the record passed in has the revision currently stored, and on
+/// success the stored revision is incremented. Deletes take the revision the
+/// caller expects, or `None` to delete regardless.
+///
+/// Deleting only moves a record to the trash: the `get_*` methods stop
+/// returning it, [`Storage::get_trash`] lists it, and updating it with
+/// `trashed_at` cleared restores it. [`Storage::purge_trash`] removes it for good.
+pub trait Storage: Send + Sync {
+    /// Re-read menu items from the backing store, replacing the cached copy
+    fn load_menu_items(&self) -> Result<(), StorageError>;
+    /// Re-read notices from the backing store, replacing the cached copy
+    fn load_notices(&self) -> Result<(), StorageError>;
+    /// Re-read admin users from the backing store, replacing the cached copy
+    fn load_admin_users(&self) -> Result<(), StorageError>;
+    /// Re-read menu presets from the backing store, replacing the cached copy
+    fn load_menu_presets(&self) -> Result<(), StorageError>;
+    /// Re-read menu schedules from the backing store, replacing the cached copy
+    fn load_menu_schedules(&self) -> Result<(), StorageError>;
+    /// Re-read menu categories from the backing store, replacing the cached copy
+    fn load_menu_categories(&self) -> Result<(), StorageError>;
+    /// Re-read ingredients from the backing store, replacing the cached copy
+    fn load_ingredients(&self) -> Result<(), StorageError>;
+    /// Re-read diner feedback from the backing store, replacing the cached copy
+    fn load_feedback(&self) -> Result<(), StorageError>;
+
+    fn get_menu_items(&self) -> Result<Vec<MenuItem>, StorageError>;
+    fn add_menu_item(&self, item: MenuItem) -> Result<(), StorageError>;
+    fn update_menu_item(&self, id: Uuid, updated_item: MenuItem) -> Result<(), StorageError>;
+    fn delete_menu_item(
+        &self,
+        id: Uuid,
+        expected_revision: Option<u64>,
+    ) -> Result<(), StorageError>;
+
+    fn get_notices(&self) -> Result<Vec<Notice>, StorageError>;
+    fn add_notice(&self, notice: Notice) -> Result<(), StorageError>;
+    fn update_notice(&self, id: Uuid, updated_notice: Notice) -> Result<(), StorageError>;
+    fn delete_notice(&self, id: Uuid, expected_revision: Option<u64>) -> Result<(), StorageError>;
+
+    fn get_admin_users(&self) -> Result<Vec<AdminUser>, StorageError>;
+    fn get_admin_user_by_username(&self, username: &str)
+    -> Result<Option<AdminUser>, StorageError>;
+    fn add_admin_user(&self, user: AdminUser) -> Result<(), StorageError>;
+
+    fn get_menu_presets(&self) -> Result<Vec<MenuPreset>, StorageError>;
+    fn add_menu_preset(&self, preset: MenuPreset) -> Result<(), StorageError>;
+    fn update_menu_preset(&self, id: Uuid, updated_preset: MenuPreset) -> Result<(), StorageError>;
+    fn delete_menu_preset(
+        &self,
+        id: Uuid,
+        expected_revision: Option<u64>,
+    ) -> Result<(), StorageError>;
+
+    fn get_menu_schedules(&self) -> Result<Vec<MenuSchedule>, StorageError>;
+    fn add_menu_schedule(&self, schedule: MenuSchedule) -> Result<(), StorageError>;
+    fn update_menu_schedule(
+        &self,
+        id: Uuid,
+        updated_schedule: MenuSchedule,
+    ) -> Result<(), StorageError>;
+    fn delete_menu_schedule(
+        &self,
+        id: Uuid,
+        expected_revision: Option<u64>,
+    ) -> Result<(), StorageError>;
+
+    /// Live categories, in display order
+    fn get_menu_categories(&self) -> Result<Vec<MenuCategory>, StorageError>;
+    fn add_menu_category(&self, category: MenuCategory) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::AddMenuCategory(category)])
+    }
+    fn update_menu_category(
+        &self,
+        id: Uuid,
+        updated_category: MenuCategory,
+    ) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::UpdateMenuCategory(id, updated_category)])
+    }
+    fn delete_menu_category(
+        &self,
+        id: Uuid,
+        expected_revision: Option<u64>,
+    ) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::DeleteMenuCategory(id, expected_revision)])
+    }
+
+    /// Live ingredients, by name
+    fn get_ingredients(&self) -> Result<Vec<Ingredient>, StorageError>;
+    fn add_ingredient(&self, ingredient: Ingredient) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::AddIngredient(ingredient)])
+    }
+    /// Update an ingredient and, in the same change, every live item whose
+    /// recipe uses it and whose allergens or tags still follow the recipe.
+    /// Values someone set by hand are left as they are.
+    ///
+    /// Returns each changed item as it was before and after the change.
+    fn update_ingredient(
+        &self,
+        id: Uuid,
+        updated_ingredient: Ingredient,
+    ) -> Result<Vec<(MenuItem, MenuItem)>, StorageError> {
+        let before = self.get_ingredients()?;
+        let mut after = before.clone();
+        if let Some(ingredient) = after.iter_mut().find(|ingredient| ingredient.id == id) {
+            *ingredient = updated_ingredient.clone();
+        }
+
+        let changed: Vec<(MenuItem, MenuItem)> = self
+            .get_menu_items()?
+            .into_iter()
+            .filter(|item| item.recipe.iter().any(|line| line.ingredient_id == id))
+            .filter_map(|item| {
+                let old = item.derived(&before)?;
+                let new = item.derived(&after)?;
+                let mut updated = item.clone();
+                if item.allergens == old.allergens {
+                    updated.allergens = new.allergens;
+                }
+                if item.may_contain == old.may_contain {
+                    updated.may_contain = new.may_contain;
+                }
+                if item.dietary_tags == old.dietary_tags {
+                    updated.dietary_tags = new.dietary_tags;
+                }
+                let differs = updated.allergens != item.allergens
+                    || updated.may_contain != item.may_contain
+                    || updated.dietary_tags != item.dietary_tags;
+                differs.then_some((item, updated))
+            })
+            .collect();
+
+        let mut ops = vec![BatchOp::UpdateIngredient(id, updated_ingredient)];
+        ops.extend(
+            changed
+                .iter()
+                .map(|(_, after)| BatchOp::UpdateMenuItem(after.id, after.clone())),
+        );
+        self.apply_batch(ops)?;
+
+        // Storage bumped the stored revisions on success
+        Ok(changed
+            .into_iter()
+            .map(|(before, mut after)| {
+                after.revision += 1;
+                (before, after)
+            })
+            .collect())
+    }
+    fn delete_ingredient(
+        &self,
+        id: Uuid,
+        expected_revision: Option<u64>,
+    ) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::DeleteIngredient(id, expected_revision)])
+    }
+
+    /// Live diner feedback, oldest first
+    fn get_feedback(&self) -> Result<Vec<Feedback>, StorageError>;
+    fn add_feedback(&self, feedback: Feedback) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::AddFeedback(feedback)])
+    }
+    fn update_feedback(&self, id: Uuid, updated_feedback: Feedback) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::UpdateFeedback(id, updated_feedback)])
+    }
+    fn delete_feedback(
+        &self,
+        id: Uuid,
+        expected_revision: Option<u64>,
+    ) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::DeleteFeedback(id, expected_revision)])
+    }
+
+    /// Every record currently in the trash
+    fn get_trash(&self) -> Result<Trash, StorageError>;
+
+    /// Permanently remove records trashed before `cutoff`, returning how many were removed
+    fn purge_trash(&self, cutoff: DateTime<Utc>) -> Result<usize, StorageError> {
+        let trash = self.get_trash()?;
+        let expired = |trashed_at: Option<DateTime<Utc>>| trashed_at.is_some_and(|t| t < cutoff);
+
+        let mut ops = Vec::new();
+        ops.extend(
+            trash
+                .menu_items
+                .iter()
+                .filter(|item| expired(item.trashed_at))
+                .map(|item| BatchOp::PurgeMenuItem(item.id)),
+        );
+        ops.extend(
+            trash
+                .notices
+                .iter()
+                .filter(|notice| expired(notice.trashed_at))
+                .map(|notice| BatchOp::PurgeNotice(notice.id)),
+        );
+        ops.extend(
+            trash
+                .menu_presets
+                .iter()
+                .filter(|preset| expired(preset.trashed_at))
+                .map(|preset| BatchOp::PurgeMenuPreset(preset.id)),
+        );
+        ops.extend(
+            trash
+                .menu_schedules
+                .iter()
+                .filter(|schedule| expired(schedule.trashed_at))
+                .map(|schedule| BatchOp::PurgeMenuSchedule(schedule.id)),
+        );
+        ops.extend(
+            trash
+                .menu_categories
+                .iter()
+                .filter(|category| expired(category.trashed_at))
+                .map(|category| BatchOp::PurgeMenuCategory(category.id)),
+        );
+        ops.extend(
+            trash
+                .ingredients
+                .iter()
+                .filter(|ingredient| expired(ingredient.trashed_at))
+                .map(|ingredient| BatchOp::PurgeIngredient(ingredient.id)),
+        );
+        ops.extend(
+            trash
+                .feedback
+                .iter()
+                .filter(|feedback| expired(feedback.trashed_at))
+                .map(|feedback| BatchOp::PurgeFeedback(feedback.id)),
+        );
+
+        let purged = ops.len();
+        if purged > 0 {
+            self.apply_batch(ops)?;
+        }
+        Ok(purged)
+    }
+
+    /// Delete a menu item after dropping it from every live preset that lists it.
+    ///
+    /// Returns each changed preset as it was before and after the change.
+    fn delete_menu_item_cascade(
+        &self,
+        id: Uuid,
+        expected_revision: Option<u64>,
+    ) -> Result<Vec<(MenuPreset, MenuPreset)>, StorageError> {
+        let changed: Vec<(MenuPreset, MenuPreset)> = self
+            .get_menu_presets()?
+            .into_iter()
+            .filter(|preset| preset.menu_item_ids.contains(&id))
+            .map(|before| {
+                let mut after = before.clone();
+                after.menu_item_ids.retain(|item_id| *item_id != id);
+                after.price_overrides.remove(&id);
+                after.par_levels.remove(&id);
+                after.updated_at = Utc::now();
+                (before, after)
+            })
+            .collect();
+
+        let mut ops: Vec<BatchOp> = changed
+            .iter()
+            .map(|(_, after)| BatchOp::UpdateMenuPreset(after.id, after.clone()))
+            .collect();
+        ops.push(BatchOp::DeleteMenuItem(id, expected_revision));
+        self.apply_batch(ops)?;
+
+        // Storage bumped the stored revisions on success
+        Ok(changed
+            .into_iter()
+            .map(|(before, mut after)| {
+                after.revision += 1;
+                (before, after)
+            })
+            .collect())
+    }
+
+    /// Delete a menu preset together with the pending and active schedules that use it.
+    ///
+    /// Returns the schedules that were moved to the trash.
+    fn delete_menu_preset_cascade(
+        &self,
+        id: Uuid,
+        expected_revision: Option<u64>,
+    ) -> Result<Vec<MenuSchedule>, StorageError> {
+        let schedules: Vec<MenuSchedule> = self
+            .get_menu_schedules()?
+            .into_iter()
+            .filter(|schedule| schedule.preset_id == id && integrity::is_upcoming(&schedule.status))
+            .collect();
+
+        let mut ops: Vec<BatchOp> = schedules
+            .iter()
+            .map(|schedule| BatchOp::DeleteMenuSchedule(schedule.id, Some(schedule.revision)))
+            .collect();
+        ops.push(BatchOp::DeleteMenuPreset(id, expected_revision));
+        self.apply_batch(ops)?;
+        Ok(schedules)
+    }
+
+    /// References from live records to records that are missing or in the trash
+    fn find_dangling_references(&self) -> Result<Vec<DanglingReference>, StorageError> {
+        Ok(integrity::find_dangling(
+            &self.get_menu_items()?,
+            &self.get_menu_presets()?,
+            &self.get_menu_schedules()?,
+            &self.get_menu_categories()?,
+            &self.get_ingredients()?,
+            &self.get_trash()?,
+        ))
+    }
+
+    /// Apply several changes as one unit: either every operation is persisted
+    /// or none is, and each affected collection is written only once.
+    fn apply_batch(&self, ops: Vec<BatchOp>) -> Result<(), StorageError>;
+
+    /// Every record in every collection, trash included
+    fn export_all(&self) -> Result<Dataset, StorageError>;
+
+    /// Replace the contents of every collection with `dataset` as one unit
+    fn replace_all(&self, dataset: Dataset) -> Result<(), StorageError>;
+}
+
 pub struct JsonStorage {
     menu_items: Arc<Mutex<Vec<MenuItem>>>,
     notices: Arc<Mutex<Vec<Notice>>>,
     admin_users: Arc<Mutex<Vec<AdminUser>>>,
     menu_presets: Arc<Mutex<Vec<MenuPreset>>>,
     menu_schedules: Arc<Mutex<Vec<MenuSchedule>>>,
+    menu_categories: Arc<Mutex<Vec<MenuCategory>>>,
+    ingredients: Arc<Mutex<Vec<Ingredient>>>,
+    feedback: Arc<Mutex<Vec<Feedback>>>,
     menu_items_path: String,
     notices_path: String,
     admin_users_path: String,
     menu_presets_path: String,
     menu_schedules_path: String,
+    menu_categories_path: String,
+    ingredients_path: String,
+    feedback_path: String,
+    journal: Journal,
 }
 
 impl JsonStorage {
+    // One path per data file
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         menu_items_path: &str,
         notices_path: &str,
         admin_users_path: &str,
         menu_presets_path: &str,
         menu_schedules_path: &str,
+        menu_categories_path: &str,
+        ingredients_path: &str,
+        feedback_path: &str,
     ) -> Result<Self, StorageError> {
         log::debug!("JsonStorage::new() started");
 
@@ -157,6 +778,9 @@ impl JsonStorage {
         let admin_users = Arc::new(Mutex::new(Vec::new()));
         let menu_presets = Arc::new(Mutex::new(Vec::new()));
         let menu_schedules = Arc::new(Mutex::new(Vec::new()));
+        let menu_categories = Arc::new(Mutex::new(Vec::new()));
+        let ingredients = Arc::new(Mutex::new(Vec::new()));
+        let feedback = Arc::new(Mutex::new(Vec::new()));
 
         let storage = Self {
             menu_items,
@@ -164,13 +788,44 @@ impl JsonStorage {
             admin_users,
             menu_presets,
             menu_schedules,
+            menu_categories,
+            ingredients,
+            feedback,
             menu_items_path: menu_items_path.to_string(),
             notices_path: notices_path.to_string(),
             admin_users_path: admin_users_path.to_string(),
             menu_presets_path: menu_presets_path.to_string(),
             menu_schedules_path: menu_schedules_path.to_string(),
+            menu_categories_path: menu_categories_path.to_string(),
+            ingredients_path: ingredients_path.to_string(),
+            feedback_path: feedback_path.to_string(),
+            journal: Journal::new(data_dir.join(".journal.json")),
         };
 
+        // Finish or roll back any write interrupted by a crash
+        log::debug!("Recovering journal...");
+        storage.journal.recover(&[
+            menu_items_path,
+            notices_path,
+            admin_users_path,
+            menu_presets_path,
+            menu_schedules_path,
+            menu_categories_path,
+            ingredients_path,
+            feedback_path,
+        ])?;
+
+        // Bring files written by older versions up to the current schema
+        log::debug!("Checking data file schema versions...");
+        schema::migrate_file(menu_items_path, schema::MENU_ITEMS)?;
+        schema::migrate_file(notices_path, schema::NOTICES)?;
+        schema::migrate_file(admin_users_path, schema::ADMIN_USERS)?;
+        schema::migrate_file(menu_presets_path, schema::MENU_PRESETS)?;
+        schema::migrate_file(menu_schedules_path, schema::MENU_SCHEDULES)?;
+        schema::migrate_file(menu_categories_path, schema::MENU_CATEGORIES)?;
+        schema::migrate_file(ingredients_path, schema::INGREDIENTS)?;
+        schema::migrate_file(feedback_path, schema::FEEDBACK)?;
+
         // Load existing data or create empty files
         log::debug!("Loading menu items...");
         storage.load_menu_items()?;
@@ -192,11 +847,25 @@ impl JsonStorage {
         storage.load_menu_schedules()?;
         log::debug!("Menu schedules loaded successfully");
 
+        log::debug!("Loading menu categories...");
+        storage.load_menu_categories()?;
+        log::debug!("Menu categories loaded successfully");
+
+        log::debug!("Loading ingredients...");
+        storage.load_ingredients()?;
+        log::debug!("Ingredients loaded successfully");
+
+        log::debug!("Loading feedback...");
+        storage.load_feedback()?;
+        log::debug!("Feedback loaded successfully");
+
         log::debug!("JsonStorage::new() completed");
         Ok(storage)
     }
+}
 
-    pub fn load_menu_items(&self) -> Result<(), StorageError> {
+impl Storage for JsonStorage {
+    fn load_menu_items(&self) -> Result<(), StorageError> {
         log::debug!(
             "load_menu_items() started for path: {}",
             self.menu_items_path
@@ -206,68 +875,51 @@ impl JsonStorage {
             log::debug!("Creating empty menu items file");
             // Create empty file with empty array
             let empty_vec: Vec<MenuItem> = Vec::new();
-            let json_data = serde_json::to_string_pretty(&empty_vec)?;
-            fs::write(path, json_data)?;
+            let json_data = schema::encode(&empty_vec, schema::MENU_ITEMS)?;
+            write_atomic(path, json_data.as_bytes())?;
         }
 
-        log::debug!("Reading menu items file");
-        let file_content = fs::read_to_string(path)?;
-        let items: Vec<MenuItem> = serde_json::from_str(&file_content)?;
-
         log::debug!("Acquiring menu items mutex");
         let mut menu_items = self
             .menu_items
             .lock()
             .map_err(|_| StorageError::PoisonError)?;
+
+        log::debug!("Reading menu items file");
+        // Parse while holding the lock so a concurrent write cannot be overwritten
+        // with older file contents, and bad JSON leaves the loaded data as is
+        let file_content = fs::read_to_string(path)?;
+        let items: Vec<MenuItem> = schema::decode(&file_content, schema::MENU_ITEMS)?;
         *menu_items = items;
         log::debug!("Menu items loaded: {} items", menu_items.len());
 
         Ok(())
     }
 
-    pub fn save_menu_items(&self) -> Result<(), StorageError> {
-        let menu_items = self
-            .menu_items
-            .lock()
-            .map_err(|_| StorageError::PoisonError)?;
-        let json_data = serde_json::to_string_pretty(&*menu_items)?;
-        match fs::write(&self.menu_items_path, json_data) {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                log::error!(
-                    "Failed to write to {}: {}",
-                    &self.menu_items_path,
-                    e
-                );
-                Err(e.into())
-            }
-        }
-    }
-
-    pub fn load_notices(&self) -> Result<(), StorageError> {
+    fn load_notices(&self) -> Result<(), StorageError> {
         log::debug!("load_notices() started for path: {}", self.notices_path);
         let path = Path::new(&self.notices_path);
         if !path.exists() {
             log::debug!("Creating empty notices file");
             // Create empty file with empty array
             let empty_vec: Vec<Notice> = Vec::new();
-            let json_data = serde_json::to_string_pretty(&empty_vec)?;
-            fs::write(path, json_data)?;
+            let json_data = schema::encode(&empty_vec, schema::NOTICES)?;
+            write_atomic(path, json_data.as_bytes())?;
         }
 
-        log::debug!("Reading notices file");
-        let file_content = fs::read_to_string(path)?;
-        let notices: Vec<Notice> = serde_json::from_str(&file_content)?;
-
         log::debug!("Acquiring notices mutex");
         let mut notices_lock = self.notices.lock().map_err(|_| StorageError::PoisonError)?;
+
+        log::debug!("Reading notices file");
+        let file_content = fs::read_to_string(path)?;
+        let notices: Vec<Notice> = schema::decode(&file_content, schema::NOTICES)?;
         *notices_lock = notices;
         log::debug!("Notices loaded: {} items", notices_lock.len());
 
         Ok(())
     }
 
-    pub fn load_admin_users(&self) -> Result<(), StorageError> {
+    fn load_admin_users(&self) -> Result<(), StorageError> {
         log::debug!(
             "load_admin_users() started for path: {}",
             self.admin_users_path
@@ -277,67 +929,26 @@ impl JsonStorage {
             log::debug!("Creating empty admin users file");
             // Create empty file with empty array
             let empty_vec: Vec<AdminUser> = Vec::new();
-            let json_data = serde_json::to_string_pretty(&empty_vec)?;
-            fs::write(path, json_data)?;
+            let json_data = schema::encode(&empty_vec, schema::ADMIN_USERS)?;
+            write_atomic(path, json_data.as_bytes())?;
         }
 
-        log::debug!("Reading admin users file");
-        let file_content = fs::read_to_string(path)?;
-        let users: Vec<AdminUser> = serde_json::from_str(&file_content)?;
-
         log::debug!("Acquiring admin users mutex");
         let mut admin_users_lock = self
             .admin_users
             .lock()
             .map_err(|_| StorageError::PoisonError)?;
+
+        log::debug!("Reading admin users file");
+        let file_content = fs::read_to_string(path)?;
+        let users: Vec<AdminUser> = schema::decode(&file_content, schema::ADMIN_USERS)?;
         *admin_users_lock = users;
         log::debug!("Admin users loaded: {} users", admin_users_lock.len());
 
         Ok(())
     }
 
-    pub fn save_notices(&self) -> Result<(), StorageError> {
-        let notices = self.notices.lock().map_err(|_| StorageError::PoisonError)?;
-        let json_data = serde_json::to_string_pretty(&*notices)?;
-        match fs::write(&self.notices_path, json_data) {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                log::error!(
-                    "Failed to write to {}: {}",
-                    &self.notices_path,
-                    e
-                );
-                Err(e.into())
-            }
-        }
-    }
-
-    pub fn save_admin_users(&self) -> Result<(), StorageError> {
-        log::debug!("save_admin_users() started");
-        let admin_users = self
-            .admin_users
-            .lock()
-            .map_err(|_| StorageError::PoisonError)?;
-        log::debug!("Admin users mutex acquired for saving");
-        let json_data = serde_json::to_string_pretty(&*admin_users)?;
-        log::debug!("JSON serialization completed");
-        match fs::write(&self.admin_users_path, json_data) {
-            Ok(_) => {
-                log::debug!("File write completed");
-                Ok(())
-            }
-            Err(e) => {
-                log::error!(
-                    "Failed to write to {}: {}",
-                    &self.admin_users_path,
-                    e
-                );
-                Err(e.into())
-            }
-        }
-    }
-
-    pub fn load_menu_presets(&self) -> Result<(), StorageError> {
+    fn load_menu_presets(&self) -> Result<(), StorageError> {
         log::debug!(
             "load_menu_presets() started for path: {}",
             self.menu_presets_path
@@ -347,26 +958,26 @@ impl JsonStorage {
             log::debug!("Creating empty menu presets file");
             // Create empty file with empty array
             let empty_vec: Vec<MenuPreset> = Vec::new();
-            let json_data = serde_json::to_string_pretty(&empty_vec)?;
-            fs::write(path, json_data)?;
+            let json_data = schema::encode(&empty_vec, schema::MENU_PRESETS)?;
+            write_atomic(path, json_data.as_bytes())?;
         }
 
-        log::debug!("Reading menu presets file");
-        let file_content = fs::read_to_string(path)?;
-        let presets: Vec<MenuPreset> = serde_json::from_str(&file_content)?;
-
         log::debug!("Acquiring menu presets mutex");
         let mut menu_presets = self
             .menu_presets
             .lock()
             .map_err(|_| StorageError::PoisonError)?;
+
+        log::debug!("Reading menu presets file");
+        let file_content = fs::read_to_string(path)?;
+        let presets: Vec<MenuPreset> = schema::decode(&file_content, schema::MENU_PRESETS)?;
         *menu_presets = presets;
         log::debug!("Menu presets loaded: {} items", menu_presets.len());
 
         Ok(())
     }
 
-    pub fn load_menu_schedules(&self) -> Result<(), StorageError> {
+    fn load_menu_schedules(&self) -> Result<(), StorageError> {
         log::debug!(
             "load_menu_schedules() started for path: {}",
             self.menu_schedules_path
@@ -376,222 +987,170 @@ impl JsonStorage {
             log::debug!("Creating empty menu schedules file");
             // Create empty file with empty array
             let empty_vec: Vec<MenuSchedule> = Vec::new();
-            let json_data = serde_json::to_string_pretty(&empty_vec)?;
-            fs::write(path, json_data)?;
+            let json_data = schema::encode(&empty_vec, schema::MENU_SCHEDULES)?;
+            write_atomic(path, json_data.as_bytes())?;
         }
 
-        log::debug!("Reading menu schedules file");
-        let file_content = fs::read_to_string(path)?;
-        let schedules: Vec<MenuSchedule> = serde_json::from_str(&file_content)?;
-
         log::debug!("Acquiring menu schedules mutex");
         let mut menu_schedules = self
             .menu_schedules
             .lock()
             .map_err(|_| StorageError::PoisonError)?;
+
+        log::debug!("Reading menu schedules file");
+        let file_content = fs::read_to_string(path)?;
+        let schedules: Vec<MenuSchedule> = schema::decode(&file_content, schema::MENU_SCHEDULES)?;
         *menu_schedules = schedules;
         log::debug!("Menu schedules loaded: {} items", menu_schedules.len());
 
         Ok(())
     }
 
-    pub fn save_menu_presets(&self) -> Result<(), StorageError> {
-        let menu_presets = self
-            .menu_presets
-            .lock()
-            .map_err(|_| StorageError::PoisonError)?;
-        let json_data = serde_json::to_string_pretty(&*menu_presets)?;
-        match fs::write(&self.menu_presets_path, json_data) {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                log::error!(
-                    "Failed to write to {}: {}",
-                    &self.menu_presets_path,
-                    e
-                );
-                Err(e.into())
-            }
+    fn load_menu_categories(&self) -> Result<(), StorageError> {
+        log::debug!(
+            "load_menu_categories() started for path: {}",
+            self.menu_categories_path
+        );
+        let path = Path::new(&self.menu_categories_path);
+        if !path.exists() {
+            // Start from the categories that used to be built in, which is
+            // also what items written before categories existed refer to
+            log::info!("Creating menu categories file with the default categories");
+            let defaults = category::legacy_categories(Utc::now());
+            let json_data = schema::encode(&defaults, schema::MENU_CATEGORIES)?;
+            write_atomic(path, json_data.as_bytes())?;
         }
-    }
 
-    pub fn save_menu_schedules(&self) -> Result<(), StorageError> {
-        let menu_schedules = self
-            .menu_schedules
+        log::debug!("Acquiring menu categories mutex");
+        let mut menu_categories = self
+            .menu_categories
             .lock()
             .map_err(|_| StorageError::PoisonError)?;
-        let json_data = serde_json::to_string_pretty(&*menu_schedules)?;
-        match fs::write(&self.menu_schedules_path, json_data) {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                log::error!(
-                    "Failed to write to {}: {}",
-                    &self.menu_schedules_path,
-                    e
-                );
-                Err(e.into())
-            }
-        }
+
+        log::debug!("Reading menu categories file");
+        let file_content = fs::read_to_string(path)?;
+        let categories: Vec<MenuCategory> = schema::decode(&file_content, schema::MENU_CATEGORIES)?;
+        *menu_categories = categories;
+        log::debug!("Menu categories loaded: {} items", menu_categories.len());
+
+        Ok(())
     }
 
-    pub fn get_menu_items(&self) -> Result<Vec<MenuItem>, StorageError> {
-        let menu_items = self
-            .menu_items
+    fn load_ingredients(&self) -> Result<(), StorageError> {
+        log::debug!(
+            "load_ingredients() started for path: {}",
+            self.ingredients_path
+        );
+        let path = Path::new(&self.ingredients_path);
+        if !path.exists() {
+            log::debug!("Creating empty ingredients file");
+            let empty_vec: Vec<Ingredient> = Vec::new();
+            let json_data = schema::encode(&empty_vec, schema::INGREDIENTS)?;
+            write_atomic(path, json_data.as_bytes())?;
+        }
+
+        log::debug!("Acquiring ingredients mutex");
+        let mut ingredients = self
+            .ingredients
             .lock()
             .map_err(|_| StorageError::PoisonError)?;
-        Ok(menu_items.clone())
-    }
 
-    pub fn get_notices(&self) -> Result<Vec<Notice>, StorageError> {
-        let notices = self.notices.lock().map_err(|_| StorageError::PoisonError)?;
-        Ok(notices.clone())
+        log::debug!("Reading ingredients file");
+        let file_content = fs::read_to_string(path)?;
+        let loaded: Vec<Ingredient> = schema::decode(&file_content, schema::INGREDIENTS)?;
+        *ingredients = loaded;
+        log::debug!("Ingredients loaded: {} items", ingredients.len());
+
+        Ok(())
     }
 
-    pub fn get_admin_users(&self) -> Result<Vec<AdminUser>, StorageError> {
-        let admin_users = self
-            .admin_users
+    fn load_feedback(&self) -> Result<(), StorageError> {
+        log::debug!("load_feedback() started for path: {}", self.feedback_path);
+        let path = Path::new(&self.feedback_path);
+        if !path.exists() {
+            log::debug!("Creating empty feedback file");
+            let empty_vec: Vec<Feedback> = Vec::new();
+            let json_data = schema::encode(&empty_vec, schema::FEEDBACK)?;
+            write_atomic(path, json_data.as_bytes())?;
+        }
+
+        log::debug!("Acquiring feedback mutex");
+        let mut feedback = self
+            .feedback
             .lock()
             .map_err(|_| StorageError::PoisonError)?;
-        Ok(admin_users.clone())
+
+        log::debug!("Reading feedback file");
+        let file_content = fs::read_to_string(path)?;
+        let loaded: Vec<Feedback> = schema::decode(&file_content, schema::FEEDBACK)?;
+        *feedback = loaded;
+        log::debug!("Feedback loaded: {} entries", feedback.len());
+
+        Ok(())
     }
 
-    pub fn add_menu_item(&self, item: MenuItem) -> Result<(), AppError> {
-        let mut menu_items = self
+    fn get_menu_items(&self) -> Result<Vec<MenuItem>, StorageError> {
+        let menu_items = self
             .menu_items
             .lock()
             .map_err(|_| StorageError::PoisonError)?;
-        menu_items.push(item);
-        // Explicitly drop the lock before calling save_menu_items
-        drop(menu_items);
-        self.save_menu_items().map_err(|e| AppError::from(e))
+        Ok(live(&menu_items))
     }
 
-    pub fn add_notice(&self, notice: Notice) -> Result<(), StorageError> {
-        let mut notices = self.notices.lock().map_err(|_| StorageError::PoisonError)?;
-        notices.push(notice);
-        // Explicitly drop the lock before calling save_notices
-        drop(notices);
-        self.save_notices()
+    fn add_menu_item(&self, item: MenuItem) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::AddMenuItem(item)])
     }
 
-    pub fn update_menu_item(&self, id: Uuid, updated_item: MenuItem) -> Result<(), AppError> {
+    fn update_menu_item(&self, id: Uuid, updated_item: MenuItem) -> Result<(), StorageError> {
         log::debug!(
             "update_menu_item() called with id: {}, item: {:?}",
             id,
             updated_item
         );
-        log::debug!("About to acquire menu_items lock in update_menu_item");
-        let mut menu_items = self
-            .menu_items
-            .lock()
-            .map_err(|_| StorageError::PoisonError)?;
-        log::debug!("Acquired menu_items lock in update_menu_item");
-        if let Some(index) = menu_items.iter().position(|item| item.id == id) {
-            menu_items[index] = updated_item;
-            log::debug!("Item updated in memory");
-            // Explicitly drop the lock before calling save_menu_items
-            drop(menu_items);
-            log::debug!("Released menu_items lock in update_menu_item");
-            log::debug!("About to call save_menu_items()");
-            self.save_menu_items().map_err(|e| AppError::from(e))?;
-            log::debug!("save_menu_items() completed successfully");
-            Ok(())
-        } else {
-            // Explicitly drop the lock before returning error
-            drop(menu_items);
-            log::debug!("Released menu_items lock in update_menu_item (not found)");
-            Err(AppError::Storage(format!(
-                "Menu item with id {} not found",
-                id
-            )))
-        }
+        self.apply_batch(vec![BatchOp::UpdateMenuItem(id, updated_item)])
     }
 
-    pub fn delete_menu_item(&self, id: Uuid) -> Result<(), AppError> {
+    fn delete_menu_item(
+        &self,
+        id: Uuid,
+        expected_revision: Option<u64>,
+    ) -> Result<(), StorageError> {
         log::debug!("delete_menu_item() called with id: {}", id);
-        log::debug!("About to acquire menu_items lock in delete_menu_item");
-        let mut menu_items = self
-            .menu_items
-            .lock()
-            .map_err(|_| StorageError::PoisonError)?;
-        log::debug!("Acquired menu_items lock in delete_menu_item");
-        if let Some(index) = menu_items.iter().position(|item| item.id == id) {
-            menu_items.remove(index);
-            log::debug!("Item removed from memory");
-            // Explicitly drop the lock before calling save_menu_items
-            drop(menu_items);
-            log::debug!("Released menu_items lock in delete_menu_item");
-            log::debug!("About to call save_menu_items()");
-            self.save_menu_items().map_err(|e| AppError::from(e))?;
-            log::debug!("save_menu_items() completed successfully");
-            Ok(())
-        } else {
-            // Explicitly drop the lock before returning error
-            drop(menu_items);
-            log::debug!("Released menu_items lock in delete_menu_item (not found)");
-            Err(AppError::Storage(format!(
-                "Menu item with id {} not found",
-                id
-            )))
-        }
+        self.apply_batch(vec![BatchOp::DeleteMenuItem(id, expected_revision)])
+    }
+
+    fn get_notices(&self) -> Result<Vec<Notice>, StorageError> {
+        let notices = self.notices.lock().map_err(|_| StorageError::PoisonError)?;
+        Ok(live(&notices))
+    }
+
+    fn add_notice(&self, notice: Notice) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::AddNotice(notice)])
     }
 
-    pub fn update_notice(&self, id: Uuid, updated_notice: Notice) -> Result<(), StorageError> {
+    fn update_notice(&self, id: Uuid, updated_notice: Notice) -> Result<(), StorageError> {
         log::debug!(
             "update_notice() called with id: {}, notice: {:?}",
             id,
             updated_notice
         );
-        log::debug!("About to acquire notices lock in update_notice");
-        let mut notices = self.notices.lock().map_err(|_| StorageError::PoisonError)?;
-        log::debug!("Acquired notices lock in update_notice");
-        if let Some(index) = notices.iter().position(|notice| notice.id == id) {
-            notices[index] = updated_notice;
-            log::debug!("Notice updated in memory");
-            // Explicitly drop the lock before calling save_notices
-            drop(notices);
-            log::debug!("Released notices lock in update_notice");
-            log::debug!("About to call save_notices()");
-            self.save_notices()?;
-            log::debug!("save_notices() completed successfully");
-            Ok(())
-        } else {
-            // Explicitly drop the lock before returning error
-            drop(notices);
-            log::debug!("Released notices lock in update_notice (not found)");
-            Err(StorageError::Io(io::Error::new(
-                io::ErrorKind::NotFound,
-                format!("Notice with id {} not found", id),
-            )))
-        }
+        self.apply_batch(vec![BatchOp::UpdateNotice(id, updated_notice)])
     }
 
-    pub fn delete_notice(&self, id: Uuid) -> Result<(), StorageError> {
+    fn delete_notice(&self, id: Uuid, expected_revision: Option<u64>) -> Result<(), StorageError> {
         log::debug!("delete_notice() called with id: {}", id);
-        log::debug!("About to acquire notices lock in delete_notice");
-        let mut notices = self.notices.lock().map_err(|_| StorageError::PoisonError)?;
-        log::debug!("Acquired notices lock in delete_notice");
-        if let Some(index) = notices.iter().position(|notice| notice.id == id) {
-            notices.remove(index);
-            log::debug!("Notice removed from memory");
-            // Explicitly drop the lock before calling save_notices
-            drop(notices);
-            log::debug!("Released notices lock in delete_notice");
-            log::debug!("About to call save_notices()");
-            self.save_notices()?;
-            log::debug!("save_notices() completed successfully");
-            Ok(())
-        } else {
-            // Explicitly drop the lock before returning error
-            drop(notices);
-            log::debug!("Released notices lock in delete_notice (not found)");
-            Err(StorageError::Io(io::Error::new(
-                io::ErrorKind::NotFound,
-                format!("Notice with id {} not found", id),
-            )))
-        }
+        self.apply_batch(vec![BatchOp::DeleteNotice(id, expected_revision)])
     }
 
-    pub fn get_admin_user_by_username(
+    fn get_admin_users(&self) -> Result<Vec<AdminUser>, StorageError> {
+        let admin_users = self
+            .admin_users
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        Ok(admin_users.clone())
+    }
+
+    fn get_admin_user_by_username(
         &self,
         username: &str,
     ) -> Result<Option<AdminUser>, StorageError> {
@@ -606,99 +1165,69 @@ impl JsonStorage {
         Ok(user)
     }
 
-    pub fn add_admin_user(&self, user: AdminUser) -> Result<(), StorageError> {
+    fn add_admin_user(&self, user: AdminUser) -> Result<(), StorageError> {
         log::debug!("add_admin_user() started");
-        {
-            let mut admin_users = self
-                .admin_users
-                .lock()
-                .map_err(|_| StorageError::PoisonError)?;
-            log::debug!("Admin users mutex acquired");
-            admin_users.push(user);
-            log::debug!("User added to vector");
-        } // Lock is released here
-        log::debug!("Saving admin users...");
-        self.save_admin_users()?;
-        log::debug!("add_admin_user() completed successfully");
-        Ok(())
-    }
-
-    pub fn get_menu_presets(&self) -> Result<Vec<MenuPreset>, StorageError> {
-        let menu_presets = self
-            .menu_presets
+        let mut admin_users = self
+            .admin_users
             .lock()
             .map_err(|_| StorageError::PoisonError)?;
-        Ok(menu_presets.clone())
-    }
+        log::debug!("Admin users mutex acquired");
 
-    pub fn get_menu_schedules(&self) -> Result<Vec<MenuSchedule>, StorageError> {
-        let menu_schedules = self
-            .menu_schedules
-            .lock()
-            .map_err(|_| StorageError::PoisonError)?;
-        Ok(menu_schedules.clone())
+        // Persist before touching the live list so a failed write changes nothing
+        let mut new_admin_users = admin_users.clone();
+        new_admin_users.push(user);
+        let json_data = schema::encode(&new_admin_users, schema::ADMIN_USERS)?;
+        self.journal
+            .commit(vec![JournalEntry::new(&self.admin_users_path, json_data)])?;
+        *admin_users = new_admin_users;
+
+        log::debug!("add_admin_user() completed successfully");
+        Ok(())
     }
 
-    pub fn add_menu_preset(&self, preset: MenuPreset) -> Result<(), StorageError> {
-        let mut menu_presets = self
+    fn get_menu_presets(&self) -> Result<Vec<MenuPreset>, StorageError> {
+        let menu_presets = self
             .menu_presets
             .lock()
             .map_err(|_| StorageError::PoisonError)?;
-        menu_presets.push(preset);
-        // Explicitly drop the lock before calling save_menu_presets
-        drop(menu_presets);
-        self.save_menu_presets()
+        Ok(live(&menu_presets))
     }
 
-    pub fn add_menu_schedule(&self, schedule: MenuSchedule) -> Result<(), StorageError> {
-        let mut menu_schedules = self
-            .menu_schedules
-            .lock()
-            .map_err(|_| StorageError::PoisonError)?;
-        menu_schedules.push(schedule);
-        // Explicitly drop the lock before calling save_menu_schedules
-        drop(menu_schedules);
-        self.save_menu_schedules()
+    fn add_menu_preset(&self, preset: MenuPreset) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::AddMenuPreset(preset)])
     }
 
-    pub fn update_menu_preset(
-        &self,
-        id: Uuid,
-        updated_preset: MenuPreset,
-    ) -> Result<(), StorageError> {
+    fn update_menu_preset(&self, id: Uuid, updated_preset: MenuPreset) -> Result<(), StorageError> {
         log::debug!(
             "update_menu_preset() called with id: {}, preset: {:?}",
             id,
             updated_preset
         );
-        log::debug!("About to acquire menu_presets lock in update_menu_preset");
-        let mut menu_presets = self
-            .menu_presets
+        self.apply_batch(vec![BatchOp::UpdateMenuPreset(id, updated_preset)])
+    }
+
+    fn delete_menu_preset(
+        &self,
+        id: Uuid,
+        expected_revision: Option<u64>,
+    ) -> Result<(), StorageError> {
+        log::debug!("delete_menu_preset() called with id: {}", id);
+        self.apply_batch(vec![BatchOp::DeleteMenuPreset(id, expected_revision)])
+    }
+
+    fn get_menu_schedules(&self) -> Result<Vec<MenuSchedule>, StorageError> {
+        let menu_schedules = self
+            .menu_schedules
             .lock()
             .map_err(|_| StorageError::PoisonError)?;
-        log::debug!("Acquired menu_presets lock in update_menu_preset");
-        if let Some(index) = menu_presets.iter().position(|preset| preset.id == id) {
-            menu_presets[index] = updated_preset;
-            log::debug!("Preset updated in memory");
-            // Explicitly drop the lock before calling save_menu_presets
-            drop(menu_presets);
-            log::debug!("Released menu_presets lock in update_menu_preset");
-            log::debug!("About to call save_menu_presets()");
-            self.save_menu_presets()?;
-            log::debug!("save_menu_presets() completed successfully");
-            Ok(())
-        } else {
-            // Explicitly drop the lock before returning error
-            drop(menu_presets);
-            log::debug!("Released menu_presets lock in update_menu_preset (not found)");
-            Err(StorageError::Io(io::Error::new(
-                io::ErrorKind::NotFound,
-                format!("Menu preset with id {} not found", id),
-            )))
-        }
+        Ok(live(&menu_schedules))
+    }
+
+    fn add_menu_schedule(&self, schedule: MenuSchedule) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::AddMenuSchedule(schedule)])
     }
 
-    pub fn update_menu_schedule(
+    fn update_menu_schedule(
         &self,
         id: Uuid,
         updated_schedule: MenuSchedule,
@@ -708,88 +1237,333 @@ impl JsonStorage {
             id,
             updated_schedule
         );
-        log::debug!("About to acquire menu_schedules lock in update_menu_schedule");
-        let mut menu_schedules = self
+        self.apply_batch(vec![BatchOp::UpdateMenuSchedule(id, updated_schedule)])
+    }
+
+    fn delete_menu_schedule(
+        &self,
+        id: Uuid,
+        expected_revision: Option<u64>,
+    ) -> Result<(), StorageError> {
+        log::debug!("delete_menu_schedule() called with id: {}", id);
+        self.apply_batch(vec![BatchOp::DeleteMenuSchedule(id, expected_revision)])
+    }
+
+    fn get_menu_categories(&self) -> Result<Vec<MenuCategory>, StorageError> {
+        let menu_categories = self
+            .menu_categories
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut categories = live(&menu_categories);
+        category::sort(&mut categories);
+        Ok(categories)
+    }
+
+    fn get_ingredients(&self) -> Result<Vec<Ingredient>, StorageError> {
+        let ingredients = self
+            .ingredients
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut live_ingredients = live(&ingredients);
+        live_ingredients.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(live_ingredients)
+    }
+
+    fn get_feedback(&self) -> Result<Vec<Feedback>, StorageError> {
+        let feedback = self
+            .feedback
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut live_feedback = live(&feedback);
+        live_feedback.sort_by_key(|entry| entry.created_at);
+        Ok(live_feedback)
+    }
+
+    fn get_trash(&self) -> Result<Trash, StorageError> {
+        // Same lock order as apply_batch
+        let menu_items = self
+            .menu_items
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let notices = self.notices.lock().map_err(|_| StorageError::PoisonError)?;
+        let menu_presets = self
+            .menu_presets
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let menu_schedules = self
             .menu_schedules
             .lock()
             .map_err(|_| StorageError::PoisonError)?;
-        log::debug!("Acquired menu_schedules lock in update_menu_schedule");
-        if let Some(index) = menu_schedules.iter().position(|schedule| schedule.id == id) {
-            menu_schedules[index] = updated_schedule;
-            log::debug!("Schedule updated in memory");
-            // Explicitly drop the lock before calling save_menu_schedules
-            drop(menu_schedules);
-            log::debug!("Released menu_schedules lock in update_menu_schedule");
-            log::debug!("About to call save_menu_schedules()");
-            self.save_menu_schedules()?;
-            log::debug!("save_menu_schedules() completed successfully");
-            Ok(())
-        } else {
-            // Explicitly drop the lock before returning error
-            drop(menu_schedules);
-            log::debug!("Released menu_schedules lock in update_menu_schedule (not found)");
-            Err(StorageError::Io(io::Error::new(
-                io::ErrorKind::NotFound,
-                format!("Menu schedule with id {} not found", id),
-            )))
-        }
+        let menu_categories = self
+            .menu_categories
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let ingredients = self
+            .ingredients
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let feedback = self
+            .feedback
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        Ok(Trash {
+            menu_items: trashed(&menu_items),
+            notices: trashed(&notices),
+            menu_presets: trashed(&menu_presets),
+            menu_schedules: trashed(&menu_schedules),
+            menu_categories: trashed(&menu_categories),
+            ingredients: trashed(&ingredients),
+            feedback: trashed(&feedback),
+        })
     }
 
-    pub fn delete_menu_preset(&self, id: Uuid) -> Result<(), StorageError> {
-        log::debug!("delete_menu_preset() called with id: {}", id);
-        log::debug!("About to acquire menu_presets lock in delete_menu_preset");
+    fn apply_batch(&self, ops: Vec<BatchOp>) -> Result<(), StorageError> {
+        log::debug!("apply_batch() called with {} operations", ops.len());
+
+        // Lock every collection in a fixed order so concurrent batches cannot deadlock
+        let mut menu_items = self
+            .menu_items
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut notices = self.notices.lock().map_err(|_| StorageError::PoisonError)?;
         let mut menu_presets = self
             .menu_presets
             .lock()
             .map_err(|_| StorageError::PoisonError)?;
-        log::debug!("Acquired menu_presets lock in delete_menu_preset");
-        if let Some(index) = menu_presets.iter().position(|preset| preset.id == id) {
-            menu_presets.remove(index);
-            log::debug!("Preset removed from memory");
-            // Explicitly drop the lock before calling save_menu_presets
-            drop(menu_presets);
-            log::debug!("Released menu_presets lock in delete_menu_preset");
-            log::debug!("About to call save_menu_presets()");
-            self.save_menu_presets()?;
-            log::debug!("save_menu_presets() completed successfully");
-            Ok(())
-        } else {
-            // Explicitly drop the lock before returning error
-            drop(menu_presets);
-            log::debug!("Released menu_presets lock in delete_menu_preset (not found)");
-            Err(StorageError::Io(io::Error::new(
-                io::ErrorKind::NotFound,
-                format!("Menu preset with id {} not found", id),
-            )))
+        let mut menu_schedules = self
+            .menu_schedules
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut menu_categories = self
+            .menu_categories
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut ingredients = self
+            .ingredients
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut feedback = self
+            .feedback
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+
+        // Work on copies so a failing operation leaves the live data untouched
+        let mut new_menu_items = menu_items.clone();
+        let mut new_notices = notices.clone();
+        let mut new_menu_presets = menu_presets.clone();
+        let mut new_menu_schedules = menu_schedules.clone();
+        let mut new_menu_categories = menu_categories.clone();
+        let mut new_ingredients = ingredients.clone();
+        let mut new_feedback = feedback.clone();
+        let touched = apply_ops(
+            BatchTarget {
+                menu_items: &mut new_menu_items,
+                notices: &mut new_notices,
+                menu_presets: &mut new_menu_presets,
+                menu_schedules: &mut new_menu_schedules,
+                menu_categories: &mut new_menu_categories,
+                ingredients: &mut new_ingredients,
+                feedback: &mut new_feedback,
+            },
+            ops,
+        )?;
+
+        let mut entries = Vec::new();
+        if touched.menu_items {
+            entries.push(JournalEntry::new(
+                &self.menu_items_path,
+                schema::encode(&new_menu_items, schema::MENU_ITEMS)?,
+            ));
+        }
+        if touched.notices {
+            entries.push(JournalEntry::new(
+                &self.notices_path,
+                schema::encode(&new_notices, schema::NOTICES)?,
+            ));
+        }
+        if touched.menu_presets {
+            entries.push(JournalEntry::new(
+                &self.menu_presets_path,
+                schema::encode(&new_menu_presets, schema::MENU_PRESETS)?,
+            ));
+        }
+        if touched.menu_schedules {
+            entries.push(JournalEntry::new(
+                &self.menu_schedules_path,
+                schema::encode(&new_menu_schedules, schema::MENU_SCHEDULES)?,
+            ));
+        }
+        if touched.menu_categories {
+            entries.push(JournalEntry::new(
+                &self.menu_categories_path,
+                schema::encode(&new_menu_categories, schema::MENU_CATEGORIES)?,
+            ));
+        }
+        if touched.ingredients {
+            entries.push(JournalEntry::new(
+                &self.ingredients_path,
+                schema::encode(&new_ingredients, schema::INGREDIENTS)?,
+            ));
+        }
+        if touched.feedback {
+            entries.push(JournalEntry::new(
+                &self.feedback_path,
+                schema::encode(&new_feedback, schema::FEEDBACK)?,
+            ));
+        }
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        log::debug!("Persisting batch across {} files", entries.len());
+        self.journal.commit(entries)?;
+
+        if touched.menu_items {
+            *menu_items = new_menu_items;
+        }
+        if touched.notices {
+            *notices = new_notices;
+        }
+        if touched.menu_presets {
+            *menu_presets = new_menu_presets;
+        }
+        if touched.menu_schedules {
+            *menu_schedules = new_menu_schedules;
+        }
+        if touched.menu_categories {
+            *menu_categories = new_menu_categories;
+        }
+        if touched.ingredients {
+            *ingredients = new_ingredients;
+        }
+        if touched.feedback {
+            *feedback = new_feedback;
         }
+        log::debug!("apply_batch() completed successfully");
+        Ok(())
     }
 
-    pub fn delete_menu_schedule(&self, id: Uuid) -> Result<(), StorageError> {
-        log::debug!("delete_menu_schedule() called with id: {}", id);
-        log::debug!("About to acquire menu_schedules lock in delete_menu_schedule");
+    fn export_all(&self) -> Result<Dataset, StorageError> {
+        // Same lock order as apply_batch, with admin users last
+        let menu_items = self
+            .menu_items
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let notices = self.notices.lock().map_err(|_| StorageError::PoisonError)?;
+        let menu_presets = self
+            .menu_presets
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let menu_schedules = self
+            .menu_schedules
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let menu_categories = self
+            .menu_categories
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let ingredients = self
+            .ingredients
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let feedback = self
+            .feedback
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let admin_users = self
+            .admin_users
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        Ok(Dataset {
+            menu_items: menu_items.clone(),
+            notices: notices.clone(),
+            admin_users: admin_users.clone(),
+            menu_presets: menu_presets.clone(),
+            menu_schedules: menu_schedules.clone(),
+            menu_categories: menu_categories.clone(),
+            ingredients: ingredients.clone(),
+            feedback: feedback.clone(),
+        })
+    }
+
+    fn replace_all(&self, dataset: Dataset) -> Result<(), StorageError> {
+        log::debug!("replace_all() called");
+        let mut menu_items = self
+            .menu_items
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut notices = self.notices.lock().map_err(|_| StorageError::PoisonError)?;
+        let mut menu_presets = self
+            .menu_presets
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
         let mut menu_schedules = self
             .menu_schedules
             .lock()
             .map_err(|_| StorageError::PoisonError)?;
-        log::debug!("Acquired menu_schedules lock in delete_menu_schedule");
-        if let Some(index) = menu_schedules.iter().position(|schedule| schedule.id == id) {
-            menu_schedules.remove(index);
-            log::debug!("Schedule removed from memory");
-            // Explicitly drop the lock before calling save_menu_schedules
-            drop(menu_schedules);
-            log::debug!("Released menu_schedules lock in delete_menu_schedule");
-            log::debug!("About to call save_menu_schedules()");
-            self.save_menu_schedules()?;
-            log::debug!("save_menu_schedules() completed successfully");
-            Ok(())
-        } else {
-            // Explicitly drop the lock before returning error
-            drop(menu_schedules);
-            log::debug!("Released menu_schedules lock in delete_menu_schedule (not found)");
-            Err(StorageError::Io(io::Error::new(
-                io::ErrorKind::NotFound,
-                format!("Menu schedule with id {} not found", id),
-            )))
-        }
+        let mut menu_categories = self
+            .menu_categories
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut ingredients = self
+            .ingredients
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut feedback = self
+            .feedback
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut admin_users = self
+            .admin_users
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+
+        // All eight files go through one journal commit so a crash cannot
+        // leave a mix of old and restored collections behind
+        self.journal.commit(vec![
+            JournalEntry::new(
+                &self.menu_items_path,
+                schema::encode(&dataset.menu_items, schema::MENU_ITEMS)?,
+            ),
+            JournalEntry::new(
+                &self.notices_path,
+                schema::encode(&dataset.notices, schema::NOTICES)?,
+            ),
+            JournalEntry::new(
+                &self.admin_users_path,
+                schema::encode(&dataset.admin_users, schema::ADMIN_USERS)?,
+            ),
+            JournalEntry::new(
+                &self.menu_presets_path,
+                schema::encode(&dataset.menu_presets, schema::MENU_PRESETS)?,
+            ),
+            JournalEntry::new(
+                &self.menu_schedules_path,
+                schema::encode(&dataset.menu_schedules, schema::MENU_SCHEDULES)?,
+            ),
+            JournalEntry::new(
+                &self.menu_categories_path,
+                schema::encode(&dataset.menu_categories, schema::MENU_CATEGORIES)?,
+            ),
+            JournalEntry::new(
+                &self.ingredients_path,
+                schema::encode(&dataset.ingredients, schema::INGREDIENTS)?,
+            ),
+            JournalEntry::new(
+                &self.feedback_path,
+                schema::encode(&dataset.feedback, schema::FEEDBACK)?,
+            ),
+        ])?;
+
+        *menu_items = dataset.menu_items;
+        *notices = dataset.notices;
+        *admin_users = dataset.admin_users;
+        *menu_presets = dataset.menu_presets;
+        *menu_schedules = dataset.menu_schedules;
+        *menu_categories = dataset.menu_categories;
+        *ingredients = dataset.ingredients;
+        *feedback = dataset.feedback;
+        log::debug!("replace_all() completed successfully");
+        Ok(())
     }
 }
@@ -0,0 +1,266 @@
+use serde::{Deserialize, Serialize};
+
+use super::Coded;
+
+/// The 14 allergens that UK and EU food law requires to be declared
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Allergen {
+    Celery,
+    /// Cereals containing gluten: wheat, rye, barley, oats
+    Gluten,
+    Crustaceans,
+    Eggs,
+    Fish,
+    Lupin,
+    Milk,
+    Molluscs,
+    Mustard,
+    /// Tree nuts such as almonds, hazelnuts and walnuts
+    TreeNuts,
+    Peanuts,
+    Sesame,
+    Soya,
+    /// Sulphur dioxide and sulphites
+    Sulphites,
+}
+
+impl Allergen {
+    pub const ALL: [Allergen; 14] = [
+        Allergen::Celery,
+        Allergen::Gluten,
+        Allergen::Crustaceans,
+        Allergen::Eggs,
+        Allergen::Fish,
+        Allergen::Lupin,
+        Allergen::Milk,
+        Allergen::Molluscs,
+        Allergen::Mustard,
+        Allergen::TreeNuts,
+        Allergen::Peanuts,
+        Allergen::Sesame,
+        Allergen::Soya,
+        Allergen::Sulphites,
+    ];
+
+    /// Name used in the API and data files
+    pub fn code(self) -> &'static str {
+        match self {
+            Allergen::Celery => "Celery",
+            Allergen::Gluten => "Gluten",
+            Allergen::Crustaceans => "Crustaceans",
+            Allergen::Eggs => "Eggs",
+            Allergen::Fish => "Fish",
+            Allergen::Lupin => "Lupin",
+            Allergen::Milk => "Milk",
+            Allergen::Molluscs => "Molluscs",
+            Allergen::Mustard => "Mustard",
+            Allergen::TreeNuts => "TreeNuts",
+            Allergen::Peanuts => "Peanuts",
+            Allergen::Sesame => "Sesame",
+            Allergen::Soya => "Soya",
+            Allergen::Sulphites => "Sulphites",
+        }
+    }
+
+    /// Name shown to diners, as worded in the regulations
+    pub fn label(self) -> &'static str {
+        match self {
+            Allergen::Celery => "Celery",
+            Allergen::Gluten => "Cereals containing gluten",
+            Allergen::Crustaceans => "Crustaceans",
+            Allergen::Eggs => "Eggs",
+            Allergen::Fish => "Fish",
+            Allergen::Lupin => "Lupin",
+            Allergen::Milk => "Milk",
+            Allergen::Molluscs => "Molluscs",
+            Allergen::Mustard => "Mustard",
+            Allergen::TreeNuts => "Tree nuts",
+            Allergen::Peanuts => "Peanuts",
+            Allergen::Sesame => "Sesame",
+            Allergen::Soya => "Soya",
+            Allergen::Sulphites => "Sulphur dioxide and sulphites",
+        }
+    }
+
+    /// Best guess for a free-text value written before allergens were typed
+    fn recognize(value: &str) -> Option<Allergen> {
+        if let Some(allergen) = Self::parse(value) {
+            return Some(allergen);
+        }
+        let allergen = match value.trim().to_lowercase().as_str() {
+            "celeriac" => Allergen::Celery,
+            "wheat" | "barley" | "rye" | "oats" | "spelt" | "cereals" => Allergen::Gluten,
+            "crustacean" | "prawn" | "prawns" | "shrimp" | "crab" | "lobster" => {
+                Allergen::Crustaceans
+            }
+            "egg" => Allergen::Eggs,
+            "lupine" | "lupins" => Allergen::Lupin,
+            "dairy" | "lactose" => Allergen::Milk,
+            "mollusc" | "mollusk" | "mollusks" | "mussels" | "oysters" | "squid" => {
+                Allergen::Molluscs
+            }
+            "nuts" | "nut" | "tree nut" | "treenut" | "almonds" | "hazelnuts" | "walnuts"
+            | "cashews" | "pecans" | "pistachios" => Allergen::TreeNuts,
+            "peanut" | "groundnut" | "groundnuts" => Allergen::Peanuts,
+            "mustard seed" | "mustard seeds" => Allergen::Mustard,
+            "sesame seeds" | "sesame seed" => Allergen::Sesame,
+            "soy" | "soybean" | "soybeans" | "soy beans" => Allergen::Soya,
+            "sulphite" | "sulfite" | "sulfites" | "sulphur dioxide" | "sulfur dioxide" => {
+                Allergen::Sulphites
+            }
+            _ => return None,
+        };
+        Some(allergen)
+    }
+}
+
+/// Free-text phrases that mark a trace warning rather than an ingredient
+const MAY_CONTAIN_PREFIXES: &[&str] = &["may contain traces of", "may contain", "traces of"];
+
+/// Outcome of mapping an item's legacy free-text allergens
+#[derive(Debug, Default)]
+pub struct LegacyAllergens {
+    pub contains: Vec<Allergen>,
+    pub may_contain: Vec<Allergen>,
+    /// Values that matched no allergen and need a person to look at them
+    pub unmapped: Vec<String>,
+}
+
+/// Map the free-text allergens of one menu item onto [`Allergen`], logging
+/// every value that could not be mapped so it shows up in the upgrade log
+pub fn map_legacy(item_id: &str, item_name: &str, values: &[String]) -> LegacyAllergens {
+    let mut mapped = LegacyAllergens::default();
+    for value in values {
+        let lower = value.trim().to_lowercase();
+        let (trace, rest) = MAY_CONTAIN_PREFIXES
+            .iter()
+            .find_map(|prefix| lower.strip_prefix(prefix))
+            .map_or((false, lower.as_str()), |rest| {
+                (true, rest.trim_start_matches([':', ' ']))
+            });
+
+        match Allergen::recognize(rest) {
+            Some(allergen) => {
+                log::info!(
+                    "Mapped allergen '{}' on menu item '{}' ({}) to {}{}",
+                    value,
+                    item_name,
+                    item_id,
+                    if trace { "may contain " } else { "" },
+                    allergen.code()
+                );
+                if trace {
+                    mapped.may_contain.push(allergen);
+                } else {
+                    mapped.contains.push(allergen);
+                }
+            }
+            None if value.trim().is_empty() => {}
+            None => {
+                log::warn!(
+                    "Could not map allergen '{}' on menu item '{}' ({}); kept in unmapped_allergens",
+                    value,
+                    item_name,
+                    item_id
+                );
+                mapped.unmapped.push(value.clone());
+            }
+        }
+    }
+
+    normalize(&mut mapped.contains);
+    normalize(&mut mapped.may_contain);
+    // Contains takes precedence over a trace warning for the same allergen
+    mapped
+        .may_contain
+        .retain(|allergen| !mapped.contains.contains(allergen));
+    mapped
+}
+
+/// Sort and deduplicate a list of allergens
+pub fn normalize(allergens: &mut Vec<Allergen>) {
+    allergens.sort();
+    allergens.dedup();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_accepts_codes_and_labels_in_any_case() {
+        assert_eq!(Allergen::parse("TreeNuts"), Some(Allergen::TreeNuts));
+        assert_eq!(Allergen::parse(" tree nuts "), Some(Allergen::TreeNuts));
+        assert_eq!(
+            Allergen::parse("CEREALS CONTAINING GLUTEN"),
+            Some(Allergen::Gluten)
+        );
+        // Synonyms are only for upgrading old data, not for the API
+        assert_eq!(Allergen::parse("dairy"), None);
+    }
+
+    #[test]
+    fn legacy_synonyms_are_mapped() {
+        let values = ["Dairy", "wheat", "soy", "Prawns", "sulfites", "egg"].map(String::from);
+
+        let mapped = map_legacy("1", "Stir fry", &values);
+
+        assert_eq!(
+            mapped.contains,
+            vec![
+                Allergen::Gluten,
+                Allergen::Crustaceans,
+                Allergen::Eggs,
+                Allergen::Milk,
+                Allergen::Soya,
+                Allergen::Sulphites,
+            ]
+        );
+        assert!(mapped.may_contain.is_empty());
+        assert!(mapped.unmapped.is_empty());
+    }
+
+    #[test]
+    fn trace_warnings_go_to_may_contain() {
+        let values = [
+            "May contain traces of peanuts",
+            "may contain: sesame",
+            "Traces of nuts",
+        ]
+        .map(String::from);
+
+        let mapped = map_legacy("1", "Flapjack", &values);
+
+        assert!(mapped.contains.is_empty());
+        assert_eq!(
+            mapped.may_contain,
+            vec![Allergen::TreeNuts, Allergen::Peanuts, Allergen::Sesame]
+        );
+    }
+
+    #[test]
+    fn contains_wins_over_a_trace_of_the_same_allergen() {
+        let values = ["milk", "dairy", "may contain milk", "may contain egg"].map(String::from);
+
+        let mapped = map_legacy("1", "Custard", &values);
+
+        assert_eq!(mapped.contains, vec![Allergen::Milk]);
+        assert_eq!(mapped.may_contain, vec![Allergen::Eggs]);
+    }
+
+    #[test]
+    fn unrecognised_values_are_kept_verbatim() {
+        let values = ["Milk", "Unicorn Dust", "  ", "may contain glitter"].map(String::from);
+
+        let mapped = map_legacy("1", "Cake", &values);
+
+        assert_eq!(mapped.contains, vec![Allergen::Milk]);
+        assert_eq!(
+            mapped.unmapped,
+            vec![
+                "Unicorn Dust".to_string(),
+                "may contain glitter".to_string()
+            ]
+        );
+    }
+}
@@ -0,0 +1,568 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::integrity::{
+    CASCADE_REMEDY, MOVE_ITEMS_REMEDY, REMOVE_FROM_RECIPES_REMEDY, check_item_references,
+    check_preset_references, check_schedule_references, ensure_unreferenced, item_dependents,
+    preset_dependents, recipe_dependents, schedule_dependents,
+};
+use super::{
+    Feedback, Ingredient, MenuCategory, MenuItem, MenuPreset, MenuSchedule, Notice, StorageError,
+};
+
+/// A single change applied as part of [`Storage::apply_batch`](super::Storage::apply_batch)
+///
+/// Updates only succeed if the record's `revision` still matches the stored
+/// one, and bump it on success. Deletes move a live record to the trash and
+/// optionally carry the revision the caller last saw; `None` deletes
+/// unconditionally. Purges remove an already trashed record for good.
+///
+/// References are checked as each operation runs: a menu item needs a live
+/// category and live recipe ingredients, a preset may only list live menu
+/// items, a pending or active schedule needs a live preset, and none of them
+/// can be deleted while something live still refers to it.
+#[derive(Debug, Clone)]
+pub enum BatchOp {
+    AddMenuItem(MenuItem),
+    UpdateMenuItem(Uuid, MenuItem),
+    DeleteMenuItem(Uuid, Option<u64>),
+    PurgeMenuItem(Uuid),
+    AddNotice(Notice),
+    UpdateNotice(Uuid, Notice),
+    DeleteNotice(Uuid, Option<u64>),
+    PurgeNotice(Uuid),
+    AddMenuPreset(MenuPreset),
+    UpdateMenuPreset(Uuid, MenuPreset),
+    DeleteMenuPreset(Uuid, Option<u64>),
+    PurgeMenuPreset(Uuid),
+    AddMenuSchedule(MenuSchedule),
+    UpdateMenuSchedule(Uuid, MenuSchedule),
+    DeleteMenuSchedule(Uuid, Option<u64>),
+    PurgeMenuSchedule(Uuid),
+    AddMenuCategory(MenuCategory),
+    UpdateMenuCategory(Uuid, MenuCategory),
+    DeleteMenuCategory(Uuid, Option<u64>),
+    PurgeMenuCategory(Uuid),
+    AddIngredient(Ingredient),
+    UpdateIngredient(Uuid, Ingredient),
+    DeleteIngredient(Uuid, Option<u64>),
+    PurgeIngredient(Uuid),
+    AddFeedback(Feedback),
+    UpdateFeedback(Uuid, Feedback),
+    DeleteFeedback(Uuid, Option<u64>),
+    PurgeFeedback(Uuid),
+}
+
+/// Working copies of the collections a batch can modify
+pub struct BatchTarget<'a> {
+    pub menu_items: &'a mut Vec<MenuItem>,
+    pub notices: &'a mut Vec<Notice>,
+    pub menu_presets: &'a mut Vec<MenuPreset>,
+    pub menu_schedules: &'a mut Vec<MenuSchedule>,
+    pub menu_categories: &'a mut Vec<MenuCategory>,
+    pub ingredients: &'a mut Vec<Ingredient>,
+    pub feedback: &'a mut Vec<Feedback>,
+}
+
+/// Which collections a batch changed, so only those get persisted
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Touched {
+    pub menu_items: bool,
+    pub notices: bool,
+    pub menu_presets: bool,
+    pub menu_schedules: bool,
+    pub menu_categories: bool,
+    pub ingredients: bool,
+    pub feedback: bool,
+}
+
+/// Apply `ops` in order to in-memory collections.
+///
+/// Stops at the first failing operation. Callers work on copies and only
+/// swap them in once this returns `Ok`, which keeps the batch all-or-nothing.
+pub fn apply_ops(target: BatchTarget<'_>, ops: Vec<BatchOp>) -> Result<Touched, StorageError> {
+    let mut touched = Touched::default();
+    for op in ops {
+        match op {
+            BatchOp::AddMenuItem(item) => {
+                check_item_references(&item, target.menu_categories, target.ingredients)?;
+                target.menu_items.push(item);
+                touched.menu_items = true;
+            }
+            BatchOp::UpdateMenuItem(id, item) => {
+                check_item_references(&item, target.menu_categories, target.ingredients)?;
+                replace(target.menu_items, id, item)?;
+                touched.menu_items = true;
+            }
+            BatchOp::DeleteMenuItem(id, expected) => {
+                ensure_unreferenced(
+                    MenuItem::LABEL,
+                    id,
+                    &preset_dependents(id, target.menu_presets),
+                    CASCADE_REMEDY,
+                )?;
+                trash(target.menu_items, id, expected)?;
+                touched.menu_items = true;
+            }
+            BatchOp::PurgeMenuItem(id) => {
+                purge(target.menu_items, id)?;
+                touched.menu_items = true;
+            }
+            BatchOp::AddNotice(notice) => {
+                target.notices.push(notice);
+                touched.notices = true;
+            }
+            BatchOp::UpdateNotice(id, notice) => {
+                replace(target.notices, id, notice)?;
+                touched.notices = true;
+            }
+            BatchOp::DeleteNotice(id, expected) => {
+                trash(target.notices, id, expected)?;
+                touched.notices = true;
+            }
+            BatchOp::PurgeNotice(id) => {
+                purge(target.notices, id)?;
+                touched.notices = true;
+            }
+            BatchOp::AddMenuPreset(preset) => {
+                check_preset_references(&preset, target.menu_items)?;
+                target.menu_presets.push(preset);
+                touched.menu_presets = true;
+            }
+            BatchOp::UpdateMenuPreset(id, preset) => {
+                check_preset_references(&preset, target.menu_items)?;
+                replace(target.menu_presets, id, preset)?;
+                touched.menu_presets = true;
+            }
+            BatchOp::DeleteMenuPreset(id, expected) => {
+                ensure_unreferenced(
+                    MenuPreset::LABEL,
+                    id,
+                    &schedule_dependents(id, target.menu_schedules),
+                    CASCADE_REMEDY,
+                )?;
+                trash(target.menu_presets, id, expected)?;
+                touched.menu_presets = true;
+            }
+            BatchOp::PurgeMenuPreset(id) => {
+                purge(target.menu_presets, id)?;
+                touched.menu_presets = true;
+            }
+            BatchOp::AddMenuSchedule(schedule) => {
+                check_schedule_references(&schedule, target.menu_presets)?;
+                target.menu_schedules.push(schedule);
+                touched.menu_schedules = true;
+            }
+            BatchOp::UpdateMenuSchedule(id, schedule) => {
+                check_schedule_references(&schedule, target.menu_presets)?;
+                replace(target.menu_schedules, id, schedule)?;
+                touched.menu_schedules = true;
+            }
+            BatchOp::DeleteMenuSchedule(id, expected) => {
+                trash(target.menu_schedules, id, expected)?;
+                touched.menu_schedules = true;
+            }
+            BatchOp::PurgeMenuSchedule(id) => {
+                purge(target.menu_schedules, id)?;
+                touched.menu_schedules = true;
+            }
+            BatchOp::AddMenuCategory(category) => {
+                target.menu_categories.push(category);
+                touched.menu_categories = true;
+            }
+            BatchOp::UpdateMenuCategory(id, category) => {
+                replace(target.menu_categories, id, category)?;
+                touched.menu_categories = true;
+            }
+            BatchOp::DeleteMenuCategory(id, expected) => {
+                ensure_unreferenced(
+                    MenuCategory::LABEL,
+                    id,
+                    &item_dependents(id, target.menu_items),
+                    MOVE_ITEMS_REMEDY,
+                )?;
+                trash(target.menu_categories, id, expected)?;
+                touched.menu_categories = true;
+            }
+            BatchOp::PurgeMenuCategory(id) => {
+                purge(target.menu_categories, id)?;
+                touched.menu_categories = true;
+            }
+            BatchOp::AddIngredient(ingredient) => {
+                target.ingredients.push(ingredient);
+                touched.ingredients = true;
+            }
+            BatchOp::UpdateIngredient(id, ingredient) => {
+                replace(target.ingredients, id, ingredient)?;
+                touched.ingredients = true;
+            }
+            BatchOp::DeleteIngredient(id, expected) => {
+                ensure_unreferenced(
+                    Ingredient::LABEL,
+                    id,
+                    &recipe_dependents(id, target.menu_items),
+                    REMOVE_FROM_RECIPES_REMEDY,
+                )?;
+                trash(target.ingredients, id, expected)?;
+                touched.ingredients = true;
+            }
+            BatchOp::PurgeIngredient(id) => {
+                purge(target.ingredients, id)?;
+                touched.ingredients = true;
+            }
+            BatchOp::AddFeedback(feedback) => {
+                target.feedback.push(feedback);
+                touched.feedback = true;
+            }
+            BatchOp::UpdateFeedback(id, feedback) => {
+                replace(target.feedback, id, feedback)?;
+                touched.feedback = true;
+            }
+            BatchOp::DeleteFeedback(id, expected) => {
+                trash(target.feedback, id, expected)?;
+                touched.feedback = true;
+            }
+            BatchOp::PurgeFeedback(id) => {
+                purge(target.feedback, id)?;
+                touched.feedback = true;
+            }
+        }
+    }
+    Ok(touched)
+}
+
+/// Build the error reported when a write was based on an outdated revision
+pub fn stale_revision(label: &str, id: Uuid, expected: u64, current: u64) -> StorageError {
+    StorageError::PreconditionFailed(format!(
+        "{} with id {} was modified by someone else (expected revision {}, current revision {})",
+        label, id, expected, current
+    ))
+}
+
+/// Common accessors for the collections a batch can modify
+pub trait Record {
+    const LABEL: &'static str;
+
+    fn id(&self) -> Uuid;
+    fn revision(&self) -> u64;
+    fn set_revision(&mut self, revision: u64);
+    fn trashed_at(&self) -> Option<DateTime<Utc>>;
+    fn set_trashed_at(&mut self, trashed_at: Option<DateTime<Utc>>);
+}
+
+/// Implement [`Record`] for types with `id`, `revision` and `trashed_at` fields
+macro_rules! impl_record {
+    ($($ty:ty => $label:literal),+ $(,)?) => {
+        $(
+            impl Record for $ty {
+                const LABEL: &'static str = $label;
+
+                fn id(&self) -> Uuid {
+                    self.id
+                }
+                fn revision(&self) -> u64 {
+                    self.revision
+                }
+                fn set_revision(&mut self, revision: u64) {
+                    self.revision = revision;
+                }
+                fn trashed_at(&self) -> Option<DateTime<Utc>> {
+                    self.trashed_at
+                }
+                fn set_trashed_at(&mut self, trashed_at: Option<DateTime<Utc>>) {
+                    self.trashed_at = trashed_at;
+                }
+            }
+        )+
+    };
+}
+
+impl_record! {
+    MenuItem => "Menu item",
+    Notice => "Notice",
+    MenuPreset => "Menu preset",
+    MenuSchedule => "Menu schedule",
+    MenuCategory => "Menu category",
+    Ingredient => "Ingredient",
+    Feedback => "Feedback",
+}
+
+fn replace<T: Record>(records: &mut [T], id: Uuid, mut record: T) -> Result<(), StorageError> {
+    let index = position(records, id)?;
+    check_revision(&records[index], record.revision())?;
+    record.set_revision(records[index].revision() + 1);
+    records[index] = record;
+    Ok(())
+}
+
+fn trash<T: Record>(
+    records: &mut [T],
+    id: Uuid,
+    expected: Option<u64>,
+) -> Result<(), StorageError> {
+    let index = position(records, id)?;
+    let record = &mut records[index];
+    if record.trashed_at().is_some() {
+        return Err(StorageError::NotFound(format!(
+            "{} with id {} not found",
+            T::LABEL,
+            id
+        )));
+    }
+    if let Some(expected) = expected {
+        check_revision(record, expected)?;
+    }
+    record.set_trashed_at(Some(Utc::now()));
+    record.set_revision(record.revision() + 1);
+    Ok(())
+}
+
+fn purge<T: Record>(records: &mut Vec<T>, id: Uuid) -> Result<(), StorageError> {
+    let index = position(records, id)?;
+    if records[index].trashed_at().is_none() {
+        return Err(StorageError::Conflict(format!(
+            "{} with id {} is not in the trash",
+            T::LABEL,
+            id
+        )));
+    }
+    records.remove(index);
+    Ok(())
+}
+
+fn check_revision<T: Record>(current: &T, expected: u64) -> Result<(), StorageError> {
+    if current.revision() == expected {
+        Ok(())
+    } else {
+        Err(stale_revision(
+            T::LABEL,
+            current.id(),
+            expected,
+            current.revision(),
+        ))
+    }
+}
+
+fn position<T: Record>(records: &[T], id: Uuid) -> Result<usize, StorageError> {
+    records
+        .iter()
+        .position(|existing| existing.id() == id)
+        .ok_or_else(|| StorageError::NotFound(format!("{} with id {} not found", T::LABEL, id)))
+}
+
+/// Records that have not been moved to the trash
+pub fn live<T: Record + Clone>(records: &[T]) -> Vec<T> {
+    records
+        .iter()
+        .filter(|record| record.trashed_at().is_none())
+        .cloned()
+        .collect()
+}
+
+/// Records currently in the trash
+pub fn trashed<T: Record + Clone>(records: &[T]) -> Vec<T> {
+    records
+        .iter()
+        .filter(|record| record.trashed_at().is_some())
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{MemoryStorage, NoticeSeverity, Prices, Storage, Translations, category};
+
+    fn item(name: &str) -> MenuItem {
+        MenuItem {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            category_id: category::legacy_id("Mains").unwrap(),
+            description: String::new(),
+            allergens: Vec::new(),
+            is_available: true,
+            revision: 0,
+            trashed_at: None,
+            may_contain: Vec::new(),
+            unmapped_allergens: Vec::new(),
+            dietary_tags: Vec::new(),
+            nutrition: None,
+            prices: Prices::new(),
+            preset_prices: Prices::new(),
+            image: None,
+            recipe: Vec::new(),
+            translations: Translations::new(),
+            stock: None,
+            variants: Vec::new(),
+        }
+    }
+
+    fn notice(title: &str) -> Notice {
+        let now = chrono::Utc::now();
+        Notice {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            content: String::new(),
+            is_active: true,
+            created_at: now,
+            updated_at: now,
+            revision: 0,
+            trashed_at: None,
+            translations: Translations::new(),
+            publish_at: None,
+            expire_at: None,
+            severity: NoticeSeverity::Info,
+            pinned: false,
+            emergency: false,
+        }
+    }
+
+    #[test]
+    fn batch_applies_every_op_and_bumps_revisions() {
+        let storage = MemoryStorage::new();
+        let soup = item("Soup");
+        storage.add_menu_item(soup.clone()).unwrap();
+
+        let mut renamed = soup.clone();
+        renamed.name = "Tomato soup".to_string();
+        storage
+            .apply_batch(vec![
+                BatchOp::UpdateMenuItem(soup.id, renamed),
+                BatchOp::AddNotice(notice("Closed Monday")),
+            ])
+            .unwrap();
+
+        let items = storage.get_menu_items().unwrap();
+        assert_eq!(items[0].name, "Tomato soup");
+        assert_eq!(items[0].revision, 1);
+        assert_eq!(storage.get_notices().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn failing_op_leaves_every_collection_untouched() {
+        let storage = MemoryStorage::new();
+        let soup = item("Soup");
+        storage.add_menu_item(soup.clone()).unwrap();
+
+        let mut renamed = soup.clone();
+        renamed.name = "Tomato soup".to_string();
+        let result = storage.apply_batch(vec![
+            BatchOp::UpdateMenuItem(soup.id, renamed),
+            BatchOp::AddNotice(notice("Closed Monday")),
+            BatchOp::DeleteMenuItem(Uuid::new_v4(), None),
+        ]);
+
+        assert!(matches!(result, Err(StorageError::NotFound(_))));
+        let items = storage.get_menu_items().unwrap();
+        assert_eq!(items[0].name, "Soup");
+        assert_eq!(items[0].revision, 0);
+        assert!(storage.get_notices().unwrap().is_empty());
+    }
+
+    #[test]
+    fn later_ops_see_earlier_ones() {
+        let storage = MemoryStorage::new();
+        let soup = item("Soup");
+
+        storage
+            .apply_batch(vec![
+                BatchOp::AddMenuItem(soup.clone()),
+                BatchOp::DeleteMenuItem(soup.id, None),
+            ])
+            .unwrap();
+
+        assert!(storage.get_menu_items().unwrap().is_empty());
+    }
+
+    #[test]
+    fn update_with_stale_revision_is_rejected() {
+        let storage = MemoryStorage::new();
+        let soup = item("Soup");
+        storage.add_menu_item(soup.clone()).unwrap();
+        storage.update_menu_item(soup.id, soup.clone()).unwrap();
+
+        // Still based on revision 0, which the first update replaced
+        let mut stale = soup.clone();
+        stale.name = "Stale".to_string();
+        let result = storage.update_menu_item(soup.id, stale);
+
+        assert!(matches!(result, Err(StorageError::PreconditionFailed(_))));
+        let items = storage.get_menu_items().unwrap();
+        assert_eq!(items[0].name, "Soup");
+        assert_eq!(items[0].revision, 1);
+    }
+
+    #[test]
+    fn delete_checks_expected_revision_and_moves_to_trash() {
+        let storage = MemoryStorage::new();
+        let soup = item("Soup");
+        storage.add_menu_item(soup.clone()).unwrap();
+
+        assert!(matches!(
+            storage.delete_menu_item(soup.id, Some(7)),
+            Err(StorageError::PreconditionFailed(_))
+        ));
+        storage.delete_menu_item(soup.id, Some(0)).unwrap();
+
+        assert!(storage.get_menu_items().unwrap().is_empty());
+        let trash = storage.get_trash().unwrap();
+        assert_eq!(trash.menu_items.len(), 1);
+        assert_eq!(trash.menu_items[0].revision, 1);
+        assert!(matches!(
+            storage.delete_menu_item(soup.id, None),
+            Err(StorageError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn restore_clears_trashed_at() {
+        let storage = MemoryStorage::new();
+        let soup = item("Soup");
+        storage.add_menu_item(soup.clone()).unwrap();
+        storage.delete_menu_item(soup.id, None).unwrap();
+
+        let mut trashed = storage.get_trash().unwrap().menu_items.remove(0);
+        trashed.trashed_at = None;
+        storage.update_menu_item(soup.id, trashed).unwrap();
+
+        assert_eq!(storage.get_menu_items().unwrap().len(), 1);
+        assert!(storage.get_trash().unwrap().menu_items.is_empty());
+    }
+
+    #[test]
+    fn purge_only_removes_trashed_records() {
+        let storage = MemoryStorage::new();
+        let soup = item("Soup");
+        storage.add_menu_item(soup.clone()).unwrap();
+
+        assert!(matches!(
+            storage.apply_batch(vec![BatchOp::PurgeMenuItem(soup.id)]),
+            Err(StorageError::Conflict(_))
+        ));
+        storage.delete_menu_item(soup.id, None).unwrap();
+        storage
+            .apply_batch(vec![BatchOp::PurgeMenuItem(soup.id)])
+            .unwrap();
+
+        assert!(storage.get_trash().unwrap().menu_items.is_empty());
+    }
+
+    #[test]
+    fn purge_trash_keeps_records_trashed_after_the_cutoff() {
+        let storage = MemoryStorage::new();
+        let soup = item("Soup");
+        let closed = notice("Closed Monday");
+        storage.add_menu_item(soup.clone()).unwrap();
+        storage.add_notice(closed.clone()).unwrap();
+        storage.delete_menu_item(soup.id, None).unwrap();
+        let cutoff = Utc::now();
+        storage.delete_notice(closed.id, None).unwrap();
+
+        assert_eq!(storage.purge_trash(cutoff).unwrap(), 1);
+
+        let trash = storage.get_trash().unwrap();
+        assert!(trash.menu_items.is_empty());
+        assert_eq!(trash.notices.len(), 1);
+        assert_eq!(storage.purge_trash(cutoff).unwrap(), 0);
+    }
+}
@@ -0,0 +1,107 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::MenuCategory;
+
+/// The categories that used to be fixed in code. Their ids never change, so
+/// every backend and every old snapshot migrates items to the same records.
+const LEGACY: [(Uuid, &str, &str); 4] = [
+    (
+        Uuid::from_u128(0x5f0c_2a6e_91d4_4b3a_8e27_0c6b_7d1a_0001),
+        "Mains",
+        "🍽️",
+    ),
+    (
+        Uuid::from_u128(0x5f0c_2a6e_91d4_4b3a_8e27_0c6b_7d1a_0002),
+        "Sides",
+        "🥗",
+    ),
+    (
+        Uuid::from_u128(0x5f0c_2a6e_91d4_4b3a_8e27_0c6b_7d1a_0003),
+        "Desserts",
+        "🍰",
+    ),
+    (
+        Uuid::from_u128(0x5f0c_2a6e_91d4_4b3a_8e27_0c6b_7d1a_0004),
+        "Beverages",
+        "☕",
+    ),
+];
+
+/// Id of the category that replaced a `MenuCategory` enum value
+pub fn legacy_id(name: &str) -> Option<Uuid> {
+    LEGACY
+        .iter()
+        .find(|(_, legacy_name, _)| *legacy_name == name)
+        .map(|(id, _, _)| *id)
+}
+
+/// Whether a category is one of the defaults, as seeded and never edited
+pub fn is_untouched_default(category: &MenuCategory) -> bool {
+    category.revision == 0
+        && category.trashed_at.is_none()
+        && LEGACY.iter().any(|(id, _, _)| *id == category.id)
+}
+
+/// The former enum values as categories, in their original order
+pub fn legacy_categories(now: DateTime<Utc>) -> Vec<MenuCategory> {
+    LEGACY
+        .iter()
+        .zip(1..)
+        .map(|((id, name, icon), display_order)| MenuCategory {
+            id: *id,
+            name: name.to_string(),
+            icon: icon.to_string(),
+            display_order,
+            created_at: now,
+            updated_at: now,
+            revision: 0,
+            trashed_at: None,
+        })
+        .collect()
+}
+
+/// Put categories in the order the menu lists them
+pub fn sort(categories: &mut [MenuCategory]) {
+    categories.sort_by(|a, b| {
+        a.display_order
+            .cmp(&b.display_order)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sort_uses_display_order_then_name() {
+        let mut categories = legacy_categories(Utc::now());
+        categories[0].display_order = 3;
+        categories[1].name = "Bakes".to_string();
+        categories[1].display_order = 3;
+
+        sort(&mut categories);
+
+        let names: Vec<&str> = categories.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Bakes", "Desserts", "Mains", "Beverages"]);
+    }
+
+    #[test]
+    fn only_unedited_defaults_count_as_untouched() {
+        let mut categories = legacy_categories(Utc::now());
+        assert!(categories.iter().all(is_untouched_default));
+
+        categories[0].revision = 1;
+        categories[1].trashed_at = Some(Utc::now());
+        categories[2].id = Uuid::new_v4();
+
+        assert_eq!(
+            categories
+                .iter()
+                .filter(|c| is_untouched_default(c))
+                .count(),
+            1
+        );
+    }
+}
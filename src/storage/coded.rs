@@ -0,0 +1,50 @@
+use super::{Allergen, DietaryTag, ModerationStatus, NoticeSeverity, PortionSize, PriceTier};
+
+/// A fixed set of values identified by a code, such as allergens or dietary tags
+pub trait Coded: Copy + Ord + 'static {
+    /// What a value is called in error messages
+    const KIND: &'static str;
+    const ALL: &'static [Self];
+
+    /// Name used in the API and data files
+    fn code(self) -> &'static str;
+
+    /// Name shown in the interface
+    fn label(self) -> &'static str;
+
+    /// Look up a value by its code or label, ignoring case
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        Self::ALL.iter().copied().find(|known| {
+            known.code().eq_ignore_ascii_case(value) || known.label().eq_ignore_ascii_case(value)
+        })
+    }
+}
+
+/// Implement [`Coded`] on top of a type's own `ALL`, `code` and `label`
+macro_rules! impl_coded {
+    ($($ty:ty => $kind:literal),+ $(,)?) => {
+        $(
+            impl Coded for $ty {
+                const KIND: &'static str = $kind;
+                const ALL: &'static [Self] = &<$ty>::ALL;
+
+                fn code(self) -> &'static str {
+                    <$ty>::code(self)
+                }
+                fn label(self) -> &'static str {
+                    <$ty>::label(self)
+                }
+            }
+        )+
+    };
+}
+
+impl_coded! {
+    Allergen => "allergen",
+    DietaryTag => "dietary tag",
+    PriceTier => "price tier",
+    PortionSize => "portion size",
+    ModerationStatus => "moderation status",
+    NoticeSeverity => "notice severity",
+}
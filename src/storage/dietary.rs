@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+use super::Allergen;
+
+/// Diets and religious requirements a menu item is suitable for
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DietaryTag {
+    Vegetarian,
+    Vegan,
+    Halal,
+    Kosher,
+    GlutenFree,
+}
+
+impl DietaryTag {
+    pub const ALL: [DietaryTag; 5] = [
+        DietaryTag::Vegetarian,
+        DietaryTag::Vegan,
+        DietaryTag::Halal,
+        DietaryTag::Kosher,
+        DietaryTag::GlutenFree,
+    ];
+
+    /// Name used in the API and data files
+    pub fn code(self) -> &'static str {
+        match self {
+            DietaryTag::Vegetarian => "Vegetarian",
+            DietaryTag::Vegan => "Vegan",
+            DietaryTag::Halal => "Halal",
+            DietaryTag::Kosher => "Kosher",
+            DietaryTag::GlutenFree => "GlutenFree",
+        }
+    }
+
+    /// Name shown to diners
+    pub fn label(self) -> &'static str {
+        match self {
+            DietaryTag::Vegetarian => "Vegetarian",
+            DietaryTag::Vegan => "Vegan",
+            DietaryTag::Halal => "Halal",
+            DietaryTag::Kosher => "Kosher",
+            DietaryTag::GlutenFree => "Gluten-free",
+        }
+    }
+
+    /// Allergens an item carrying this tag cannot contain
+    pub fn excluded_allergens(self) -> &'static [Allergen] {
+        match self {
+            DietaryTag::Vegetarian => &[Allergen::Fish, Allergen::Crustaceans, Allergen::Molluscs],
+            DietaryTag::Vegan => &[
+                Allergen::Fish,
+                Allergen::Crustaceans,
+                Allergen::Molluscs,
+                Allergen::Milk,
+                Allergen::Eggs,
+            ],
+            DietaryTag::GlutenFree => &[Allergen::Gluten],
+            DietaryTag::Halal | DietaryTag::Kosher => &[],
+        }
+    }
+}
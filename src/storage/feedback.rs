@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Lowest and highest rating a diner can give
+pub const MIN_RATING: u8 = 1;
+pub const MAX_RATING: u8 = 5;
+
+/// Longest comment accepted, in characters
+pub const MAX_COMMENT_CHARS: usize = 1000;
+
+/// Where a diner's comment stands with the moderators
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ModerationStatus {
+    /// Waiting in the moderation queue
+    Pending,
+    /// Shown on the item page
+    Approved,
+    /// Hidden; the rating still counts
+    Rejected,
+}
+
+impl ModerationStatus {
+    pub const ALL: [ModerationStatus; 3] = [
+        ModerationStatus::Pending,
+        ModerationStatus::Approved,
+        ModerationStatus::Rejected,
+    ];
+
+    /// Name used in the API and data files
+    pub fn code(self) -> &'static str {
+        match self {
+            ModerationStatus::Pending => "Pending",
+            ModerationStatus::Approved => "Approved",
+            ModerationStatus::Rejected => "Rejected",
+        }
+    }
+
+    /// Name shown to admins
+    pub fn label(self) -> &'static str {
+        match self {
+            ModerationStatus::Pending => "Awaiting review",
+            ModerationStatus::Approved => "Approved",
+            ModerationStatus::Rejected => "Rejected",
+        }
+    }
+}
+
+/// A diner's rating of a menu item, with an optional comment
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Feedback {
+    pub id: Uuid,
+    pub menu_item_id: Uuid,
+    /// From [`MIN_RATING`] to [`MAX_RATING`]
+    pub rating: u8,
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// A rating without a comment has nothing to review and starts out approved
+    pub status: ModerationStatus,
+    pub created_at: DateTime<Utc>,
+    /// When a moderator last approved or rejected the comment
+    #[serde(default)]
+    pub moderated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub revision: u64,
+    #[serde(default)]
+    pub trashed_at: Option<DateTime<Utc>>,
+}
+
+/// Count and average of a set of ratings
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct RatingSummary {
+    pub count: usize,
+    /// `None` when there are no ratings
+    pub average: Option<f64>,
+    /// How many ratings gave each score, from 1 to 5
+    pub distribution: BTreeMap<u8, usize>,
+}
+
+impl RatingSummary {
+    pub fn of<'a>(feedback: impl IntoIterator<Item = &'a Feedback>) -> Self {
+        let mut distribution: BTreeMap<u8, usize> = (MIN_RATING..=MAX_RATING)
+            .map(|rating| (rating, 0))
+            .collect();
+        let mut total = 0u64;
+        for entry in feedback {
+            *distribution.entry(entry.rating).or_default() += 1;
+            total += u64::from(entry.rating);
+        }
+        let count = distribution.values().sum();
+        Self {
+            count,
+            average: (count > 0).then(|| total as f64 / count as f64),
+            distribution,
+        }
+    }
+}
+
+/// Length of the periods a rating trend is broken into
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TrendPeriod {
+    Day,
+    #[default]
+    Week,
+    Month,
+}
+
+impl TrendPeriod {
+    /// First day of the period `date` falls in; weeks start on Monday
+    fn start_of(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            TrendPeriod::Day => date,
+            TrendPeriod::Week => date - Days::new(date.weekday().num_days_from_monday().into()),
+            TrendPeriod::Month => date.with_day(1).unwrap_or(date),
+        }
+    }
+
+    fn next(self, start: NaiveDate) -> NaiveDate {
+        match self {
+            TrendPeriod::Day => start + Days::new(1),
+            TrendPeriod::Week => start + Days::new(7),
+            TrendPeriod::Month => start + Months::new(1),
+        }
+    }
+}
+
+/// Ratings given during one period of a trend
+#[derive(Debug, Serialize, Clone)]
+pub struct TrendPoint {
+    pub period_start: NaiveDate,
+    pub count: usize,
+    pub average: Option<f64>,
+}
+
+/// Average rating per period, from the period of the oldest rating to that
+/// of the newest. Periods without ratings are included with a count of zero
+/// so the trend has no gaps.
+pub fn rating_trend(feedback: &[&Feedback], period: TrendPeriod) -> Vec<TrendPoint> {
+    let mut by_period: BTreeMap<NaiveDate, Vec<&Feedback>> = BTreeMap::new();
+    for entry in feedback {
+        by_period
+            .entry(period.start_of(entry.created_at.date_naive()))
+            .or_default()
+            .push(entry);
+    }
+    let (Some(first), Some(last)) = (
+        by_period.keys().next().copied(),
+        by_period.keys().next_back().copied(),
+    ) else {
+        return Vec::new();
+    };
+
+    let mut points = Vec::new();
+    let mut start = first;
+    while start <= last {
+        let summary = RatingSummary::of(by_period.get(&start).into_iter().flatten().copied());
+        points.push(TrendPoint {
+            period_start: start,
+            count: summary.count,
+            average: summary.average,
+        });
+        start = period.next(start);
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::storage::Coded;
+
+    fn rating(rating: u8, day: u32) -> Feedback {
+        Feedback {
+            id: Uuid::new_v4(),
+            menu_item_id: Uuid::new_v4(),
+            rating,
+            comment: None,
+            status: ModerationStatus::Approved,
+            created_at: Utc.with_ymd_and_hms(2026, 3, day, 12, 0, 0).unwrap(),
+            moderated_at: None,
+            revision: 0,
+            trashed_at: None,
+        }
+    }
+
+    #[test]
+    fn summary_counts_every_score() {
+        let feedback = [rating(5, 2), rating(4, 2), rating(5, 3)];
+
+        let summary = RatingSummary::of(&feedback);
+
+        assert_eq!(summary.count, 3);
+        assert_eq!(summary.average, Some(14.0 / 3.0));
+        assert_eq!(
+            summary.distribution,
+            BTreeMap::from([(1, 0), (2, 0), (3, 0), (4, 1), (5, 2)])
+        );
+        assert_eq!(RatingSummary::of(&[]).average, None);
+    }
+
+    #[test]
+    fn trend_starts_weeks_on_monday_and_has_no_gaps() {
+        // 2 March 2026 is a Monday; nobody rated anything the week of the 9th
+        let feedback = [rating(2, 4), rating(4, 8), rating(5, 17)];
+        let refs: Vec<&Feedback> = feedback.iter().collect();
+
+        let trend = rating_trend(&refs, TrendPeriod::Week);
+
+        let points: Vec<(u32, usize, Option<f64>)> = trend
+            .iter()
+            .map(|point| (point.period_start.day(), point.count, point.average))
+            .collect();
+        assert_eq!(
+            points,
+            [(2, 2, Some(3.0)), (9, 0, None), (16, 1, Some(5.0))]
+        );
+        assert!(rating_trend(&[], TrendPeriod::Day).is_empty());
+    }
+
+    #[test]
+    fn moderation_status_parses_its_label_too() {
+        assert_eq!(
+            ModerationStatus::parse("awaiting review"),
+            Some(ModerationStatus::Pending)
+        );
+        assert_eq!(
+            ModerationStatus::parse("rejected"),
+            Some(ModerationStatus::Rejected)
+        );
+    }
+}
@@ -0,0 +1,641 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::{
+    Ingredient, MenuCategory, MenuItem, MenuPreset, MenuSchedule, ScheduleStatus, StorageError,
+    Trash,
+};
+
+/// A live record that still refers to the one being removed
+#[derive(Debug, Clone)]
+pub struct Dependent {
+    pub label: &'static str,
+    pub id: Uuid,
+    pub name: String,
+}
+
+/// Why a reference no longer resolves
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceProblem {
+    /// The target does not exist at all
+    Missing,
+    /// The target is in the trash
+    Trashed,
+}
+
+/// A reference from a live record to a record that is not live
+#[derive(Debug, Serialize, Clone)]
+pub struct DanglingReference {
+    /// Collection holding the reference, e.g. `menu_presets`
+    pub collection: &'static str,
+    pub id: Uuid,
+    pub name: String,
+    /// Field holding the reference
+    pub field: &'static str,
+    pub target_id: Uuid,
+    pub problem: ReferenceProblem,
+}
+
+/// Whether a schedule in this state can still apply its preset
+pub fn is_upcoming(status: &ScheduleStatus) -> bool {
+    matches!(status, ScheduleStatus::Pending | ScheduleStatus::Active)
+}
+
+/// Whether a schedule needs its preset to stay around
+fn needs_preset(schedule: &MenuSchedule) -> bool {
+    schedule.trashed_at.is_none() && is_upcoming(&schedule.status)
+}
+
+/// What to do about dependents of a record that supports cascading deletes
+pub const CASCADE_REMEDY: &str = "remove those references first or delete with cascade";
+
+/// What to do about the items still filed under a category
+pub const MOVE_ITEMS_REMEDY: &str = "move those items to another category first";
+
+/// What to do about the recipes that still use an ingredient
+pub const REMOVE_FROM_RECIPES_REMEDY: &str = "remove it from those recipes first";
+
+/// Refuse to remove a record that live records still refer to; `remedy`
+/// tells the caller how to get past the refusal
+pub fn ensure_unreferenced(
+    label: &str,
+    id: Uuid,
+    dependents: &[Dependent],
+    remedy: &str,
+) -> Result<(), StorageError> {
+    if dependents.is_empty() {
+        return Ok(());
+    }
+    let list = dependents
+        .iter()
+        .map(|d| format!("{} '{}' ({})", d.label, d.name, d.id))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Err(StorageError::Conflict(format!(
+        "{} with id {} is still used by {}; {}",
+        label, id, list, remedy
+    )))
+}
+
+/// Build the error for a write that would point at a record that is not live
+pub fn missing_reference(
+    label: &str,
+    name: &str,
+    target_label: &str,
+    target_id: Uuid,
+) -> StorageError {
+    StorageError::Conflict(format!(
+        "{} '{}' refers to {} {}, which does not exist or is in the trash",
+        label, name, target_label, target_id
+    ))
+}
+
+/// Live menu items filed under the category
+pub fn item_dependents(category_id: Uuid, items: &[MenuItem]) -> Vec<Dependent> {
+    items
+        .iter()
+        .filter(|item| item.trashed_at.is_none() && item.category_id == category_id)
+        .map(|item| Dependent {
+            label: "menu item",
+            id: item.id,
+            name: item.name.clone(),
+        })
+        .collect()
+}
+
+/// Live menu items whose recipe uses the ingredient
+pub fn recipe_dependents(ingredient_id: Uuid, items: &[MenuItem]) -> Vec<Dependent> {
+    items
+        .iter()
+        .filter(|item| {
+            item.trashed_at.is_none()
+                && item
+                    .recipe
+                    .iter()
+                    .any(|line| line.ingredient_id == ingredient_id)
+        })
+        .map(|item| Dependent {
+            label: "menu item",
+            id: item.id,
+            name: item.name.clone(),
+        })
+        .collect()
+}
+
+/// Live presets that list the menu item
+pub fn preset_dependents(item_id: Uuid, presets: &[MenuPreset]) -> Vec<Dependent> {
+    presets
+        .iter()
+        .filter(|preset| preset.trashed_at.is_none() && preset.menu_item_ids.contains(&item_id))
+        .map(|preset| Dependent {
+            label: "menu preset",
+            id: preset.id,
+            name: preset.name.clone(),
+        })
+        .collect()
+}
+
+/// Pending or active schedules that will apply the preset
+pub fn schedule_dependents(preset_id: Uuid, schedules: &[MenuSchedule]) -> Vec<Dependent> {
+    schedules
+        .iter()
+        .filter(|schedule| needs_preset(schedule) && schedule.preset_id == preset_id)
+        .map(|schedule| Dependent {
+            label: "menu schedule",
+            id: schedule.id,
+            name: schedule.name.clone(),
+        })
+        .collect()
+}
+
+/// Make sure a live menu item is filed under a live category and its recipe
+/// only uses live ingredients
+pub fn check_item_references(
+    item: &MenuItem,
+    categories: &[MenuCategory],
+    ingredients: &[Ingredient],
+) -> Result<(), StorageError> {
+    if item.trashed_at.is_some() {
+        return Ok(());
+    }
+    let live = categories
+        .iter()
+        .any(|category| category.id == item.category_id && category.trashed_at.is_none());
+    if !live {
+        return Err(missing_reference(
+            "Menu item",
+            &item.name,
+            "menu category",
+            item.category_id,
+        ));
+    }
+    for line in &item.recipe {
+        let live = ingredients.iter().any(|ingredient| {
+            ingredient.id == line.ingredient_id && ingredient.trashed_at.is_none()
+        });
+        if !live {
+            return Err(missing_reference(
+                "Menu item",
+                &item.name,
+                "ingredient",
+                line.ingredient_id,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Make sure every item a live preset lists is live itself
+pub fn check_preset_references(
+    preset: &MenuPreset,
+    items: &[MenuItem],
+) -> Result<(), StorageError> {
+    if preset.trashed_at.is_some() {
+        return Ok(());
+    }
+    for item_id in &preset.menu_item_ids {
+        let live = items
+            .iter()
+            .any(|item| item.id == *item_id && item.trashed_at.is_none());
+        if !live {
+            return Err(missing_reference(
+                "Menu preset",
+                &preset.name,
+                "menu item",
+                *item_id,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Make sure a schedule that can still run points at a live preset
+pub fn check_schedule_references(
+    schedule: &MenuSchedule,
+    presets: &[MenuPreset],
+) -> Result<(), StorageError> {
+    if !needs_preset(schedule) {
+        return Ok(());
+    }
+    let live = presets
+        .iter()
+        .any(|preset| preset.id == schedule.preset_id && preset.trashed_at.is_none());
+    if live {
+        Ok(())
+    } else {
+        Err(missing_reference(
+            "Menu schedule",
+            &schedule.name,
+            "menu preset",
+            schedule.preset_id,
+        ))
+    }
+}
+
+/// Every reference from a live record that does not resolve to a live record.
+///
+/// `items`, `presets`, `schedules`, `categories` and `ingredients` are the
+/// live collections; `trash` tells apart targets that were trashed from ones
+/// that are gone entirely.
+pub fn find_dangling(
+    items: &[MenuItem],
+    presets: &[MenuPreset],
+    schedules: &[MenuSchedule],
+    categories: &[MenuCategory],
+    ingredients: &[Ingredient],
+    trash: &Trash,
+) -> Vec<DanglingReference> {
+    let mut dangling = Vec::new();
+
+    for item in items {
+        if categories
+            .iter()
+            .any(|category| category.id == item.category_id)
+        {
+            continue;
+        }
+        let problem = if trash
+            .menu_categories
+            .iter()
+            .any(|category| category.id == item.category_id)
+        {
+            ReferenceProblem::Trashed
+        } else {
+            ReferenceProblem::Missing
+        };
+        dangling.push(DanglingReference {
+            collection: "menu_items",
+            id: item.id,
+            name: item.name.clone(),
+            field: "category_id",
+            target_id: item.category_id,
+            problem,
+        });
+    }
+
+    for item in items {
+        for line in &item.recipe {
+            if ingredients
+                .iter()
+                .any(|ingredient| ingredient.id == line.ingredient_id)
+            {
+                continue;
+            }
+            let problem = if trash
+                .ingredients
+                .iter()
+                .any(|ingredient| ingredient.id == line.ingredient_id)
+            {
+                ReferenceProblem::Trashed
+            } else {
+                ReferenceProblem::Missing
+            };
+            dangling.push(DanglingReference {
+                collection: "menu_items",
+                id: item.id,
+                name: item.name.clone(),
+                field: "recipe",
+                target_id: line.ingredient_id,
+                problem,
+            });
+        }
+    }
+
+    for preset in presets {
+        for item_id in &preset.menu_item_ids {
+            if items.iter().any(|item| item.id == *item_id) {
+                continue;
+            }
+            let problem = if trash.menu_items.iter().any(|item| item.id == *item_id) {
+                ReferenceProblem::Trashed
+            } else {
+                ReferenceProblem::Missing
+            };
+            dangling.push(DanglingReference {
+                collection: "menu_presets",
+                id: preset.id,
+                name: preset.name.clone(),
+                field: "menu_item_ids",
+                target_id: *item_id,
+                problem,
+            });
+        }
+    }
+
+    for schedule in schedules.iter().filter(|schedule| needs_preset(schedule)) {
+        if presets.iter().any(|preset| preset.id == schedule.preset_id) {
+            continue;
+        }
+        let problem = if trash
+            .menu_presets
+            .iter()
+            .any(|preset| preset.id == schedule.preset_id)
+        {
+            ReferenceProblem::Trashed
+        } else {
+            ReferenceProblem::Missing
+        };
+        dangling.push(DanglingReference {
+            collection: "menu_schedules",
+            id: schedule.id,
+            name: schedule.name.clone(),
+            field: "preset_id",
+            target_id: schedule.preset_id,
+            problem,
+        });
+    }
+
+    dangling
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::Utc;
+
+    use super::*;
+    use crate::storage::recipe::QuantityUnit;
+    use crate::storage::{
+        MemoryStorage, Prices, RecipeIngredient, ScheduleRecurrence, Storage, Translations,
+        category,
+    };
+
+    fn item(name: &str) -> MenuItem {
+        MenuItem {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            category_id: category::legacy_id("Mains").unwrap(),
+            description: String::new(),
+            allergens: Vec::new(),
+            is_available: true,
+            revision: 0,
+            trashed_at: None,
+            may_contain: Vec::new(),
+            unmapped_allergens: Vec::new(),
+            dietary_tags: Vec::new(),
+            nutrition: None,
+            prices: Prices::new(),
+            preset_prices: Prices::new(),
+            image: None,
+            recipe: Vec::new(),
+            translations: Translations::new(),
+            stock: None,
+            variants: Vec::new(),
+        }
+    }
+
+    fn preset(name: &str, menu_item_ids: &[Uuid]) -> MenuPreset {
+        let now = Utc::now();
+        MenuPreset {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            description: String::new(),
+            menu_item_ids: menu_item_ids.to_vec(),
+            created_at: now,
+            updated_at: now,
+            revision: 0,
+            trashed_at: None,
+            price_overrides: BTreeMap::new(),
+            par_levels: BTreeMap::new(),
+        }
+    }
+
+    fn schedule(name: &str, preset_id: Uuid, status: ScheduleStatus) -> MenuSchedule {
+        let now = Utc::now();
+        MenuSchedule {
+            id: Uuid::new_v4(),
+            preset_id,
+            name: name.to_string(),
+            description: String::new(),
+            start_time: now,
+            end_time: now + chrono::Duration::hours(1),
+            recurrence: ScheduleRecurrence::Daily,
+            status,
+            error_message: None,
+            created_at: now,
+            updated_at: now,
+            revision: 0,
+            trashed_at: None,
+        }
+    }
+
+    #[test]
+    fn item_in_a_preset_cannot_be_deleted() {
+        let storage = MemoryStorage::new();
+        let soup = item("Soup");
+        storage.add_menu_item(soup.clone()).unwrap();
+        storage
+            .add_menu_preset(preset("Lunch", &[soup.id]))
+            .unwrap();
+
+        assert!(matches!(
+            storage.delete_menu_item(soup.id, None),
+            Err(StorageError::Conflict(_))
+        ));
+        assert_eq!(storage.get_menu_items().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn preset_cannot_list_a_trashed_item() {
+        let storage = MemoryStorage::new();
+        let soup = item("Soup");
+        storage.add_menu_item(soup.clone()).unwrap();
+        storage.delete_menu_item(soup.id, None).unwrap();
+
+        assert!(matches!(
+            storage.add_menu_preset(preset("Lunch", &[soup.id])),
+            Err(StorageError::Conflict(_))
+        ));
+    }
+
+    #[test]
+    fn cascade_drops_the_item_from_presets() {
+        let storage = MemoryStorage::new();
+        let soup = item("Soup");
+        let salad = item("Salad");
+        storage.add_menu_item(soup.clone()).unwrap();
+        storage.add_menu_item(salad.clone()).unwrap();
+        storage
+            .add_menu_preset(preset("Lunch", &[soup.id, salad.id]))
+            .unwrap();
+
+        storage.delete_menu_item_cascade(soup.id, None).unwrap();
+
+        assert_eq!(
+            storage.get_menu_presets().unwrap()[0].menu_item_ids,
+            vec![salad.id]
+        );
+        assert_eq!(storage.get_trash().unwrap().menu_items.len(), 1);
+    }
+
+    #[test]
+    fn cascade_trashes_upcoming_schedules_only() {
+        let storage = MemoryStorage::new();
+        let lunch = preset("Lunch", &[]);
+        storage.add_menu_preset(lunch.clone()).unwrap();
+        let pending = schedule("Monday", lunch.id, ScheduleStatus::Pending);
+        let ended = schedule("Last week", lunch.id, ScheduleStatus::Ended);
+        storage.add_menu_schedule(pending.clone()).unwrap();
+        storage.add_menu_schedule(ended.clone()).unwrap();
+
+        assert!(matches!(
+            storage.delete_menu_preset(lunch.id, None),
+            Err(StorageError::Conflict(_))
+        ));
+        storage.delete_menu_preset_cascade(lunch.id, None).unwrap();
+
+        assert!(storage.get_menu_presets().unwrap().is_empty());
+        let schedules = storage.get_menu_schedules().unwrap();
+        assert_eq!(schedules.len(), 1);
+        assert_eq!(schedules[0].id, ended.id);
+        assert_eq!(
+            storage.get_trash().unwrap().menu_schedules[0].id,
+            pending.id
+        );
+    }
+
+    #[test]
+    fn find_dangling_tells_trashed_targets_from_missing_ones() {
+        let soup = item("Soup");
+        let mut salad = item("Salad");
+        salad.trashed_at = Some(Utc::now());
+        let gone = Uuid::new_v4();
+        let lunch = preset("Lunch", &[soup.id, salad.id, gone]);
+        let pending = schedule("Monday", gone, ScheduleStatus::Pending);
+        let ended = schedule("Last week", gone, ScheduleStatus::Ended);
+        let trash = Trash {
+            menu_items: vec![salad.clone()],
+            notices: Vec::new(),
+            menu_presets: Vec::new(),
+            menu_schedules: Vec::new(),
+            menu_categories: Vec::new(),
+            ingredients: Vec::new(),
+            feedback: Vec::new(),
+        };
+
+        let (lunch_id, pending_id) = (lunch.id, pending.id);
+        let dangling = find_dangling(
+            &[soup],
+            &[lunch],
+            &[pending, ended],
+            &category::legacy_categories(Utc::now()),
+            &[],
+            &trash,
+        );
+
+        let found: Vec<_> = dangling
+            .iter()
+            .map(|d| (d.collection, d.id, d.target_id, d.problem))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "menu_presets",
+                    lunch_id,
+                    salad.id,
+                    ReferenceProblem::Trashed
+                ),
+                ("menu_presets", lunch_id, gone, ReferenceProblem::Missing),
+                (
+                    "menu_schedules",
+                    pending_id,
+                    gone,
+                    ReferenceProblem::Missing
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn category_with_items_cannot_be_deleted() {
+        let storage = MemoryStorage::new();
+        let soup = item("Soup");
+        storage.add_menu_item(soup.clone()).unwrap();
+
+        let result = storage.delete_menu_category(soup.category_id, None);
+
+        match result {
+            Err(StorageError::Conflict(message)) => assert!(message.contains(MOVE_ITEMS_REMEDY)),
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+        storage.delete_menu_item(soup.id, None).unwrap();
+        storage
+            .delete_menu_category(soup.category_id, None)
+            .unwrap();
+    }
+
+    #[test]
+    fn item_cannot_be_filed_under_an_unknown_category() {
+        let storage = MemoryStorage::new();
+        let mut soup = item("Soup");
+        soup.category_id = Uuid::new_v4();
+
+        assert!(matches!(
+            storage.add_menu_item(soup),
+            Err(StorageError::Conflict(_))
+        ));
+    }
+
+    fn ingredient(name: &str) -> Ingredient {
+        let now = Utc::now();
+        Ingredient {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            allergens: Vec::new(),
+            may_contain: Vec::new(),
+            dietary_tags: Vec::new(),
+            created_at: now,
+            updated_at: now,
+            revision: 0,
+            trashed_at: None,
+        }
+    }
+
+    fn line(ingredient: &Ingredient) -> RecipeIngredient {
+        RecipeIngredient {
+            ingredient_id: ingredient.id,
+            quantity: 50.0,
+            unit: QuantityUnit::Grams,
+        }
+    }
+
+    #[test]
+    fn ingredient_in_a_recipe_cannot_be_deleted() {
+        let storage = MemoryStorage::new();
+        let oats = ingredient("Oats");
+        storage.add_ingredient(oats.clone()).unwrap();
+        let mut porridge = item("Porridge");
+        porridge.recipe = vec![line(&oats)];
+        storage.add_menu_item(porridge).unwrap();
+
+        match storage.delete_ingredient(oats.id, None) {
+            Err(StorageError::Conflict(message)) => {
+                assert!(message.contains(REMOVE_FROM_RECIPES_REMEDY))
+            }
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn item_cannot_be_restored_while_an_ingredient_is_trashed() {
+        let storage = MemoryStorage::new();
+        let oats = ingredient("Oats");
+        storage.add_ingredient(oats.clone()).unwrap();
+        let mut porridge = item("Porridge");
+        porridge.recipe = vec![line(&oats)];
+        storage.add_menu_item(porridge.clone()).unwrap();
+        storage.delete_menu_item(porridge.id, None).unwrap();
+        storage.delete_ingredient(oats.id, None).unwrap();
+
+        let mut restored = storage.get_trash().unwrap().menu_items.remove(0);
+        restored.trashed_at = None;
+
+        assert!(matches!(
+            storage.update_menu_item(porridge.id, restored),
+            Err(StorageError::Conflict(_))
+        ));
+    }
+}
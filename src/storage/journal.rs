@@ -0,0 +1,424 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::StorageError;
+
+/// Write `contents` to `path` so that readers only ever see the old or the
+/// new file, never a truncated one.
+///
+/// The data goes to a sibling temp file which is fsynced and then renamed
+/// over the target. The parent directory is fsynced afterwards so the rename
+/// itself survives a power loss.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp_path = tmp_path_for(path);
+    {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, path)?;
+    sync_parent_dir(path)
+}
+
+fn tmp_path_for(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> io::Result<()> {
+    // Directories cannot be opened for syncing on this platform
+    Ok(())
+}
+
+/// A single file write recorded in the journal
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub path: String,
+    pub contents: String,
+}
+
+impl JournalEntry {
+    pub fn new(path: &str, contents: String) -> Self {
+        Self {
+            path: path.to_string(),
+            contents,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalRecord {
+    id: Uuid,
+    started_at: DateTime<Utc>,
+    entries: Vec<JournalEntry>,
+}
+
+/// Write-ahead journal that makes changes spanning several data files atomic.
+///
+/// Before touching any data file the full set of new contents is written to
+/// the journal. Once every file has been replaced the journal is removed. If
+/// the process dies in between, [`Journal::recover`] replays the journal on
+/// the next start. A journal that never finished being written only exists
+/// as a temp file and is discarded, leaving the old files in place.
+///
+/// If a file write fails partway through, the files already replaced are
+/// put back and the journal is removed, so the change is rolled back as a
+/// whole. Should that rollback fail as well, the journal is left for
+/// [`Journal::recover`] and every later commit is refused, since writing
+/// around it would let the replay on the next start clobber newer data.
+///
+/// Once every file is in place the change counts as committed. If removing
+/// the journal fails at that point it still matches what is on disk, so the
+/// commit succeeds and the removal is retried before the next write.
+pub struct Journal {
+    path: PathBuf,
+    lock: Mutex<()>,
+    failed: AtomicBool,
+    cleanup_pending: AtomicBool,
+}
+
+impl Journal {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+            failed: AtomicBool::new(false),
+            cleanup_pending: AtomicBool::new(false),
+        }
+    }
+
+    /// Durably apply every entry as one unit.
+    ///
+    /// A single entry is already atomic thanks to [`write_atomic`], so the
+    /// journal is only written when more than one file changes.
+    pub fn commit(&self, entries: Vec<JournalEntry>) -> Result<(), StorageError> {
+        let _guard = self.lock.lock().map_err(|_| StorageError::PoisonError)?;
+
+        if self.failed.load(Ordering::SeqCst) {
+            return Err(StorageError::Io(io::Error::other(format!(
+                "Refusing to write: journal {:?} could not be rolled back and must be replayed by restarting",
+                self.path
+            ))));
+        }
+        if self.cleanup_pending.load(Ordering::SeqCst) {
+            remove_journal(&self.path)?;
+            self.cleanup_pending.store(false, Ordering::SeqCst);
+            log::info!(
+                "Removed journal {:?} left over from an earlier commit",
+                self.path
+            );
+        }
+
+        if let [entry] = entries.as_slice() {
+            return write_entry(entry);
+        }
+
+        let record = JournalRecord {
+            id: Uuid::new_v4(),
+            started_at: Utc::now(),
+            entries,
+        };
+        log::debug!(
+            "Writing journal {} with {} entries",
+            record.id,
+            record.entries.len()
+        );
+        // Keep what is on disk now so a failed write can be undone
+        let originals = record
+            .entries
+            .iter()
+            .map(|entry| read_original(Path::new(&entry.path)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        write_atomic(&self.path, &serde_json::to_vec(&record)?)?;
+
+        for (written, entry) in record.entries.iter().enumerate() {
+            if let Err(e) = write_entry(entry) {
+                self.roll_back(&record, &originals[..written]);
+                return Err(e);
+            }
+        }
+
+        // Every file is in place, so the change is committed from here on
+        match remove_journal(&self.path) {
+            Ok(()) => log::debug!("Journal {} committed", record.id),
+            Err(e) => {
+                log::error!(
+                    "Journal {} committed but could not be removed: {}. Retrying before the next write",
+                    record.id,
+                    e
+                );
+                self.cleanup_pending.store(true, Ordering::SeqCst);
+            }
+        }
+        Ok(())
+    }
+
+    /// Restore the files a failed commit already replaced and drop the journal
+    fn roll_back(&self, record: &JournalRecord, originals: &[Option<Vec<u8>>]) {
+        let result = record
+            .entries
+            .iter()
+            .zip(originals)
+            .try_for_each(|(entry, original)| restore_original(Path::new(&entry.path), original))
+            .and_then(|()| remove_journal(&self.path));
+
+        match result {
+            Ok(()) => log::warn!("Journal {} rolled back after a failed write", record.id),
+            Err(e) => {
+                log::error!(
+                    "Could not roll back journal {}: {}. Further writes are refused until it is replayed",
+                    record.id,
+                    e
+                );
+                self.failed.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Finish or discard any change interrupted by a crash.
+    ///
+    /// Must run before the data files are loaded. Stray temp files next to
+    /// `data_paths` are removed since they were never renamed into place.
+    pub fn recover(&self, data_paths: &[&str]) -> Result<(), StorageError> {
+        let _guard = self.lock.lock().map_err(|_| StorageError::PoisonError)?;
+
+        let journal_tmp = tmp_path_for(&self.path);
+        if journal_tmp.exists() {
+            log::warn!(
+                "Discarding incomplete journal {:?}; the change it described was rolled back",
+                journal_tmp
+            );
+            fs::remove_file(&journal_tmp)?;
+        }
+
+        if self.path.exists() {
+            let record: JournalRecord = serde_json::from_str(&fs::read_to_string(&self.path)?)?;
+            log::warn!(
+                "Replaying journal {} from {} ({} files)",
+                record.id,
+                record.started_at,
+                record.entries.len()
+            );
+            for entry in &record.entries {
+                write_entry(entry)?;
+            }
+            fs::remove_file(&self.path)?;
+            sync_parent_dir(&self.path)?;
+            log::info!("Journal {} replayed successfully", record.id);
+        }
+
+        for data_path in data_paths {
+            let tmp_path = tmp_path_for(Path::new(data_path));
+            if tmp_path.exists() {
+                log::warn!("Removing leftover temp file {:?}", tmp_path);
+                fs::remove_file(&tmp_path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Remove the journal file, treating one that is already gone as removed
+fn remove_journal(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    sync_parent_dir(path)
+}
+
+fn read_original(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn restore_original(path: &Path, original: &Option<Vec<u8>>) -> io::Result<()> {
+    match original {
+        Some(contents) => write_atomic(path, contents),
+        None => match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+    }
+}
+
+fn write_entry(entry: &JournalEntry) -> Result<(), StorageError> {
+    write_atomic(Path::new(&entry.path), entry.contents.as_bytes()).map_err(|e| {
+        log::error!("Failed to write to {}: {}", entry.path, e);
+        StorageError::from(e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("journal-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entry(path: &Path, contents: &str) -> JournalEntry {
+        JournalEntry::new(path.to_str().unwrap(), contents.to_string())
+    }
+
+    #[test]
+    fn commit_writes_every_file_and_removes_journal() {
+        let dir = temp_dir();
+        let journal = Journal::new(dir.join(".journal.json"));
+        let (a, b) = (dir.join("a.json"), dir.join("b.json"));
+
+        journal
+            .commit(vec![entry(&a, "new a"), entry(&b, "new b")])
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(&a).unwrap(), "new a");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "new b");
+        assert!(!dir.join(".journal.json").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn failed_second_entry_rolls_back_first_and_survives_recover() {
+        let dir = temp_dir();
+        let journal_path = dir.join(".journal.json");
+        let journal = Journal::new(journal_path.clone());
+        let a = dir.join("a.json");
+        let c = dir.join("c.json");
+        fs::write(&a, "old a").unwrap();
+        // The parent directory does not exist, so this write fails
+        let missing = dir.join("missing").join("b.json");
+
+        let result = journal.commit(vec![
+            entry(&a, "new a"),
+            entry(&missing, "new b"),
+            entry(&c, "new c"),
+        ]);
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&a).unwrap(), "old a");
+        assert!(!c.exists());
+        assert!(!journal_path.exists());
+
+        // A later single-file write must not be undone by recovery
+        journal.commit(vec![entry(&a, "later a")]).unwrap();
+        Journal::new(journal_path)
+            .recover(&[a.to_str().unwrap(), c.to_str().unwrap()])
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(&a).unwrap(), "later a");
+        assert!(!c.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn leftover_journal_is_removed_before_the_next_write() {
+        let dir = temp_dir();
+        let journal_path = dir.join(".journal.json");
+        let journal = Journal::new(journal_path.clone());
+        let (a, b) = (dir.join("a.json"), dir.join("b.json"));
+        journal
+            .commit(vec![entry(&a, "new a"), entry(&b, "new b")])
+            .unwrap();
+        // As if removing the journal had failed after the files were written
+        let record = JournalRecord {
+            id: Uuid::new_v4(),
+            started_at: Utc::now(),
+            entries: vec![entry(&a, "new a"), entry(&b, "new b")],
+        };
+        fs::write(&journal_path, serde_json::to_vec(&record).unwrap()).unwrap();
+        journal.cleanup_pending.store(true, Ordering::SeqCst);
+
+        journal.commit(vec![entry(&a, "later a")]).unwrap();
+        Journal::new(journal_path.clone())
+            .recover(&[a.to_str().unwrap(), b.to_str().unwrap()])
+            .unwrap();
+
+        assert!(!journal_path.exists());
+        assert_eq!(fs::read_to_string(&a).unwrap(), "later a");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rollback_removes_files_that_did_not_exist_before() {
+        let dir = temp_dir();
+        let journal = Journal::new(dir.join(".journal.json"));
+        let a = dir.join("a.json");
+        let missing = dir.join("missing").join("b.json");
+
+        assert!(
+            journal
+                .commit(vec![entry(&a, "new a"), entry(&missing, "new b")])
+                .is_err()
+        );
+
+        assert!(!a.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn recover_replays_a_complete_journal() {
+        let dir = temp_dir();
+        let journal_path = dir.join(".journal.json");
+        let (a, b) = (dir.join("a.json"), dir.join("b.json"));
+        fs::write(&a, "old a").unwrap();
+        let record = JournalRecord {
+            id: Uuid::new_v4(),
+            started_at: Utc::now(),
+            entries: vec![entry(&a, "new a"), entry(&b, "new b")],
+        };
+        fs::write(&journal_path, serde_json::to_vec(&record).unwrap()).unwrap();
+
+        Journal::new(journal_path.clone())
+            .recover(&[a.to_str().unwrap(), b.to_str().unwrap()])
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(&a).unwrap(), "new a");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "new b");
+        assert!(!journal_path.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn recover_discards_an_unfinished_journal_and_temp_files() {
+        let dir = temp_dir();
+        let journal_path = dir.join(".journal.json");
+        let a = dir.join("a.json");
+        fs::write(&a, "old a").unwrap();
+        fs::write(tmp_path_for(&journal_path), "{\"truncated").unwrap();
+        fs::write(tmp_path_for(&a), "half written").unwrap();
+
+        Journal::new(journal_path.clone())
+            .recover(&[a.to_str().unwrap()])
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(&a).unwrap(), "old a");
+        assert!(!tmp_path_for(&journal_path).exists());
+        assert!(!tmp_path_for(&a).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
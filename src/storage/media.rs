@@ -0,0 +1,325 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+use image::{DynamicImage, ImageFormat, ImageReader, Limits};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::journal::write_atomic;
+use super::{Storage, StorageError};
+
+/// Largest upload accepted, in bytes
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+/// Uploads wider or taller than this are refused before being decoded in full
+const MAX_IMAGE_DIMENSION: u32 = 8000;
+
+/// Thumbnails fit within a square of this many pixels
+const THUMBNAIL_SIZE: u32 = 320;
+
+/// Unreferenced files younger than this are left alone by [`MediaStore::prune`],
+/// so an upload whose item has not been saved yet is never removed
+const PRUNE_GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// Image formats accepted for upload
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ImageKind {
+    Jpeg,
+    Png,
+    WebP,
+}
+
+impl ImageKind {
+    pub const ALL: [ImageKind; 3] = [ImageKind::Jpeg, ImageKind::Png, ImageKind::WebP];
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ImageKind::Jpeg => "image/jpeg",
+            ImageKind::Png => "image/png",
+            ImageKind::WebP => "image/webp",
+        }
+    }
+
+    /// Look up a format by the content type of an upload
+    pub fn from_content_type(content_type: &str) -> Option<ImageKind> {
+        Self::ALL.into_iter().find(|kind| {
+            kind.content_type()
+                .eq_ignore_ascii_case(content_type.trim())
+        })
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ImageKind::Jpeg => "jpg",
+            ImageKind::Png => "png",
+            ImageKind::WebP => "webp",
+        }
+    }
+
+    fn format(self) -> ImageFormat {
+        match self {
+            ImageKind::Jpeg => ImageFormat::Jpeg,
+            ImageKind::Png => ImageFormat::Png,
+            ImageKind::WebP => ImageFormat::WebP,
+        }
+    }
+}
+
+/// Photo of a menu item. The original is served at `/media/{id}` and its
+/// thumbnail at `/media/{id}/thumb`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ItemImage {
+    pub id: Uuid,
+    pub kind: ImageKind,
+    /// Size of the original in pixels
+    pub width: u32,
+    pub height: u32,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+/// An upload that decoded cleanly, with its thumbnail ready to be written
+pub struct ProcessedImage {
+    pub image: ItemImage,
+    original: Vec<u8>,
+    thumbnail: Vec<u8>,
+}
+
+/// Decode an upload of the given format and scale it down to a thumbnail.
+///
+/// Fails if the data is not a readable image of that format or is larger
+/// than the dimension limit.
+pub fn process_image(bytes: Vec<u8>, kind: ImageKind) -> Result<ProcessedImage, image::ImageError> {
+    let mut reader = ImageReader::with_format(Cursor::new(&bytes), kind.format());
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    reader.limits(limits);
+    let decoded = reader.decode()?;
+
+    let scaled = if decoded.width() <= THUMBNAIL_SIZE && decoded.height() <= THUMBNAIL_SIZE {
+        decoded.clone()
+    } else {
+        decoded.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+    };
+    // JPEG has no alpha channel and the WebP encoder only takes 8-bit pixels
+    let scaled = match kind {
+        ImageKind::Jpeg => DynamicImage::ImageRgb8(scaled.to_rgb8()),
+        ImageKind::Png | ImageKind::WebP => DynamicImage::ImageRgba8(scaled.to_rgba8()),
+    };
+    let mut thumbnail = Cursor::new(Vec::new());
+    scaled.write_to(&mut thumbnail, kind.format())?;
+
+    Ok(ProcessedImage {
+        image: ItemImage {
+            id: Uuid::new_v4(),
+            kind,
+            width: decoded.width(),
+            height: decoded.height(),
+            uploaded_at: Utc::now(),
+        },
+        original: bytes,
+        thumbnail: thumbnail.into_inner(),
+    })
+}
+
+/// Image files of menu items, one original and one thumbnail per image
+pub struct MediaStore {
+    dir: PathBuf,
+}
+
+impl MediaStore {
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    /// Write an image and its thumbnail, returning the record to link from the item
+    pub fn save(&self, processed: ProcessedImage) -> Result<ItemImage, StorageError> {
+        let image = processed.image;
+        fs::create_dir_all(&self.dir)?;
+        write_atomic(&self.path(image.id, image.kind, false), &processed.original)?;
+        write_atomic(&self.path(image.id, image.kind, true), &processed.thumbnail)?;
+        log::info!("Saved image {} in {:?}", image.id, self.dir);
+        Ok(image)
+    }
+
+    /// Path of an image or its thumbnail, if the file exists
+    pub fn find(&self, id: Uuid, thumbnail: bool) -> Option<PathBuf> {
+        ImageKind::ALL
+            .into_iter()
+            .map(|kind| self.path(id, kind, thumbnail))
+            .find(|path| path.is_file())
+    }
+
+    /// Delete an image and its thumbnail; files already gone are not an error
+    pub fn remove(&self, image: &ItemImage) -> Result<(), StorageError> {
+        for thumbnail in [false, true] {
+            match fs::remove_file(self.path(image.id, image.kind, thumbnail)) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        log::info!("Removed image {}", image.id);
+        Ok(())
+    }
+
+    /// Delete every file that no menu item, live or in the trash, refers to,
+    /// returning how many were removed. Images of trashed items are kept so
+    /// restoring the item brings its photo back.
+    pub fn prune(&self, storage: &dyn Storage) -> Result<usize, StorageError> {
+        let trash = storage.get_trash()?;
+        let referenced: HashSet<Uuid> = storage
+            .get_menu_items()?
+            .iter()
+            .chain(&trash.menu_items)
+            .filter_map(|item| item.image.as_ref().map(|image| image.id))
+            .collect();
+
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let cutoff = SystemTime::now() - PRUNE_GRACE;
+        let mut removed = 0;
+        for entry in entries {
+            let entry = entry?;
+            // Every file name starts with the image id, e.g. `<id>.thumb.jpg`
+            let name = entry.file_name();
+            let Some(id) = name
+                .to_str()
+                .and_then(|name| name.split('.').next())
+                .and_then(|id| Uuid::parse_str(id).ok())
+            else {
+                continue;
+            };
+            if referenced.contains(&id) || entry.metadata()?.modified()? > cutoff {
+                continue;
+            }
+            fs::remove_file(entry.path())?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    fn path(&self, id: Uuid, kind: ImageKind, thumbnail: bool) -> PathBuf {
+        let name = if thumbnail {
+            format!("{}.thumb.{}", id, kind.extension())
+        } else {
+            format!("{}.{}", id, kind.extension())
+        };
+        self.dir.join(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+    use crate::storage::{MemoryStorage, MenuItem, Prices, Translations, category};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        RgbImage::from_pixel(width, height, Rgb([200, 120, 40]))
+            .write_to(&mut bytes, ImageFormat::Png)
+            .unwrap();
+        bytes.into_inner()
+    }
+
+    fn media() -> MediaStore {
+        let dir = std::env::temp_dir().join(format!("media-test-{}", Uuid::new_v4()));
+        MediaStore::new(dir.to_str().unwrap())
+    }
+
+    fn item_with(image: &ItemImage) -> MenuItem {
+        MenuItem {
+            id: Uuid::new_v4(),
+            name: "Soup".to_string(),
+            category_id: category::legacy_id("Mains").unwrap(),
+            description: String::new(),
+            allergens: Vec::new(),
+            is_available: true,
+            revision: 0,
+            trashed_at: None,
+            may_contain: Vec::new(),
+            unmapped_allergens: Vec::new(),
+            dietary_tags: Vec::new(),
+            nutrition: None,
+            prices: Prices::new(),
+            preset_prices: Prices::new(),
+            image: Some(image.clone()),
+            recipe: Vec::new(),
+            translations: Translations::new(),
+            stock: None,
+            variants: Vec::new(),
+        }
+    }
+
+    /// Make both files of an image look as if they were written long ago
+    fn age(media: &MediaStore, image: &ItemImage) {
+        let then = SystemTime::now() - PRUNE_GRACE * 2;
+        for thumbnail in [false, true] {
+            let file = fs::File::options()
+                .write(true)
+                .open(media.path(image.id, image.kind, thumbnail))
+                .unwrap();
+            file.set_modified(then).unwrap();
+        }
+    }
+
+    #[test]
+    fn thumbnail_fits_the_square_and_the_original_size_is_kept() {
+        let processed = process_image(png(1280, 640), ImageKind::Png).unwrap();
+
+        assert_eq!((processed.image.width, processed.image.height), (1280, 640));
+        let thumbnail = image::load_from_memory(&processed.thumbnail).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (320, 160));
+    }
+
+    #[test]
+    fn data_not_in_the_declared_format_is_refused() {
+        assert!(process_image(png(10, 10), ImageKind::Jpeg).is_err());
+        assert!(process_image(b"not an image".to_vec(), ImageKind::Png).is_err());
+    }
+
+    #[test]
+    fn prune_keeps_referenced_and_recent_files() {
+        let media = media();
+        let storage = MemoryStorage::new();
+        let save = || {
+            media
+                .save(process_image(png(4, 4), ImageKind::Png).unwrap())
+                .unwrap()
+        };
+        let (live, trashed, orphan, fresh) = (save(), save(), save(), save());
+        for image in [&live, &trashed, &orphan] {
+            age(&media, image);
+        }
+        storage.add_menu_item(item_with(&live)).unwrap();
+        let binned = item_with(&trashed);
+        storage.add_menu_item(binned.clone()).unwrap();
+        storage.delete_menu_item(binned.id, None).unwrap();
+
+        assert_eq!(media.prune(&storage).unwrap(), 2);
+
+        assert!(media.find(orphan.id, false).is_none());
+        assert!(media.find(orphan.id, true).is_none());
+        for image in [&live, &trashed, &fresh] {
+            assert!(media.find(image.id, false).is_some());
+            assert!(media.find(image.id, true).is_some());
+        }
+        fs::remove_dir_all(&media.dir).unwrap();
+    }
+
+    #[test]
+    fn prune_of_a_missing_directory_removes_nothing() {
+        assert_eq!(media().prune(&MemoryStorage::new()).unwrap(), 0);
+    }
+}
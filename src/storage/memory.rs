@@ -0,0 +1,437 @@
+use std::sync::Mutex;
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use super::batch::{BatchTarget, Record, apply_ops, live, trashed};
+use super::category;
+use super::{
+    AdminUser, BatchOp, Dataset, Feedback, Ingredient, MenuCategory, MenuItem, MenuPreset,
+    MenuSchedule, Notice, Storage, StorageError, Trash,
+};
+
+/// Storage backend that keeps every collection in memory only.
+///
+/// Nothing is persisted, which makes it useful for tests and throwaway
+/// demo instances. The `load_*` methods are no-ops since there is no
+/// backing store to re-read.
+#[derive(Default)]
+pub struct MemoryStorage {
+    menu_items: Mutex<Vec<MenuItem>>,
+    notices: Mutex<Vec<Notice>>,
+    admin_users: Mutex<Vec<AdminUser>>,
+    menu_presets: Mutex<Vec<MenuPreset>>,
+    menu_schedules: Mutex<Vec<MenuSchedule>>,
+    menu_categories: Mutex<Vec<MenuCategory>>,
+    ingredients: Mutex<Vec<Ingredient>>,
+    feedback: Mutex<Vec<Feedback>>,
+}
+
+impl MemoryStorage {
+    /// Empty storage holding only the default categories
+    pub fn new() -> Self {
+        Self {
+            menu_categories: Mutex::new(category::legacy_categories(Utc::now())),
+            ..Self::default()
+        }
+    }
+}
+
+fn snapshot<T: Clone>(records: &Mutex<Vec<T>>) -> Result<Vec<T>, StorageError> {
+    Ok(records
+        .lock()
+        .map_err(|_| StorageError::PoisonError)?
+        .clone())
+}
+
+fn live_snapshot<T: Record + Clone>(records: &Mutex<Vec<T>>) -> Result<Vec<T>, StorageError> {
+    Ok(live(
+        &records.lock().map_err(|_| StorageError::PoisonError)?,
+    ))
+}
+
+impl Storage for MemoryStorage {
+    fn load_menu_items(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn load_notices(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn load_admin_users(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn load_menu_presets(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn load_menu_schedules(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn load_menu_categories(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn load_ingredients(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn load_feedback(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn get_menu_items(&self) -> Result<Vec<MenuItem>, StorageError> {
+        live_snapshot(&self.menu_items)
+    }
+
+    fn add_menu_item(&self, item: MenuItem) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::AddMenuItem(item)])
+    }
+
+    fn update_menu_item(&self, id: Uuid, updated_item: MenuItem) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::UpdateMenuItem(id, updated_item)])
+    }
+
+    fn delete_menu_item(
+        &self,
+        id: Uuid,
+        expected_revision: Option<u64>,
+    ) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::DeleteMenuItem(id, expected_revision)])
+    }
+
+    fn get_notices(&self) -> Result<Vec<Notice>, StorageError> {
+        live_snapshot(&self.notices)
+    }
+
+    fn add_notice(&self, notice: Notice) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::AddNotice(notice)])
+    }
+
+    fn update_notice(&self, id: Uuid, updated_notice: Notice) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::UpdateNotice(id, updated_notice)])
+    }
+
+    fn delete_notice(&self, id: Uuid, expected_revision: Option<u64>) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::DeleteNotice(id, expected_revision)])
+    }
+
+    fn get_admin_users(&self) -> Result<Vec<AdminUser>, StorageError> {
+        snapshot(&self.admin_users)
+    }
+
+    fn get_admin_user_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<AdminUser>, StorageError> {
+        let admin_users = self
+            .admin_users
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        Ok(admin_users
+            .iter()
+            .find(|user| user.username == username)
+            .cloned())
+    }
+
+    fn add_admin_user(&self, user: AdminUser) -> Result<(), StorageError> {
+        self.admin_users
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?
+            .push(user);
+        Ok(())
+    }
+
+    fn get_menu_presets(&self) -> Result<Vec<MenuPreset>, StorageError> {
+        live_snapshot(&self.menu_presets)
+    }
+
+    fn add_menu_preset(&self, preset: MenuPreset) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::AddMenuPreset(preset)])
+    }
+
+    fn update_menu_preset(&self, id: Uuid, updated_preset: MenuPreset) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::UpdateMenuPreset(id, updated_preset)])
+    }
+
+    fn delete_menu_preset(
+        &self,
+        id: Uuid,
+        expected_revision: Option<u64>,
+    ) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::DeleteMenuPreset(id, expected_revision)])
+    }
+
+    fn get_menu_schedules(&self) -> Result<Vec<MenuSchedule>, StorageError> {
+        live_snapshot(&self.menu_schedules)
+    }
+
+    fn add_menu_schedule(&self, schedule: MenuSchedule) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::AddMenuSchedule(schedule)])
+    }
+
+    fn update_menu_schedule(
+        &self,
+        id: Uuid,
+        updated_schedule: MenuSchedule,
+    ) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::UpdateMenuSchedule(id, updated_schedule)])
+    }
+
+    fn delete_menu_schedule(
+        &self,
+        id: Uuid,
+        expected_revision: Option<u64>,
+    ) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::DeleteMenuSchedule(id, expected_revision)])
+    }
+
+    fn get_menu_categories(&self) -> Result<Vec<MenuCategory>, StorageError> {
+        let mut categories = live_snapshot(&self.menu_categories)?;
+        category::sort(&mut categories);
+        Ok(categories)
+    }
+
+    fn get_ingredients(&self) -> Result<Vec<Ingredient>, StorageError> {
+        let mut ingredients = live_snapshot(&self.ingredients)?;
+        ingredients.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(ingredients)
+    }
+
+    fn get_feedback(&self) -> Result<Vec<Feedback>, StorageError> {
+        let mut feedback = live_snapshot(&self.feedback)?;
+        feedback.sort_by_key(|entry| entry.created_at);
+        Ok(feedback)
+    }
+
+    fn get_trash(&self) -> Result<Trash, StorageError> {
+        Ok(Trash {
+            menu_items: trashed(&snapshot(&self.menu_items)?),
+            notices: trashed(&snapshot(&self.notices)?),
+            menu_presets: trashed(&snapshot(&self.menu_presets)?),
+            menu_schedules: trashed(&snapshot(&self.menu_schedules)?),
+            menu_categories: trashed(&snapshot(&self.menu_categories)?),
+            ingredients: trashed(&snapshot(&self.ingredients)?),
+            feedback: trashed(&snapshot(&self.feedback)?),
+        })
+    }
+
+    fn apply_batch(&self, ops: Vec<BatchOp>) -> Result<(), StorageError> {
+        let mut menu_items = self
+            .menu_items
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut notices = self.notices.lock().map_err(|_| StorageError::PoisonError)?;
+        let mut menu_presets = self
+            .menu_presets
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut menu_schedules = self
+            .menu_schedules
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut menu_categories = self
+            .menu_categories
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut ingredients = self
+            .ingredients
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut feedback = self
+            .feedback
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+
+        let mut new_menu_items = menu_items.clone();
+        let mut new_notices = notices.clone();
+        let mut new_menu_presets = menu_presets.clone();
+        let mut new_menu_schedules = menu_schedules.clone();
+        let mut new_menu_categories = menu_categories.clone();
+        let mut new_ingredients = ingredients.clone();
+        let mut new_feedback = feedback.clone();
+        apply_ops(
+            BatchTarget {
+                menu_items: &mut new_menu_items,
+                notices: &mut new_notices,
+                menu_presets: &mut new_menu_presets,
+                menu_schedules: &mut new_menu_schedules,
+                menu_categories: &mut new_menu_categories,
+                ingredients: &mut new_ingredients,
+                feedback: &mut new_feedback,
+            },
+            ops,
+        )?;
+
+        *menu_items = new_menu_items;
+        *notices = new_notices;
+        *menu_presets = new_menu_presets;
+        *menu_schedules = new_menu_schedules;
+        *menu_categories = new_menu_categories;
+        *ingredients = new_ingredients;
+        *feedback = new_feedback;
+        Ok(())
+    }
+
+    fn export_all(&self) -> Result<Dataset, StorageError> {
+        Ok(Dataset {
+            menu_items: snapshot(&self.menu_items)?,
+            notices: snapshot(&self.notices)?,
+            admin_users: snapshot(&self.admin_users)?,
+            menu_presets: snapshot(&self.menu_presets)?,
+            menu_schedules: snapshot(&self.menu_schedules)?,
+            menu_categories: snapshot(&self.menu_categories)?,
+            ingredients: snapshot(&self.ingredients)?,
+            feedback: snapshot(&self.feedback)?,
+        })
+    }
+
+    fn replace_all(&self, dataset: Dataset) -> Result<(), StorageError> {
+        let mut menu_items = self
+            .menu_items
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut notices = self.notices.lock().map_err(|_| StorageError::PoisonError)?;
+        let mut menu_presets = self
+            .menu_presets
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut menu_schedules = self
+            .menu_schedules
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut menu_categories = self
+            .menu_categories
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut ingredients = self
+            .ingredients
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut feedback = self
+            .feedback
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+        let mut admin_users = self
+            .admin_users
+            .lock()
+            .map_err(|_| StorageError::PoisonError)?;
+
+        *menu_items = dataset.menu_items;
+        *notices = dataset.notices;
+        *admin_users = dataset.admin_users;
+        *menu_presets = dataset.menu_presets;
+        *menu_schedules = dataset.menu_schedules;
+        *menu_categories = dataset.menu_categories;
+        *ingredients = dataset.ingredients;
+        *feedback = dataset.feedback;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::recipe::{QuantityUnit, RecipeIngredient};
+    use crate::storage::{Allergen, Prices, Translations};
+
+    fn item(name: &str) -> MenuItem {
+        MenuItem {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            category_id: category::legacy_id("Mains").unwrap(),
+            description: String::new(),
+            allergens: Vec::new(),
+            is_available: true,
+            revision: 0,
+            trashed_at: None,
+            may_contain: Vec::new(),
+            unmapped_allergens: Vec::new(),
+            dietary_tags: Vec::new(),
+            nutrition: None,
+            prices: Prices::new(),
+            preset_prices: Prices::new(),
+            image: None,
+            recipe: Vec::new(),
+            translations: Translations::new(),
+            stock: None,
+            variants: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let storage = MemoryStorage::new();
+        let mut soup = item("Soup");
+        storage.add_menu_item(soup.clone()).unwrap();
+
+        soup.name = "Tomato soup".to_string();
+        storage.update_menu_item(soup.id, soup.clone()).unwrap();
+
+        let items = storage.get_menu_items().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name, "Tomato soup");
+
+        storage.delete_menu_item(soup.id, None).unwrap();
+        assert!(storage.get_menu_items().unwrap().is_empty());
+    }
+
+    #[test]
+    fn missing_records_are_reported() {
+        let storage = MemoryStorage::new();
+        let soup = item("Soup");
+
+        assert!(matches!(
+            storage.update_menu_item(soup.id, soup.clone()),
+            Err(StorageError::NotFound(_))
+        ));
+        assert!(matches!(
+            storage.delete_menu_item(soup.id, None),
+            Err(StorageError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn ingredient_update_carries_over_to_items_that_follow_the_recipe() {
+        let storage = MemoryStorage::new();
+        let now = Utc::now();
+        let butter = Ingredient {
+            id: Uuid::new_v4(),
+            name: "Butter".to_string(),
+            allergens: vec![Allergen::Milk],
+            may_contain: Vec::new(),
+            dietary_tags: Vec::new(),
+            created_at: now,
+            updated_at: now,
+            revision: 0,
+            trashed_at: None,
+        };
+        storage.add_ingredient(butter.clone()).unwrap();
+        let mut toast = item("Buttered toast");
+        toast.allergens = vec![Allergen::Milk];
+        toast.recipe = vec![RecipeIngredient {
+            ingredient_id: butter.id,
+            quantity: 10.0,
+            unit: QuantityUnit::Grams,
+        }];
+        storage.add_menu_item(toast.clone()).unwrap();
+        let mut hand_set = item("Toast with egg wash");
+        hand_set.allergens = vec![Allergen::Gluten];
+        hand_set.recipe = toast.recipe.clone();
+        storage.add_menu_item(hand_set).unwrap();
+
+        let mut eggy = butter.clone();
+        eggy.allergens.push(Allergen::Eggs);
+        let changed = storage.update_ingredient(butter.id, eggy).unwrap();
+
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].1.id, toast.id);
+        let items = storage.get_menu_items().unwrap();
+        let stored = items.iter().find(|item| item.id == toast.id).unwrap();
+        assert_eq!(stored.allergens, vec![Allergen::Eggs, Allergen::Milk]);
+        assert_eq!(stored.revision, 1);
+    }
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+/// Kilojoules per kilocalorie, as used on UK and EU nutrition labels
+const KJ_PER_KCAL: f64 = 4.184;
+
+/// Nutrition facts for one serving of a menu item; masses are in grams
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Nutrition {
+    pub serving_size_g: f64,
+    pub energy_kcal: f64,
+    pub fat_g: f64,
+    pub saturates_g: f64,
+    pub carbohydrate_g: f64,
+    pub sugars_g: f64,
+    pub protein_g: f64,
+    pub salt_g: f64,
+}
+
+/// One column of a nutrition panel
+#[derive(Debug, Serialize, Clone)]
+pub struct NutritionValues {
+    pub energy_kj: f64,
+    pub energy_kcal: f64,
+    pub fat_g: f64,
+    pub saturates_g: f64,
+    pub carbohydrate_g: f64,
+    pub sugars_g: f64,
+    pub protein_g: f64,
+    pub salt_g: f64,
+}
+
+impl Nutrition {
+    pub fn per_serving(&self) -> NutritionValues {
+        self.scaled(1.0)
+    }
+
+    /// Values per 100 g, the basis labels use for comparing products
+    pub fn per_100g(&self) -> NutritionValues {
+        self.scaled(100.0 / self.serving_size_g)
+    }
+
+    fn scaled(&self, factor: f64) -> NutritionValues {
+        NutritionValues {
+            energy_kj: self.energy_kcal * KJ_PER_KCAL * factor,
+            energy_kcal: self.energy_kcal * factor,
+            fat_g: self.fat_g * factor,
+            saturates_g: self.saturates_g * factor,
+            carbohydrate_g: self.carbohydrate_g * factor,
+            sugars_g: self.sugars_g * factor,
+            protein_g: self.protein_g * factor,
+            salt_g: self.salt_g * factor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lasagne() -> Nutrition {
+        Nutrition {
+            serving_size_g: 400.0,
+            energy_kcal: 600.0,
+            fat_g: 24.0,
+            saturates_g: 12.0,
+            carbohydrate_g: 60.0,
+            sugars_g: 8.0,
+            protein_g: 32.0,
+            salt_g: 2.0,
+        }
+    }
+
+    #[test]
+    fn per_serving_adds_kilojoules() {
+        let values = lasagne().per_serving();
+
+        assert_eq!(values.energy_kcal, 600.0);
+        assert!((values.energy_kj - 2510.4).abs() < 1e-9);
+        assert_eq!(values.salt_g, 2.0);
+    }
+
+    #[test]
+    fn per_100g_scales_by_serving_size() {
+        let values = lasagne().per_100g();
+
+        assert_eq!(values.energy_kcal, 150.0);
+        assert!((values.energy_kj - 627.6).abs() < 1e-9);
+        assert_eq!(values.fat_g, 6.0);
+        assert_eq!(values.saturates_g, 3.0);
+        assert_eq!(values.carbohydrate_g, 15.0);
+        assert_eq!(values.sugars_g, 2.0);
+        assert_eq!(values.protein_g, 8.0);
+        assert_eq!(values.salt_g, 0.5);
+    }
+}
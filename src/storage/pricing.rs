@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Groups of diners that can be charged different prices
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PriceTier {
+    Student,
+    Staff,
+    Visitor,
+}
+
+impl PriceTier {
+    pub const ALL: [PriceTier; 3] = [PriceTier::Student, PriceTier::Staff, PriceTier::Visitor];
+
+    /// Name used in the API and data files
+    pub fn code(self) -> &'static str {
+        match self {
+            PriceTier::Student => "Student",
+            PriceTier::Staff => "Staff",
+            PriceTier::Visitor => "Visitor",
+        }
+    }
+
+    /// Name shown to diners
+    pub fn label(self) -> &'static str {
+        match self {
+            PriceTier::Student => "Student",
+            PriceTier::Staff => "Staff",
+            PriceTier::Visitor => "Visitor",
+        }
+    }
+}
+
+/// Price per tier in minor units of the configured currency, e.g. pence.
+/// A tier missing from the map has no price for the item.
+pub type Prices = BTreeMap<PriceTier, u64>;
+
+/// Currencies with a symbol and minor unit other than the ISO 4217 defaults
+const KNOWN_CURRENCIES: &[(&str, &str, u32)] = &[
+    ("GBP", "£", 2),
+    ("EUR", "€", 2),
+    ("USD", "$", 2),
+    ("AUD", "A$", 2),
+    ("CAD", "C$", 2),
+    ("NZD", "NZ$", 2),
+    ("JPY", "¥", 0),
+    ("KRW", "₩", 0),
+];
+
+/// Currency that every price is held in
+#[derive(Debug, Serialize, Clone)]
+pub struct Currency {
+    /// ISO 4217 code
+    pub code: String,
+    pub symbol: String,
+    /// Number of minor units in one major unit, as a power of ten
+    pub minor_units: u32,
+}
+
+impl Currency {
+    /// Look up an ISO 4217 code. Codes without a known symbol are shown as
+    /// the code itself and assumed to have two decimal places.
+    pub fn from_code(code: &str) -> Option<Currency> {
+        let code = code.trim().to_ascii_uppercase();
+        if code.len() != 3 || !code.chars().all(|c| c.is_ascii_uppercase()) {
+            return None;
+        }
+        let (symbol, minor_units) = KNOWN_CURRENCIES
+            .iter()
+            .find(|(known, _, _)| *known == code)
+            .map_or_else(
+                || (format!("{} ", code), 2),
+                |(_, symbol, minor_units)| (symbol.to_string(), *minor_units),
+            );
+        Some(Currency {
+            code,
+            symbol,
+            minor_units,
+        })
+    }
+
+    /// Format an amount in minor units for display, e.g. `350` as `£3.50`
+    pub fn format(&self, amount: u64) -> String {
+        if self.minor_units == 0 {
+            return format!("{}{}", self.symbol, amount);
+        }
+        let scale = 10u64.pow(self.minor_units);
+        format!(
+            "{}{}.{:0width$}",
+            self.symbol,
+            amount / scale,
+            amount % scale,
+            width = self.minor_units as usize
+        )
+    }
+}
+
+impl Default for Currency {
+    fn default() -> Self {
+        Currency::from_code("GBP").expect("GBP is a valid currency code")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Coded;
+
+    #[test]
+    fn format_pads_the_minor_units() {
+        let pounds = Currency::from_code("gbp").unwrap();
+        assert_eq!(pounds.format(350), "£3.50");
+        assert_eq!(pounds.format(5), "£0.05");
+        assert_eq!(pounds.format(1200), "£12.00");
+    }
+
+    #[test]
+    fn format_of_a_currency_without_minor_units_has_no_point() {
+        assert_eq!(Currency::from_code("JPY").unwrap().format(450), "¥450");
+    }
+
+    #[test]
+    fn unknown_codes_fall_back_to_the_code_and_two_places() {
+        assert_eq!(
+            Currency::from_code("CHF").unwrap().format(1999),
+            "CHF 19.99"
+        );
+        assert!(Currency::from_code("POUNDS").is_none());
+        assert!(Currency::from_code("G1P").is_none());
+    }
+
+    #[test]
+    fn tiers_parse_in_any_case() {
+        assert_eq!(PriceTier::parse(" staff "), Some(PriceTier::Staff));
+        assert_eq!(PriceTier::parse("Guest"), None);
+    }
+}
@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{Allergen, DietaryTag, Ingredient, MenuItem, allergen};
+
+/// Unit an ingredient quantity is measured in
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum QuantityUnit {
+    Grams,
+    Millilitres,
+    /// Whole pieces, e.g. eggs or slices of bread
+    Pieces,
+}
+
+/// How much of one ingredient goes into a menu item
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RecipeIngredient {
+    pub ingredient_id: Uuid,
+    pub quantity: f64,
+    pub unit: QuantityUnit,
+}
+
+/// Allergens and dietary tags that follow from a recipe
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Derived {
+    pub allergens: Vec<Allergen>,
+    pub may_contain: Vec<Allergen>,
+    pub dietary_tags: Vec<DietaryTag>,
+    /// Recipe ingredients that are missing or in the trash. Their allergens
+    /// are unknown, so the lists above may be incomplete.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unresolved: Vec<Uuid>,
+}
+
+impl Derived {
+    /// Work out what a recipe contains from its ingredients.
+    ///
+    /// The item contains every allergen any ingredient contains, may contain
+    /// traces of the rest of their trace warnings, and suits a diet only if
+    /// every ingredient does. Returns `None` for an empty recipe, which leaves
+    /// the item's allergens and tags entirely to hand.
+    ///
+    /// Lines whose ingredient is missing or trashed are listed in
+    /// `unresolved` rather than dropped, and no diet is claimed while any are.
+    pub fn from_recipe(recipe: &[RecipeIngredient], ingredients: &[Ingredient]) -> Option<Self> {
+        if recipe.is_empty() {
+            return None;
+        }
+        let mut used: Vec<&Ingredient> = Vec::new();
+        let mut unresolved: Vec<Uuid> = Vec::new();
+        for line in recipe {
+            match ingredients.iter().find(|ingredient| {
+                ingredient.id == line.ingredient_id && ingredient.trashed_at.is_none()
+            }) {
+                Some(ingredient) => used.push(ingredient),
+                None if !unresolved.contains(&line.ingredient_id) => {
+                    unresolved.push(line.ingredient_id)
+                }
+                None => {}
+            }
+        }
+
+        let mut allergens: Vec<Allergen> = used
+            .iter()
+            .flat_map(|ingredient| ingredient.allergens.iter().copied())
+            .collect();
+        allergen::normalize(&mut allergens);
+        let mut may_contain: Vec<Allergen> = used
+            .iter()
+            .flat_map(|ingredient| ingredient.may_contain.iter().copied())
+            .filter(|allergen| !allergens.contains(allergen))
+            .collect();
+        allergen::normalize(&mut may_contain);
+        let dietary_tags = DietaryTag::ALL
+            .into_iter()
+            .filter(|tag| {
+                unresolved.is_empty()
+                    && used
+                        .iter()
+                        .all(|ingredient| ingredient.dietary_tags.contains(tag))
+            })
+            .collect();
+
+        Some(Self {
+            allergens,
+            may_contain,
+            dietary_tags,
+            unresolved,
+        })
+    }
+}
+
+/// Ways the item's hand-set allergens and tags disagree with its recipe,
+/// described for whoever set them
+pub fn mismatches(item: &MenuItem, derived: &Derived) -> Vec<String> {
+    let mut found = Vec::new();
+    if !derived.unresolved.is_empty() {
+        let ids = derived
+            .unresolved
+            .iter()
+            .map(Uuid::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        found.push(format!(
+            "Recipe uses ingredients that are missing or in the trash ({}), so its allergens may be incomplete",
+            ids
+        ));
+    }
+    compare(
+        "Allergens",
+        &item.allergens,
+        &derived.allergens,
+        Allergen::code,
+        &mut found,
+    );
+    compare(
+        "May contain",
+        &item.may_contain,
+        &derived.may_contain,
+        Allergen::code,
+        &mut found,
+    );
+    compare(
+        "Dietary tags",
+        &item.dietary_tags,
+        &derived.dietary_tags,
+        DietaryTag::code,
+        &mut found,
+    );
+    found
+}
+
+fn compare<T: Copy + PartialEq>(
+    field: &str,
+    set: &[T],
+    derived: &[T],
+    code: fn(T) -> &'static str,
+    found: &mut Vec<String>,
+) {
+    let list = |values: Vec<T>| values.into_iter().map(code).collect::<Vec<_>>().join(", ");
+    let missing: Vec<T> = derived
+        .iter()
+        .copied()
+        .filter(|value| !set.contains(value))
+        .collect();
+    let extra: Vec<T> = set
+        .iter()
+        .copied()
+        .filter(|value| !derived.contains(value))
+        .collect();
+    match (missing.is_empty(), extra.is_empty()) {
+        (true, true) => {}
+        (false, true) => found.push(format!(
+            "{} leave out {}, which the recipe has",
+            field,
+            list(missing)
+        )),
+        (true, false) => found.push(format!(
+            "{} add {}, which the recipe does not have",
+            field,
+            list(extra)
+        )),
+        (false, false) => found.push(format!(
+            "{} leave out {}, which the recipe has, and add {}, which it does not",
+            field,
+            list(missing),
+            list(extra)
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::storage::{Prices, Translations};
+
+    fn ingredient(
+        allergens: &[Allergen],
+        may_contain: &[Allergen],
+        tags: &[DietaryTag],
+    ) -> Ingredient {
+        let now = Utc::now();
+        Ingredient {
+            id: Uuid::new_v4(),
+            name: "Ingredient".to_string(),
+            allergens: allergens.to_vec(),
+            may_contain: may_contain.to_vec(),
+            dietary_tags: tags.to_vec(),
+            created_at: now,
+            updated_at: now,
+            revision: 0,
+            trashed_at: None,
+        }
+    }
+
+    fn line(ingredient_id: Uuid) -> RecipeIngredient {
+        RecipeIngredient {
+            ingredient_id,
+            quantity: 100.0,
+            unit: QuantityUnit::Grams,
+        }
+    }
+
+    fn item(allergens: &[Allergen], tags: &[DietaryTag]) -> MenuItem {
+        MenuItem {
+            id: Uuid::new_v4(),
+            name: "Toastie".to_string(),
+            category_id: Uuid::new_v4(),
+            description: String::new(),
+            allergens: allergens.to_vec(),
+            may_contain: Vec::new(),
+            unmapped_allergens: Vec::new(),
+            dietary_tags: tags.to_vec(),
+            is_available: true,
+            revision: 0,
+            trashed_at: None,
+            nutrition: None,
+            prices: Prices::new(),
+            preset_prices: Prices::new(),
+            image: None,
+            recipe: Vec::new(),
+            translations: Translations::new(),
+            stock: None,
+            variants: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn empty_recipe_derives_nothing() {
+        assert_eq!(Derived::from_recipe(&[], &[]), None);
+    }
+
+    #[test]
+    fn allergens_add_up_and_diets_need_every_ingredient() {
+        let bread = ingredient(
+            &[Allergen::Gluten],
+            &[Allergen::Sesame, Allergen::Milk],
+            &[DietaryTag::Vegan, DietaryTag::Vegetarian],
+        );
+        let cheese = ingredient(&[Allergen::Milk], &[], &[DietaryTag::Vegetarian]);
+
+        let derived =
+            Derived::from_recipe(&[line(bread.id), line(cheese.id)], &[bread, cheese]).unwrap();
+
+        assert_eq!(derived.allergens, [Allergen::Gluten, Allergen::Milk]);
+        // Milk is contained outright, so it is no longer just a trace
+        assert_eq!(derived.may_contain, [Allergen::Sesame]);
+        assert_eq!(derived.dietary_tags, [DietaryTag::Vegetarian]);
+        assert!(derived.unresolved.is_empty());
+    }
+
+    #[test]
+    fn missing_and_trashed_ingredients_are_unresolved_and_claim_no_diet() {
+        let oats = ingredient(&[], &[], &[DietaryTag::Vegan]);
+        let mut honey = ingredient(&[], &[], &[]);
+        honey.trashed_at = Some(Utc::now());
+        let gone = Uuid::new_v4();
+
+        let derived = Derived::from_recipe(
+            &[line(oats.id), line(honey.id), line(gone), line(gone)],
+            &[oats, honey.clone()],
+        )
+        .unwrap();
+
+        assert_eq!(derived.unresolved, [honey.id, gone]);
+        assert!(derived.dietary_tags.is_empty());
+    }
+
+    #[test]
+    fn mismatches_describe_what_the_item_leaves_out_and_adds() {
+        let derived = Derived {
+            allergens: vec![Allergen::Milk],
+            may_contain: Vec::new(),
+            dietary_tags: vec![DietaryTag::Vegetarian],
+            unresolved: Vec::new(),
+        };
+        let item = item(&[Allergen::Eggs], &[DietaryTag::Vegetarian]);
+
+        assert_eq!(
+            mismatches(&item, &derived),
+            ["Allergens leave out Milk, which the recipe has, and add Eggs, which it does not"]
+        );
+    }
+}
@@ -0,0 +1,596 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::StorageError;
+use super::allergen;
+use super::category;
+use super::journal::write_atomic;
+
+/// One upgrade step for the records of a data file
+pub struct Migration {
+    /// Schema version the records are at once this step has run
+    pub version: u32,
+    pub description: &'static str,
+    pub apply: fn(&mut [Value]) -> Result<(), StorageError>,
+}
+
+/// Migrations for `menu_items.json`.
+///
+/// Never edit a migration that has already shipped; append a new one instead.
+pub const MENU_ITEMS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "add revision counter",
+        apply: add_revision,
+    },
+    Migration {
+        version: 2,
+        description: "add trash timestamp",
+        apply: add_trashed_at,
+    },
+    Migration {
+        version: 3,
+        description: "map free-text allergens to the regulated allergens",
+        apply: map_allergens,
+    },
+    Migration {
+        version: 4,
+        description: "add dietary tags",
+        apply: add_dietary_tags,
+    },
+    Migration {
+        version: 5,
+        description: "add nutrition facts",
+        apply: add_nutrition,
+    },
+    Migration {
+        version: 6,
+        description: "add prices",
+        apply: add_prices,
+    },
+    Migration {
+        version: 7,
+        description: "refer to categories by id",
+        apply: reference_categories,
+    },
+    Migration {
+        version: 8,
+        description: "add image",
+        apply: add_image,
+    },
+    Migration {
+        version: 9,
+        description: "add recipe",
+        apply: add_recipe,
+    },
+    Migration {
+        version: 10,
+        description: "add translations",
+        apply: add_translations,
+    },
+    Migration {
+        version: 11,
+        description: "add stock",
+        apply: add_stock,
+    },
+    Migration {
+        version: 12,
+        description: "add variants",
+        apply: add_variants,
+    },
+];
+
+/// Migrations for `notices.json`
+pub const NOTICES: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "add revision counter",
+        apply: add_revision,
+    },
+    Migration {
+        version: 2,
+        description: "add trash timestamp",
+        apply: add_trashed_at,
+    },
+    Migration {
+        version: 3,
+        description: "add translations",
+        apply: add_translations,
+    },
+    Migration {
+        version: 4,
+        description: "add publish window",
+        apply: add_publish_window,
+    },
+    Migration {
+        version: 5,
+        description: "add severity and pinning",
+        apply: add_severity,
+    },
+];
+
+/// Migrations for `admin_users.json`
+pub const ADMIN_USERS: &[Migration] = &[];
+
+/// Migrations for `menu_presets.json`
+pub const MENU_PRESETS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "add revision counter",
+        apply: add_revision,
+    },
+    Migration {
+        version: 2,
+        description: "add trash timestamp",
+        apply: add_trashed_at,
+    },
+    Migration {
+        version: 3,
+        description: "add price overrides",
+        apply: add_price_overrides,
+    },
+    Migration {
+        version: 4,
+        description: "add par levels",
+        apply: add_par_levels,
+    },
+];
+
+/// Migrations for `menu_schedules.json`
+pub const MENU_SCHEDULES: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "add revision counter",
+        apply: add_revision,
+    },
+    Migration {
+        version: 2,
+        description: "add trash timestamp",
+        apply: add_trashed_at,
+    },
+];
+
+/// Migrations for `menu_categories.json`
+pub const MENU_CATEGORIES: &[Migration] = &[];
+
+/// Migrations for `ingredients.json`
+pub const INGREDIENTS: &[Migration] = &[];
+
+/// Migrations for `feedback.json`
+pub const FEEDBACK: &[Migration] = &[];
+
+fn add_revision(records: &mut [Value]) -> Result<(), StorageError> {
+    set_missing(records, "revision", Value::from(0));
+    Ok(())
+}
+
+fn add_trashed_at(records: &mut [Value]) -> Result<(), StorageError> {
+    set_missing(records, "trashed_at", Value::Null);
+    Ok(())
+}
+
+fn add_dietary_tags(records: &mut [Value]) -> Result<(), StorageError> {
+    set_missing(records, "dietary_tags", Value::Array(Vec::new()));
+    Ok(())
+}
+
+fn add_nutrition(records: &mut [Value]) -> Result<(), StorageError> {
+    set_missing(records, "nutrition", Value::Null);
+    Ok(())
+}
+
+fn add_prices(records: &mut [Value]) -> Result<(), StorageError> {
+    set_missing(records, "prices", Value::Object(Default::default()));
+    set_missing(records, "preset_prices", Value::Object(Default::default()));
+    Ok(())
+}
+
+fn add_image(records: &mut [Value]) -> Result<(), StorageError> {
+    set_missing(records, "image", Value::Null);
+    Ok(())
+}
+
+fn add_recipe(records: &mut [Value]) -> Result<(), StorageError> {
+    set_missing(records, "recipe", Value::Array(Vec::new()));
+    Ok(())
+}
+
+fn add_translations(records: &mut [Value]) -> Result<(), StorageError> {
+    set_missing(records, "translations", Value::Object(Default::default()));
+    Ok(())
+}
+
+fn add_publish_window(records: &mut [Value]) -> Result<(), StorageError> {
+    set_missing(records, "publish_at", Value::Null);
+    set_missing(records, "expire_at", Value::Null);
+    Ok(())
+}
+
+fn add_severity(records: &mut [Value]) -> Result<(), StorageError> {
+    set_missing(records, "severity", Value::String("Info".to_string()));
+    set_missing(records, "pinned", Value::Bool(false));
+    set_missing(records, "emergency", Value::Bool(false));
+    Ok(())
+}
+
+fn add_stock(records: &mut [Value]) -> Result<(), StorageError> {
+    set_missing(records, "stock", Value::Null);
+    Ok(())
+}
+
+fn add_variants(records: &mut [Value]) -> Result<(), StorageError> {
+    set_missing(records, "variants", Value::Array(Vec::new()));
+    Ok(())
+}
+
+fn add_par_levels(records: &mut [Value]) -> Result<(), StorageError> {
+    set_missing(records, "par_levels", Value::Object(Default::default()));
+    Ok(())
+}
+
+fn add_price_overrides(records: &mut [Value]) -> Result<(), StorageError> {
+    set_missing(
+        records,
+        "price_overrides",
+        Value::Object(Default::default()),
+    );
+    Ok(())
+}
+
+fn reference_categories(records: &mut [Value]) -> Result<(), StorageError> {
+    for record in records.iter_mut() {
+        let Value::Object(fields) = record else {
+            continue;
+        };
+        let Some(name) = fields.remove("category") else {
+            continue;
+        };
+        let name = name.as_str().unwrap_or_default();
+        let id = category::legacy_id(name).ok_or_else(|| {
+            StorageError::Schema(format!(
+                "menu item {} has unknown category '{}'",
+                fields.get("id").unwrap_or(&Value::Null),
+                name
+            ))
+        })?;
+        fields.insert("category_id".to_string(), Value::from(id.to_string()));
+    }
+    Ok(())
+}
+
+fn map_allergens(records: &mut [Value]) -> Result<(), StorageError> {
+    for record in records.iter_mut() {
+        let Value::Object(fields) = record else {
+            continue;
+        };
+        let values: Vec<String> = match fields.get("allergens") {
+            Some(value) => serde_json::from_value(value.clone())?,
+            None => Vec::new(),
+        };
+        let text = |field: &str| {
+            fields
+                .get(field)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let mapped = allergen::map_legacy(&text("id"), &text("name"), &values);
+        fields.insert(
+            "allergens".to_string(),
+            serde_json::to_value(mapped.contains)?,
+        );
+        fields.insert(
+            "may_contain".to_string(),
+            serde_json::to_value(mapped.may_contain)?,
+        );
+        fields.insert(
+            "unmapped_allergens".to_string(),
+            serde_json::to_value(mapped.unmapped)?,
+        );
+    }
+    Ok(())
+}
+
+fn set_missing(records: &mut [Value], field: &str, value: Value) {
+    for record in records.iter_mut() {
+        if let Value::Object(fields) = record {
+            fields.entry(field).or_insert_with(|| value.clone());
+        }
+    }
+}
+
+/// Schema version written by this build
+pub fn current_version(migrations: &[Migration]) -> u32 {
+    migrations.last().map_or(0, |m| m.version)
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    schema_version: u32,
+    records: &'a [T],
+}
+
+#[derive(Deserialize)]
+struct RawEnvelope {
+    schema_version: u32,
+    records: Vec<Value>,
+}
+
+/// Records of a data file, brought up to the current schema
+struct Upgraded {
+    from_version: u32,
+    /// The file predates the envelope and is a bare array
+    bare: bool,
+    records: Vec<Value>,
+}
+
+/// Serialize records in the versioned on-disk format
+pub fn encode<T: Serialize>(
+    records: &[T],
+    migrations: &[Migration],
+) -> Result<String, StorageError> {
+    Ok(serde_json::to_string_pretty(&Envelope {
+        schema_version: current_version(migrations),
+        records,
+    })?)
+}
+
+/// Versioned envelope as a JSON value, for embedding in other documents
+pub fn encode_value<T: Serialize>(
+    records: &[T],
+    migrations: &[Migration],
+) -> Result<Value, StorageError> {
+    Ok(serde_json::to_value(Envelope {
+        schema_version: current_version(migrations),
+        records,
+    })?)
+}
+
+/// Parse a data file, running any migrations it is missing in memory
+pub fn decode<T: DeserializeOwned>(
+    contents: &str,
+    migrations: &[Migration],
+) -> Result<Vec<T>, StorageError> {
+    decode_value(serde_json::from_str(contents)?, migrations)
+}
+
+/// Like [`decode`], for an envelope that is already parsed
+pub fn decode_value<T: DeserializeOwned>(
+    value: Value,
+    migrations: &[Migration],
+) -> Result<Vec<T>, StorageError> {
+    let upgraded = upgrade(value, migrations)?;
+    upgraded
+        .records
+        .into_iter()
+        .map(|record| serde_json::from_value(record).map_err(StorageError::from))
+        .collect()
+}
+
+fn upgrade(value: Value, migrations: &[Migration]) -> Result<Upgraded, StorageError> {
+    // Files written before the envelope existed are a bare array at version 0
+    let (from_version, bare, mut records) = match value {
+        Value::Array(records) => (0, true, records),
+        envelope => {
+            let envelope: RawEnvelope = serde_json::from_value(envelope)?;
+            (envelope.schema_version, false, envelope.records)
+        }
+    };
+
+    let current = current_version(migrations);
+    if from_version > current {
+        return Err(StorageError::Schema(format!(
+            "data file is at schema version {} but this build only understands up to {}",
+            from_version, current
+        )));
+    }
+
+    for migration in migrations.iter().filter(|m| m.version > from_version) {
+        (migration.apply)(&mut records)?;
+    }
+
+    Ok(Upgraded {
+        from_version,
+        bare,
+        records,
+    })
+}
+
+/// Upgrade the data file at `path` in place if it is behind the current schema.
+///
+/// The original file is kept next to it as `<name>.v<version>.<timestamp>.bak`,
+/// so an upgrade can be undone by copying the backup back.
+pub fn migrate_file(path: &str, migrations: &[Migration]) -> Result<(), StorageError> {
+    let path = Path::new(path);
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let contents = fs::read_to_string(path)?;
+    let upgraded = upgrade(serde_json::from_str(&contents)?, migrations)?;
+    let current = current_version(migrations);
+    if upgraded.from_version == current && !upgraded.bare {
+        return Ok(());
+    }
+
+    let backup = backup_path(path, upgraded.from_version);
+    if upgraded.from_version == current {
+        log::info!(
+            "Adding schema version envelope to {:?}; original kept at {:?}",
+            path,
+            backup
+        );
+    } else {
+        log::info!(
+            "Upgrading {:?} from schema version {} to {}; original kept at {:?}",
+            path,
+            upgraded.from_version,
+            current,
+            backup
+        );
+    }
+    for migration in migrations
+        .iter()
+        .filter(|m| m.version > upgraded.from_version)
+    {
+        log::info!(
+            "  migration {}: {}",
+            migration.version,
+            migration.description
+        );
+    }
+
+    write_atomic(&backup, contents.as_bytes())?;
+    write_atomic(path, encode(&upgraded.records, migrations)?.as_bytes())?;
+    Ok(())
+}
+
+fn backup_path(path: &Path, version: u32) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(
+        ".v{}.{}.bak",
+        version,
+        Utc::now().format("%Y%m%dT%H%M%SZ")
+    ));
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{Allergen, MenuItem, Notice};
+    use serde_json::json;
+
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("schema-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn legacy_item() -> Value {
+        json!({
+            "id": "2b1c4a1e-5c0e-4f7a-9a3b-6f1d2e3c4b5a",
+            "name": "Cheese Toastie",
+            "category": "Mains",
+            "description": "",
+            "allergens": ["dairy", "wheat", "may contain nuts", "unicorn dust"],
+            "is_available": true
+        })
+    }
+
+    #[test]
+    fn bare_array_runs_every_migration() {
+        let items: Vec<MenuItem> =
+            decode(&Value::Array(vec![legacy_item()]).to_string(), MENU_ITEMS).unwrap();
+
+        let item = &items[0];
+        assert_eq!(item.allergens, vec![Allergen::Gluten, Allergen::Milk]);
+        assert_eq!(item.may_contain, vec![Allergen::TreeNuts]);
+        assert_eq!(item.unmapped_allergens, vec!["unicorn dust".to_string()]);
+        assert_eq!(item.revision, 0);
+        assert_eq!(item.trashed_at, None);
+        assert_eq!(item.category_id, category::legacy_id("Mains").unwrap());
+    }
+
+    #[test]
+    fn unknown_legacy_category_is_refused() {
+        let mut item = legacy_item();
+        item["category"] = json!("Specials");
+
+        assert!(matches!(
+            decode::<MenuItem>(&Value::Array(vec![item]).to_string(), MENU_ITEMS),
+            Err(StorageError::Schema(_))
+        ));
+    }
+
+    #[test]
+    fn envelope_at_the_current_version_is_read_as_is() {
+        let envelope = json!({
+            "schema_version": current_version(NOTICES),
+            "records": [{
+                "id": "5e0a9a4e-1f7b-4c55-8d8a-2c1f0e9b7a61",
+                "title": "Closed Monday",
+                "content": "",
+                "is_active": true,
+                "created_at": "2026-01-01T00:00:00Z",
+                "updated_at": "2026-01-01T00:00:00Z",
+                "revision": 4
+            }]
+        });
+
+        let notices: Vec<Notice> = decode(&envelope.to_string(), NOTICES).unwrap();
+
+        assert_eq!(notices[0].revision, 4);
+    }
+
+    #[test]
+    fn envelope_only_runs_newer_migrations() {
+        let envelope = json!({
+            "schema_version": 2,
+            "records": [{
+                "id": "5e0a9a4e-1f7b-4c55-8d8a-2c1f0e9b7a61",
+                "title": "Closed Monday",
+                "content": "",
+                "is_active": true,
+                "created_at": "2026-01-01T00:00:00Z",
+                "updated_at": "2026-01-01T00:00:00Z",
+                "revision": 4,
+                "trashed_at": null
+            }]
+        });
+
+        let notices: Vec<Notice> = decode(&envelope.to_string(), NOTICES).unwrap();
+
+        assert_eq!(notices[0].revision, 4);
+        assert!(notices[0].translations.is_empty());
+    }
+
+    #[test]
+    fn newer_schema_version_is_refused() {
+        let envelope = json!({
+            "schema_version": current_version(NOTICES) + 1,
+            "records": []
+        });
+
+        assert!(matches!(
+            decode::<Notice>(&envelope.to_string(), NOTICES),
+            Err(StorageError::Schema(_))
+        ));
+    }
+
+    #[test]
+    fn migrate_file_upgrades_in_place_and_keeps_a_backup() {
+        let original = Value::Array(vec![legacy_item()]).to_string();
+        let path = temp_file("menu_items.json", &original);
+
+        migrate_file(path.to_str().unwrap(), MENU_ITEMS).unwrap();
+
+        let upgraded: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            upgraded["schema_version"],
+            json!(current_version(MENU_ITEMS))
+        );
+        assert_eq!(upgraded["records"][0]["revision"], json!(0));
+        let backups: Vec<_> = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|p| p.to_string_lossy().ends_with(".bak"))
+            .collect();
+        assert_eq!(backups.len(), 1);
+        assert!(backups[0].to_string_lossy().contains("menu_items.json.v0."));
+        assert_eq!(fs::read_to_string(&backups[0]).unwrap(), original);
+
+        // A file already at the current version is left alone
+        let before = fs::read_to_string(&path).unwrap();
+        migrate_file(path.to_str().unwrap(), MENU_ITEMS).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), before);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+/// How urgent a notice is, from least to most
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default,
+)]
+pub enum NoticeSeverity {
+    #[default]
+    Info,
+    Warning,
+    /// Shown as a banner across every public page
+    Critical,
+}
+
+impl NoticeSeverity {
+    pub const ALL: [NoticeSeverity; 3] = [
+        NoticeSeverity::Info,
+        NoticeSeverity::Warning,
+        NoticeSeverity::Critical,
+    ];
+
+    /// Name used in the API and data files
+    pub fn code(self) -> &'static str {
+        match self {
+            NoticeSeverity::Info => "Info",
+            NoticeSeverity::Warning => "Warning",
+            NoticeSeverity::Critical => "Critical",
+        }
+    }
+
+    /// Name shown to admins
+    pub fn label(self) -> &'static str {
+        match self {
+            NoticeSeverity::Info => "Information",
+            NoticeSeverity::Warning => "Warning",
+            NoticeSeverity::Critical => "Critical",
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::category;
+use super::journal::write_atomic;
+use super::schema;
+use super::{Dataset, Storage, StorageError};
+
+/// Number of records of each collection held by a snapshot
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct SnapshotCounts {
+    pub menu_items: usize,
+    pub notices: usize,
+    pub admin_users: usize,
+    pub menu_presets: usize,
+    pub menu_schedules: usize,
+    #[serde(default)]
+    pub menu_categories: usize,
+    #[serde(default)]
+    pub ingredients: usize,
+    #[serde(default)]
+    pub feedback: usize,
+}
+
+impl SnapshotCounts {
+    fn of(dataset: &Dataset) -> Self {
+        Self {
+            menu_items: dataset.menu_items.len(),
+            notices: dataset.notices.len(),
+            admin_users: dataset.admin_users.len(),
+            menu_presets: dataset.menu_presets.len(),
+            menu_schedules: dataset.menu_schedules.len(),
+            menu_categories: dataset.menu_categories.len(),
+            ingredients: dataset.ingredients.len(),
+            feedback: dataset.feedback.len(),
+        }
+    }
+}
+
+/// What is known about a snapshot without reading its records
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SnapshotInfo {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub label: Option<String>,
+    /// File name inside the snapshot directory
+    #[serde(skip_deserializing)]
+    pub file: String,
+    pub counts: SnapshotCounts,
+}
+
+/// Result of restoring a snapshot
+#[derive(Debug, Serialize, Clone)]
+pub struct RestoreOutcome {
+    pub restored: SnapshotInfo,
+    /// Snapshot of the data as it was just before the restore
+    pub safety_snapshot: SnapshotInfo,
+}
+
+/// Each collection stored in the same versioned envelope as its data file,
+/// so older archives go through the usual migrations when restored
+#[derive(Serialize, Deserialize)]
+struct Collections {
+    menu_items: Value,
+    notices: Value,
+    admin_users: Value,
+    menu_presets: Value,
+    menu_schedules: Value,
+    /// Missing from archives taken while categories were fixed in code
+    #[serde(default)]
+    menu_categories: Option<Value>,
+    /// Missing from archives taken before recipes were added
+    #[serde(default)]
+    ingredients: Option<Value>,
+    /// Missing from archives taken before diners could leave feedback
+    #[serde(default)]
+    feedback: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Archive {
+    #[serde(flatten)]
+    info: SnapshotInfo,
+    collections: Collections,
+}
+
+/// Point-in-time copies of the whole dataset, one JSON archive per snapshot
+pub struct SnapshotStore {
+    dir: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    /// Write every record of every collection, trash included, to a new archive
+    pub fn create(
+        &self,
+        storage: &dyn Storage,
+        label: Option<String>,
+    ) -> Result<SnapshotInfo, StorageError> {
+        let dataset = storage.export_all()?;
+        let id = Uuid::new_v4();
+        let created_at = Utc::now();
+        let info = SnapshotInfo {
+            id,
+            created_at,
+            label,
+            // The id keeps two snapshots taken in the same millisecond apart
+            file: format!(
+                "snapshot-{}-{}.json",
+                created_at.format("%Y%m%dT%H%M%S%.3fZ"),
+                id.simple()
+            ),
+            counts: SnapshotCounts::of(&dataset),
+        };
+        let archive = Archive {
+            info: info.clone(),
+            collections: Collections {
+                menu_items: schema::encode_value(&dataset.menu_items, schema::MENU_ITEMS)?,
+                notices: schema::encode_value(&dataset.notices, schema::NOTICES)?,
+                admin_users: schema::encode_value(&dataset.admin_users, schema::ADMIN_USERS)?,
+                menu_presets: schema::encode_value(&dataset.menu_presets, schema::MENU_PRESETS)?,
+                menu_schedules: schema::encode_value(
+                    &dataset.menu_schedules,
+                    schema::MENU_SCHEDULES,
+                )?,
+                menu_categories: Some(schema::encode_value(
+                    &dataset.menu_categories,
+                    schema::MENU_CATEGORIES,
+                )?),
+                ingredients: Some(schema::encode_value(
+                    &dataset.ingredients,
+                    schema::INGREDIENTS,
+                )?),
+                feedback: Some(schema::encode_value(&dataset.feedback, schema::FEEDBACK)?),
+            },
+        };
+
+        fs::create_dir_all(&self.dir)?;
+        write_atomic(
+            &self.dir.join(&info.file),
+            serde_json::to_string_pretty(&archive)?.as_bytes(),
+        )?;
+        log::info!("Created snapshot {} in {:?}", info.id, info.file);
+        Ok(info)
+    }
+
+    /// Every readable snapshot, newest first
+    pub fn list(&self) -> Result<Vec<SnapshotInfo>, StorageError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut snapshots = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            match read_info(&path) {
+                Ok(info) => snapshots.push(info),
+                Err(e) => log::warn!("Skipping unreadable snapshot {:?}: {}", path, e),
+            }
+        }
+        snapshots.sort_by_key(|info| std::cmp::Reverse(info.created_at));
+        Ok(snapshots)
+    }
+
+    /// Replace all data with the contents of snapshot `id`.
+    ///
+    /// The archive is fully decoded before anything changes, and the current
+    /// data is snapshotted first so the restore itself can be undone.
+    pub fn restore(&self, storage: &dyn Storage, id: Uuid) -> Result<RestoreOutcome, StorageError> {
+        // Look the id up rather than building a path from it, so only files
+        // that are really in the snapshot directory can be restored
+        let info = self
+            .list()?
+            .into_iter()
+            .find(|info| info.id == id)
+            .ok_or_else(|| StorageError::NotFound(format!("Snapshot with id {} not found", id)))?;
+
+        let archive: Archive =
+            serde_json::from_str(&fs::read_to_string(self.dir.join(&info.file))?)?;
+        let collections = archive.collections;
+        let dataset = Dataset {
+            menu_items: schema::decode_value(collections.menu_items, schema::MENU_ITEMS)?,
+            notices: schema::decode_value(collections.notices, schema::NOTICES)?,
+            admin_users: schema::decode_value(collections.admin_users, schema::ADMIN_USERS)?,
+            menu_presets: schema::decode_value(collections.menu_presets, schema::MENU_PRESETS)?,
+            menu_schedules: schema::decode_value(
+                collections.menu_schedules,
+                schema::MENU_SCHEDULES,
+            )?,
+            menu_categories: match collections.menu_categories {
+                Some(value) => schema::decode_value(value, schema::MENU_CATEGORIES)?,
+                None => category::legacy_categories(Utc::now()),
+            },
+            ingredients: match collections.ingredients {
+                Some(value) => schema::decode_value(value, schema::INGREDIENTS)?,
+                None => Vec::new(),
+            },
+            feedback: match collections.feedback {
+                Some(value) => schema::decode_value(value, schema::FEEDBACK)?,
+                None => Vec::new(),
+            },
+        };
+
+        let safety_snapshot = self.create(storage, Some(format!("before restore of {}", id)))?;
+        storage.replace_all(dataset)?;
+        log::info!(
+            "Restored snapshot {}; previous data kept as snapshot {}",
+            id,
+            safety_snapshot.id
+        );
+        Ok(RestoreOutcome {
+            restored: info,
+            safety_snapshot,
+        })
+    }
+}
+
+fn read_info(path: &Path) -> Result<SnapshotInfo, StorageError> {
+    // The records are skipped over; only the header fields are kept
+    let mut info: SnapshotInfo = serde_json::from_str(&fs::read_to_string(path)?)?;
+    // Trust the actual file name over whatever the archive claims
+    info.file = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{BatchOp, MemoryStorage, MenuItem, Prices, Translations};
+
+    fn item(name: &str) -> MenuItem {
+        MenuItem {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            category_id: category::legacy_id("Mains").unwrap(),
+            description: String::new(),
+            allergens: Vec::new(),
+            is_available: true,
+            revision: 0,
+            trashed_at: None,
+            may_contain: Vec::new(),
+            unmapped_allergens: Vec::new(),
+            dietary_tags: Vec::new(),
+            nutrition: None,
+            prices: Prices::new(),
+            preset_prices: Prices::new(),
+            image: None,
+            recipe: Vec::new(),
+            translations: Translations::new(),
+            stock: None,
+            variants: Vec::new(),
+        }
+    }
+
+    fn store() -> SnapshotStore {
+        let dir = std::env::temp_dir().join(format!("snapshot-test-{}", Uuid::new_v4()));
+        SnapshotStore::new(dir.to_str().unwrap())
+    }
+
+    fn names(items: &[MenuItem]) -> Vec<&str> {
+        items.iter().map(|item| item.name.as_str()).collect()
+    }
+
+    #[test]
+    fn restore_brings_back_live_and_trashed_records() {
+        let storage = MemoryStorage::new();
+        let store = store();
+        let soup = item("Soup");
+        let salad = item("Salad");
+        storage.add_menu_item(soup.clone()).unwrap();
+        storage.add_menu_item(salad.clone()).unwrap();
+        storage.delete_menu_item(salad.id, None).unwrap();
+        let snapshot = store.create(&storage, Some("lunch".to_string())).unwrap();
+        assert_eq!(snapshot.counts.menu_items, 2);
+
+        storage.add_menu_item(item("Stew")).unwrap();
+        storage
+            .apply_batch(vec![BatchOp::PurgeMenuItem(salad.id)])
+            .unwrap();
+        let outcome = store.restore(&storage, snapshot.id).unwrap();
+
+        assert_eq!(names(&storage.get_menu_items().unwrap()), ["Soup"]);
+        assert_eq!(names(&storage.get_trash().unwrap().menu_items), ["Salad"]);
+        assert_eq!(outcome.restored.label.as_deref(), Some("lunch"));
+
+        // The data replaced by the restore was kept as a snapshot of its own
+        assert_eq!(outcome.safety_snapshot.counts.menu_items, 2);
+        store.restore(&storage, outcome.safety_snapshot.id).unwrap();
+        assert_eq!(names(&storage.get_menu_items().unwrap()), ["Soup", "Stew"]);
+        fs::remove_dir_all(&store.dir).unwrap();
+    }
+
+    #[test]
+    fn list_is_newest_first() {
+        let storage = MemoryStorage::new();
+        let store = store();
+        let first = store.create(&storage, None).unwrap();
+        let second = store.create(&storage, None).unwrap();
+
+        let ids: Vec<Uuid> = store.list().unwrap().iter().map(|info| info.id).collect();
+
+        assert_eq!(ids, [second.id, first.id]);
+        fs::remove_dir_all(&store.dir).unwrap();
+    }
+
+    #[test]
+    fn unknown_snapshot_is_not_found_and_changes_nothing() {
+        let storage = MemoryStorage::new();
+        storage.add_menu_item(item("Soup")).unwrap();
+        let store = store();
+
+        assert!(matches!(
+            store.restore(&storage, Uuid::new_v4()),
+            Err(StorageError::NotFound(_))
+        ));
+        assert_eq!(storage.get_menu_items().unwrap().len(), 1);
+        assert!(store.list().unwrap().is_empty());
+    }
+}
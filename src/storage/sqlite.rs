@@ -0,0 +1,1930 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use rusqlite::types::Type;
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use uuid::Uuid;
+
+use super::allergen;
+use super::batch::stale_revision;
+use super::category;
+use super::integrity::{
+    CASCADE_REMEDY, Dependent, MOVE_ITEMS_REMEDY, REMOVE_FROM_RECIPES_REMEDY, ensure_unreferenced,
+    is_upcoming, missing_reference,
+};
+use super::{
+    AdminUser, BatchOp, Dataset, Feedback, Ingredient, MenuCategory, MenuItem, MenuPreset,
+    MenuSchedule, Notice, ScheduleStatus, Storage, StorageError, Trash,
+};
+
+/// Schema migrations, applied in order and recorded in `schema_migrations`.
+///
+/// Never edit a migration that has already shipped; append a new one instead.
+const MIGRATIONS: &[(i64, &str, &str)] = &[
+    (
+        1,
+        "initial schema",
+        r#"
+    CREATE TABLE menu_items (
+        id TEXT PRIMARY KEY NOT NULL,
+        name TEXT NOT NULL,
+        category TEXT NOT NULL,
+        description TEXT NOT NULL,
+        allergens TEXT NOT NULL DEFAULT '[]',
+        is_available INTEGER NOT NULL
+    );
+
+    CREATE TABLE notices (
+        id TEXT PRIMARY KEY NOT NULL,
+        title TEXT NOT NULL,
+        content TEXT NOT NULL,
+        is_active INTEGER NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+
+    CREATE TABLE admin_users (
+        id TEXT PRIMARY KEY NOT NULL,
+        username TEXT NOT NULL UNIQUE,
+        password_hash TEXT NOT NULL
+    );
+
+    CREATE TABLE menu_presets (
+        id TEXT PRIMARY KEY NOT NULL,
+        name TEXT NOT NULL,
+        description TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+
+    CREATE TABLE menu_preset_items (
+        preset_id TEXT NOT NULL REFERENCES menu_presets(id)
+            ON UPDATE CASCADE ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        menu_item_id TEXT NOT NULL,
+        PRIMARY KEY (preset_id, position)
+    );
+    CREATE INDEX idx_menu_preset_items_item ON menu_preset_items(menu_item_id);
+
+    CREATE TABLE menu_schedules (
+        id TEXT PRIMARY KEY NOT NULL,
+        preset_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        description TEXT NOT NULL,
+        start_time TEXT NOT NULL,
+        end_time TEXT NOT NULL,
+        recurrence TEXT NOT NULL,
+        status TEXT NOT NULL,
+        error_message TEXT,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE INDEX idx_menu_schedules_start_time ON menu_schedules(start_time);
+    "#,
+    ),
+    (
+        2,
+        "record revisions",
+        r#"
+    ALTER TABLE menu_items ADD COLUMN revision INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE notices ADD COLUMN revision INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE menu_presets ADD COLUMN revision INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE menu_schedules ADD COLUMN revision INTEGER NOT NULL DEFAULT 0;
+    "#,
+    ),
+    (
+        3,
+        "trash",
+        r#"
+    ALTER TABLE menu_items ADD COLUMN trashed_at TEXT;
+    ALTER TABLE notices ADD COLUMN trashed_at TEXT;
+    ALTER TABLE menu_presets ADD COLUMN trashed_at TEXT;
+    ALTER TABLE menu_schedules ADD COLUMN trashed_at TEXT;
+    "#,
+    ),
+    (
+        4,
+        "structured allergens",
+        r#"
+    ALTER TABLE menu_items ADD COLUMN may_contain TEXT NOT NULL DEFAULT '[]';
+    ALTER TABLE menu_items ADD COLUMN unmapped_allergens TEXT NOT NULL DEFAULT '[]';
+    "#,
+    ),
+    (
+        5,
+        "dietary tags",
+        r#"
+    ALTER TABLE menu_items ADD COLUMN dietary_tags TEXT NOT NULL DEFAULT '[]';
+    "#,
+    ),
+    (
+        6,
+        "nutrition facts",
+        r#"
+    ALTER TABLE menu_items ADD COLUMN nutrition TEXT NOT NULL DEFAULT 'null';
+    "#,
+    ),
+    (
+        7,
+        "prices",
+        r#"
+    ALTER TABLE menu_items ADD COLUMN prices TEXT NOT NULL DEFAULT '{}';
+    ALTER TABLE menu_items ADD COLUMN preset_prices TEXT NOT NULL DEFAULT '{}';
+    ALTER TABLE menu_presets ADD COLUMN price_overrides TEXT NOT NULL DEFAULT '{}';
+    "#,
+    ),
+    (
+        8,
+        "menu categories",
+        r#"
+    CREATE TABLE menu_categories (
+        id TEXT PRIMARY KEY NOT NULL,
+        name TEXT NOT NULL,
+        icon TEXT NOT NULL DEFAULT '',
+        display_order INTEGER NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        revision INTEGER NOT NULL DEFAULT 0,
+        trashed_at TEXT
+    );
+    ALTER TABLE menu_items RENAME COLUMN category TO category_id;
+    "#,
+    ),
+    (
+        9,
+        "item images",
+        r#"
+    ALTER TABLE menu_items ADD COLUMN image TEXT NOT NULL DEFAULT 'null';
+    "#,
+    ),
+    (
+        10,
+        "ingredients and recipes",
+        r#"
+    CREATE TABLE ingredients (
+        id TEXT PRIMARY KEY NOT NULL,
+        name TEXT NOT NULL,
+        allergens TEXT NOT NULL DEFAULT '[]',
+        may_contain TEXT NOT NULL DEFAULT '[]',
+        dietary_tags TEXT NOT NULL DEFAULT '[]',
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        revision INTEGER NOT NULL DEFAULT 0,
+        trashed_at TEXT
+    );
+    ALTER TABLE menu_items ADD COLUMN recipe TEXT NOT NULL DEFAULT '[]';
+    "#,
+    ),
+    (
+        11,
+        "translations",
+        r#"
+    ALTER TABLE menu_items ADD COLUMN translations TEXT NOT NULL DEFAULT '{}';
+    ALTER TABLE notices ADD COLUMN translations TEXT NOT NULL DEFAULT '{}';
+    "#,
+    ),
+    (
+        12,
+        "stock levels",
+        r#"
+    ALTER TABLE menu_items ADD COLUMN stock INTEGER;
+    ALTER TABLE menu_presets ADD COLUMN par_levels TEXT NOT NULL DEFAULT '{}';
+    "#,
+    ),
+    (
+        13,
+        "variants",
+        r#"
+    ALTER TABLE menu_items ADD COLUMN variants TEXT NOT NULL DEFAULT '[]';
+    "#,
+    ),
+    (
+        14,
+        "feedback",
+        r#"
+    CREATE TABLE feedback (
+        id TEXT PRIMARY KEY NOT NULL,
+        menu_item_id TEXT NOT NULL,
+        rating INTEGER NOT NULL,
+        comment TEXT,
+        status TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        moderated_at TEXT,
+        revision INTEGER NOT NULL DEFAULT 0,
+        trashed_at TEXT
+    );
+    CREATE INDEX idx_feedback_menu_item ON feedback (menu_item_id);
+    "#,
+    ),
+    (
+        15,
+        "notice publish window",
+        r#"
+    ALTER TABLE notices ADD COLUMN publish_at TEXT;
+    ALTER TABLE notices ADD COLUMN expire_at TEXT;
+    "#,
+    ),
+    (
+        16,
+        "notice severity and pinning",
+        r#"
+    ALTER TABLE notices ADD COLUMN severity TEXT NOT NULL DEFAULT 'Info';
+    ALTER TABLE notices ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE notices ADD COLUMN emergency INTEGER NOT NULL DEFAULT 0;
+    "#,
+    ),
+];
+
+/// Rewrites existing rows in a way SQL alone cannot express
+type DataMigration = fn(&Connection) -> Result<(), StorageError>;
+
+/// Run right after the schema migration with the same version, in the same
+/// transaction
+const DATA_MIGRATIONS: &[(i64, DataMigration)] = &[(4, map_allergens), (8, seed_categories)];
+
+/// Number of records copied by [`SqliteStorage::import_from`]
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub menu_items: usize,
+    pub notices: usize,
+    pub admin_users: usize,
+    pub menu_presets: usize,
+    pub menu_schedules: usize,
+    pub menu_categories: usize,
+    pub ingredients: usize,
+    pub feedback: usize,
+}
+
+/// Storage backend backed by an embedded SQLite database.
+///
+/// Every call goes straight to the database, so there is no in-memory cache
+/// to keep in sync and the `load_*` methods are no-ops.
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    /// Open (or create) the database at `path` and bring its schema up to date
+    pub fn open(path: &str) -> Result<Self, StorageError> {
+        log::debug!("SqliteStorage::open() started for path: {}", path);
+        if let Some(parent) = Path::new(path).parent()
+            && !parent.as_os_str().is_empty()
+            && !parent.exists()
+        {
+            log::debug!("Creating data directory: {:?}", parent);
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut conn = Connection::open(path)?;
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        run_migrations(&mut conn)?;
+
+        log::debug!("SqliteStorage::open() completed");
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Current schema version, i.e. the highest applied migration
+    pub fn schema_version(&self) -> Result<i64, StorageError> {
+        let conn = self.conn.lock().map_err(|_| StorageError::PoisonError)?;
+        Ok(current_version(&conn)?)
+    }
+
+    /// Copy every record from `source`, trash included, into this database in
+    /// one transaction.
+    ///
+    /// Intended as a one-shot migration from the JSON files, so it refuses to
+    /// run against a database that already holds data.
+    pub fn import_from(&self, source: &dyn Storage) -> Result<ImportSummary, StorageError> {
+        let dataset = source.export_all()?;
+
+        let mut conn = self.conn.lock().map_err(|_| StorageError::PoisonError)?;
+        let existing: i64 = conn.query_row(
+            "SELECT (SELECT COUNT(*) FROM menu_items)
+                  + (SELECT COUNT(*) FROM notices)
+                  + (SELECT COUNT(*) FROM admin_users)
+                  + (SELECT COUNT(*) FROM menu_presets)
+                  + (SELECT COUNT(*) FROM menu_schedules)
+                  + (SELECT COUNT(*) FROM ingredients)
+                  + (SELECT COUNT(*) FROM feedback)",
+            [],
+            |row| row.get(0),
+        )?;
+        // Every new database is seeded with the default categories; only
+        // categories added or edited since count as data
+        let categories = select_all(
+            &conn,
+            &format!("SELECT {} FROM menu_categories", MENU_CATEGORY_COLUMNS),
+            menu_category_from_row,
+        )?;
+        let edited = categories
+            .iter()
+            .any(|category| !category::is_untouched_default(category));
+        if existing > 0 || edited {
+            return Err(StorageError::Conflict(
+                "Target database already contains data; refusing to import".to_string(),
+            ));
+        }
+
+        let tx = conn.transaction()?;
+        // A new database starts out with the default categories; the source
+        // brings its own, which no item here can refer to yet
+        tx.execute("DELETE FROM menu_categories", [])?;
+        insert_dataset(&tx, &dataset)?;
+        tx.commit()?;
+
+        Ok(ImportSummary {
+            menu_items: dataset.menu_items.len(),
+            notices: dataset.notices.len(),
+            admin_users: dataset.admin_users.len(),
+            menu_presets: dataset.menu_presets.len(),
+            menu_schedules: dataset.menu_schedules.len(),
+            menu_categories: dataset.menu_categories.len(),
+            ingredients: dataset.ingredients.len(),
+            feedback: dataset.feedback.len(),
+        })
+    }
+}
+
+fn insert_dataset(conn: &Connection, dataset: &Dataset) -> Result<(), StorageError> {
+    for category in &dataset.menu_categories {
+        insert_menu_category(conn, category)?;
+    }
+    for ingredient in &dataset.ingredients {
+        insert_ingredient(conn, ingredient)?;
+    }
+    for item in &dataset.menu_items {
+        insert_menu_item(conn, item)?;
+    }
+    for notice in &dataset.notices {
+        insert_notice(conn, notice)?;
+    }
+    for user in &dataset.admin_users {
+        insert_admin_user(conn, user)?;
+    }
+    for preset in &dataset.menu_presets {
+        insert_menu_preset(conn, preset)?;
+    }
+    for schedule in &dataset.menu_schedules {
+        insert_menu_schedule(conn, schedule)?;
+    }
+    for feedback in &dataset.feedback {
+        insert_feedback(conn, feedback)?;
+    }
+    Ok(())
+}
+
+fn current_version(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+        [],
+        |row| row.get(0),
+    )
+}
+
+fn run_migrations(conn: &mut Connection) -> Result<(), StorageError> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY NOT NULL,
+            description TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        );",
+    )?;
+
+    let version = current_version(conn)?;
+    for (migration_version, description, sql) in MIGRATIONS {
+        if *migration_version <= version {
+            continue;
+        }
+        log::info!(
+            "Applying database migration {}: {}",
+            migration_version,
+            description
+        );
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        for (_, convert) in DATA_MIGRATIONS
+            .iter()
+            .filter(|(version, _)| version == migration_version)
+        {
+            convert(&tx)?;
+        }
+        tx.execute(
+            "INSERT INTO schema_migrations (version, description, applied_at) VALUES (?1, ?2, ?3)",
+            params![migration_version, description, chrono::Utc::now()],
+        )?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Replace the free-text allergens of every menu item with typed ones
+fn map_allergens(conn: &Connection) -> Result<(), StorageError> {
+    let mut stmt = conn.prepare("SELECT id, name, allergens FROM menu_items")?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                json_column::<Vec<String>>(row, 2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    for (id, name, values) in rows {
+        let mapped = allergen::map_legacy(&id, &name, &values);
+        conn.execute(
+            "UPDATE menu_items SET allergens = ?2, may_contain = ?3, unmapped_allergens = ?4
+             WHERE id = ?1",
+            params![
+                id,
+                serde_json::to_string(&mapped.contains)?,
+                serde_json::to_string(&mapped.may_contain)?,
+                serde_json::to_string(&mapped.unmapped)?,
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+/// Create the categories that replaced the fixed enum and point every menu
+/// item at the one matching its old category name
+fn seed_categories(conn: &Connection) -> Result<(), StorageError> {
+    for category in category::legacy_categories(chrono::Utc::now()) {
+        insert_menu_category(conn, &category)?;
+    }
+
+    let mut stmt = conn.prepare("SELECT id, category_id FROM menu_items")?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    for (id, name) in rows {
+        let category_id = category::legacy_id(&name).ok_or_else(|| {
+            StorageError::Schema(format!("menu item {} has unknown category '{}'", id, name))
+        })?;
+        conn.execute(
+            "UPDATE menu_items SET category_id = ?2 WHERE id = ?1",
+            params![id, category_id.to_string()],
+        )?;
+    }
+    Ok(())
+}
+
+fn conversion_error(
+    idx: usize,
+    err: impl std::error::Error + Send + Sync + 'static,
+) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, Box::new(err))
+}
+
+fn uuid_column(row: &Row, idx: usize) -> rusqlite::Result<Uuid> {
+    let value: String = row.get(idx)?;
+    Uuid::parse_str(&value).map_err(|e| conversion_error(idx, e))
+}
+
+/// Read a column holding the serde name of a unit enum variant
+fn enum_column<T: DeserializeOwned>(row: &Row, idx: usize) -> rusqlite::Result<T> {
+    let value: String = row.get(idx)?;
+    serde_json::from_value(serde_json::Value::String(value)).map_err(|e| conversion_error(idx, e))
+}
+
+/// Read a column holding a JSON document
+fn json_column<T: DeserializeOwned>(row: &Row, idx: usize) -> rusqlite::Result<T> {
+    let value: String = row.get(idx)?;
+    serde_json::from_str(&value).map_err(|e| conversion_error(idx, e))
+}
+
+/// Serde name of a unit enum variant, as stored in text columns
+fn enum_text<T: Serialize>(value: &T) -> Result<String, StorageError> {
+    match serde_json::to_value(value)? {
+        serde_json::Value::String(name) => Ok(name),
+        other => Ok(other.to_string()),
+    }
+}
+
+fn not_found(label: &str, id: Uuid) -> StorageError {
+    StorageError::NotFound(format!("{} with id {} not found", label, id))
+}
+
+fn menu_item_from_row(row: &Row) -> rusqlite::Result<MenuItem> {
+    Ok(MenuItem {
+        id: uuid_column(row, 0)?,
+        name: row.get(1)?,
+        category_id: uuid_column(row, 2)?,
+        description: row.get(3)?,
+        allergens: json_column(row, 4)?,
+        is_available: row.get(5)?,
+        revision: row.get(6)?,
+        trashed_at: row.get(7)?,
+        may_contain: json_column(row, 8)?,
+        unmapped_allergens: json_column(row, 9)?,
+        dietary_tags: json_column(row, 10)?,
+        nutrition: json_column(row, 11)?,
+        prices: json_column(row, 12)?,
+        preset_prices: json_column(row, 13)?,
+        image: json_column(row, 14)?,
+        recipe: json_column(row, 15)?,
+        translations: json_column(row, 16)?,
+        stock: row.get(17)?,
+        variants: json_column(row, 18)?,
+    })
+}
+
+fn insert_menu_item(conn: &Connection, item: &MenuItem) -> Result<(), StorageError> {
+    conn.execute(
+        "INSERT INTO menu_items (id, name, category_id, description, allergens, is_available,
+             revision, trashed_at, may_contain, unmapped_allergens, dietary_tags, nutrition,
+             prices, preset_prices, image, recipe, translations, stock, variants)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17,
+             ?18, ?19)",
+        params![
+            item.id.to_string(),
+            item.name,
+            item.category_id.to_string(),
+            item.description,
+            serde_json::to_string(&item.allergens)?,
+            item.is_available,
+            item.revision,
+            item.trashed_at,
+            serde_json::to_string(&item.may_contain)?,
+            serde_json::to_string(&item.unmapped_allergens)?,
+            serde_json::to_string(&item.dietary_tags)?,
+            serde_json::to_string(&item.nutrition)?,
+            serde_json::to_string(&item.prices)?,
+            serde_json::to_string(&item.preset_prices)?,
+            serde_json::to_string(&item.image)?,
+            serde_json::to_string(&item.recipe)?,
+            serde_json::to_string(&item.translations)?,
+            item.stock,
+            serde_json::to_string(&item.variants)?,
+        ],
+    )?;
+    Ok(())
+}
+
+fn notice_from_row(row: &Row) -> rusqlite::Result<Notice> {
+    Ok(Notice {
+        id: uuid_column(row, 0)?,
+        title: row.get(1)?,
+        content: row.get(2)?,
+        is_active: row.get(3)?,
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+        revision: row.get(6)?,
+        trashed_at: row.get(7)?,
+        translations: json_column(row, 8)?,
+        publish_at: row.get(9)?,
+        expire_at: row.get(10)?,
+        severity: enum_column(row, 11)?,
+        pinned: row.get(12)?,
+        emergency: row.get(13)?,
+    })
+}
+
+fn insert_notice(conn: &Connection, notice: &Notice) -> Result<(), StorageError> {
+    conn.execute(
+        "INSERT INTO notices (id, title, content, is_active, created_at, updated_at, revision,
+             trashed_at, translations, publish_at, expire_at, severity, pinned, emergency)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+        params![
+            notice.id.to_string(),
+            notice.title,
+            notice.content,
+            notice.is_active,
+            notice.created_at,
+            notice.updated_at,
+            notice.revision,
+            notice.trashed_at,
+            serde_json::to_string(&notice.translations)?,
+            notice.publish_at,
+            notice.expire_at,
+            enum_text(&notice.severity)?,
+            notice.pinned,
+            notice.emergency,
+        ],
+    )?;
+    Ok(())
+}
+
+fn admin_user_from_row(row: &Row) -> rusqlite::Result<AdminUser> {
+    Ok(AdminUser {
+        id: uuid_column(row, 0)?,
+        username: row.get(1)?,
+        password_hash: row.get(2)?,
+    })
+}
+
+fn insert_admin_user(conn: &Connection, user: &AdminUser) -> Result<(), StorageError> {
+    conn.execute(
+        "INSERT INTO admin_users (id, username, password_hash) VALUES (?1, ?2, ?3)",
+        params![user.id.to_string(), user.username, user.password_hash],
+    )?;
+    Ok(())
+}
+
+fn menu_preset_from_row(row: &Row) -> rusqlite::Result<MenuPreset> {
+    Ok(MenuPreset {
+        id: uuid_column(row, 0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        menu_item_ids: Vec::new(),
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+        revision: row.get(5)?,
+        trashed_at: row.get(6)?,
+        price_overrides: json_column(row, 7)?,
+        par_levels: json_column(row, 8)?,
+    })
+}
+
+fn insert_preset_items(conn: &Connection, preset: &MenuPreset) -> Result<(), StorageError> {
+    let mut stmt = conn.prepare(
+        "INSERT INTO menu_preset_items (preset_id, position, menu_item_id) VALUES (?1, ?2, ?3)",
+    )?;
+    for (position, item_id) in preset.menu_item_ids.iter().enumerate() {
+        stmt.execute(params![
+            preset.id.to_string(),
+            position as i64,
+            item_id.to_string()
+        ])?;
+    }
+    Ok(())
+}
+
+fn insert_menu_preset(conn: &Connection, preset: &MenuPreset) -> Result<(), StorageError> {
+    conn.execute(
+        "INSERT INTO menu_presets (id, name, description, created_at, updated_at, revision,
+             trashed_at, price_overrides, par_levels)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            preset.id.to_string(),
+            preset.name,
+            preset.description,
+            preset.created_at,
+            preset.updated_at,
+            preset.revision,
+            preset.trashed_at,
+            serde_json::to_string(&preset.price_overrides)?,
+            serde_json::to_string(&preset.par_levels)?,
+        ],
+    )?;
+    insert_preset_items(conn, preset)
+}
+
+fn menu_schedule_from_row(row: &Row) -> rusqlite::Result<MenuSchedule> {
+    Ok(MenuSchedule {
+        id: uuid_column(row, 0)?,
+        preset_id: uuid_column(row, 1)?,
+        name: row.get(2)?,
+        description: row.get(3)?,
+        start_time: row.get(4)?,
+        end_time: row.get(5)?,
+        recurrence: enum_column(row, 6)?,
+        status: enum_column(row, 7)?,
+        error_message: row.get(8)?,
+        created_at: row.get(9)?,
+        updated_at: row.get(10)?,
+        revision: row.get(11)?,
+        trashed_at: row.get(12)?,
+    })
+}
+
+fn insert_menu_schedule(conn: &Connection, schedule: &MenuSchedule) -> Result<(), StorageError> {
+    conn.execute(
+        "INSERT INTO menu_schedules (id, preset_id, name, description, start_time, end_time,
+             recurrence, status, error_message, created_at, updated_at, revision, trashed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        params![
+            schedule.id.to_string(),
+            schedule.preset_id.to_string(),
+            schedule.name,
+            schedule.description,
+            schedule.start_time,
+            schedule.end_time,
+            enum_text(&schedule.recurrence)?,
+            enum_text(&schedule.status)?,
+            schedule.error_message,
+            schedule.created_at,
+            schedule.updated_at,
+            schedule.revision,
+            schedule.trashed_at,
+        ],
+    )?;
+    Ok(())
+}
+
+fn menu_category_from_row(row: &Row) -> rusqlite::Result<MenuCategory> {
+    Ok(MenuCategory {
+        id: uuid_column(row, 0)?,
+        name: row.get(1)?,
+        icon: row.get(2)?,
+        display_order: row.get(3)?,
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+        revision: row.get(6)?,
+        trashed_at: row.get(7)?,
+    })
+}
+
+fn insert_menu_category(conn: &Connection, category: &MenuCategory) -> Result<(), StorageError> {
+    conn.execute(
+        "INSERT INTO menu_categories (id, name, icon, display_order, created_at, updated_at,
+             revision, trashed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            category.id.to_string(),
+            category.name,
+            category.icon,
+            category.display_order,
+            category.created_at,
+            category.updated_at,
+            category.revision,
+            category.trashed_at,
+        ],
+    )?;
+    Ok(())
+}
+
+fn ingredient_from_row(row: &Row) -> rusqlite::Result<Ingredient> {
+    Ok(Ingredient {
+        id: uuid_column(row, 0)?,
+        name: row.get(1)?,
+        allergens: json_column(row, 2)?,
+        may_contain: json_column(row, 3)?,
+        dietary_tags: json_column(row, 4)?,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+        revision: row.get(7)?,
+        trashed_at: row.get(8)?,
+    })
+}
+
+fn insert_ingredient(conn: &Connection, ingredient: &Ingredient) -> Result<(), StorageError> {
+    conn.execute(
+        "INSERT INTO ingredients (id, name, allergens, may_contain, dietary_tags, created_at,
+             updated_at, revision, trashed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            ingredient.id.to_string(),
+            ingredient.name,
+            serde_json::to_string(&ingredient.allergens)?,
+            serde_json::to_string(&ingredient.may_contain)?,
+            serde_json::to_string(&ingredient.dietary_tags)?,
+            ingredient.created_at,
+            ingredient.updated_at,
+            ingredient.revision,
+            ingredient.trashed_at,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Make sure the row exists and, if `expected` is given, is still at that
+/// revision. Runs inside the write transaction so nothing can slip in between.
+///
+/// Returns whether the row is currently in the trash.
+fn check_revision(
+    conn: &Connection,
+    table: &'static str,
+    label: &str,
+    id: Uuid,
+    expected: Option<u64>,
+) -> Result<bool, StorageError> {
+    let current: Option<(u64, bool)> = conn
+        .query_row(
+            &format!(
+                "SELECT revision, trashed_at IS NOT NULL FROM {} WHERE id = ?1",
+                table
+            ),
+            params![id.to_string()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    match (current, expected) {
+        (None, _) => Err(not_found(label, id)),
+        (Some((current, _)), Some(expected)) if current != expected => {
+            Err(stale_revision(label, id, expected, current))
+        }
+        (Some((_, trashed)), _) => Ok(trashed),
+    }
+}
+
+fn update_menu_item(conn: &Connection, id: Uuid, item: &MenuItem) -> Result<(), StorageError> {
+    check_revision(conn, "menu_items", "Menu item", id, Some(item.revision))?;
+    conn.execute(
+        "UPDATE menu_items
+         SET id = ?2, name = ?3, category_id = ?4, description = ?5, allergens = ?6,
+             is_available = ?7, trashed_at = ?8, may_contain = ?9, unmapped_allergens = ?10,
+             dietary_tags = ?11, nutrition = ?12, prices = ?13, preset_prices = ?14,
+             image = ?15, recipe = ?16, translations = ?17, stock = ?18,
+             variants = ?19, revision = revision + 1
+         WHERE id = ?1",
+        params![
+            id.to_string(),
+            item.id.to_string(),
+            item.name,
+            item.category_id.to_string(),
+            item.description,
+            serde_json::to_string(&item.allergens)?,
+            item.is_available,
+            item.trashed_at,
+            serde_json::to_string(&item.may_contain)?,
+            serde_json::to_string(&item.unmapped_allergens)?,
+            serde_json::to_string(&item.dietary_tags)?,
+            serde_json::to_string(&item.nutrition)?,
+            serde_json::to_string(&item.prices)?,
+            serde_json::to_string(&item.preset_prices)?,
+            serde_json::to_string(&item.image)?,
+            serde_json::to_string(&item.recipe)?,
+            serde_json::to_string(&item.translations)?,
+            item.stock,
+            serde_json::to_string(&item.variants)?,
+        ],
+    )?;
+    Ok(())
+}
+
+fn update_notice(conn: &Connection, id: Uuid, notice: &Notice) -> Result<(), StorageError> {
+    check_revision(conn, "notices", "Notice", id, Some(notice.revision))?;
+    conn.execute(
+        "UPDATE notices
+         SET id = ?2, title = ?3, content = ?4, is_active = ?5, created_at = ?6,
+             updated_at = ?7, trashed_at = ?8, translations = ?9, publish_at = ?10,
+             expire_at = ?11, severity = ?12, pinned = ?13, emergency = ?14,
+             revision = revision + 1
+         WHERE id = ?1",
+        params![
+            id.to_string(),
+            notice.id.to_string(),
+            notice.title,
+            notice.content,
+            notice.is_active,
+            notice.created_at,
+            notice.updated_at,
+            notice.trashed_at,
+            serde_json::to_string(&notice.translations)?,
+            notice.publish_at,
+            notice.expire_at,
+            enum_text(&notice.severity)?,
+            notice.pinned,
+            notice.emergency,
+        ],
+    )?;
+    Ok(())
+}
+
+fn update_menu_preset(
+    conn: &Connection,
+    id: Uuid,
+    preset: &MenuPreset,
+) -> Result<(), StorageError> {
+    check_revision(
+        conn,
+        "menu_presets",
+        "Menu preset",
+        id,
+        Some(preset.revision),
+    )?;
+    conn.execute(
+        "UPDATE menu_presets
+         SET id = ?2, name = ?3, description = ?4, created_at = ?5, updated_at = ?6,
+             trashed_at = ?7, price_overrides = ?8, par_levels = ?9, revision = revision + 1
+         WHERE id = ?1",
+        params![
+            id.to_string(),
+            preset.id.to_string(),
+            preset.name,
+            preset.description,
+            preset.created_at,
+            preset.updated_at,
+            preset.trashed_at,
+            serde_json::to_string(&preset.price_overrides)?,
+            serde_json::to_string(&preset.par_levels)?,
+        ],
+    )?;
+    conn.execute(
+        "DELETE FROM menu_preset_items WHERE preset_id = ?1 OR preset_id = ?2",
+        params![id.to_string(), preset.id.to_string()],
+    )?;
+    insert_preset_items(conn, preset)
+}
+
+fn update_menu_schedule(
+    conn: &Connection,
+    id: Uuid,
+    schedule: &MenuSchedule,
+) -> Result<(), StorageError> {
+    check_revision(
+        conn,
+        "menu_schedules",
+        "Menu schedule",
+        id,
+        Some(schedule.revision),
+    )?;
+    conn.execute(
+        "UPDATE menu_schedules
+         SET id = ?2, preset_id = ?3, name = ?4, description = ?5, start_time = ?6,
+             end_time = ?7, recurrence = ?8, status = ?9, error_message = ?10,
+             created_at = ?11, updated_at = ?12, trashed_at = ?13, revision = revision + 1
+         WHERE id = ?1",
+        params![
+            id.to_string(),
+            schedule.id.to_string(),
+            schedule.preset_id.to_string(),
+            schedule.name,
+            schedule.description,
+            schedule.start_time,
+            schedule.end_time,
+            enum_text(&schedule.recurrence)?,
+            enum_text(&schedule.status)?,
+            schedule.error_message,
+            schedule.created_at,
+            schedule.updated_at,
+            schedule.trashed_at,
+        ],
+    )?;
+    Ok(())
+}
+
+fn update_menu_category(
+    conn: &Connection,
+    id: Uuid,
+    category: &MenuCategory,
+) -> Result<(), StorageError> {
+    check_revision(
+        conn,
+        "menu_categories",
+        "Menu category",
+        id,
+        Some(category.revision),
+    )?;
+    conn.execute(
+        "UPDATE menu_categories
+         SET id = ?2, name = ?3, icon = ?4, display_order = ?5, created_at = ?6,
+             updated_at = ?7, trashed_at = ?8, revision = revision + 1
+         WHERE id = ?1",
+        params![
+            id.to_string(),
+            category.id.to_string(),
+            category.name,
+            category.icon,
+            category.display_order,
+            category.created_at,
+            category.updated_at,
+            category.trashed_at,
+        ],
+    )?;
+    Ok(())
+}
+
+fn update_ingredient(
+    conn: &Connection,
+    id: Uuid,
+    ingredient: &Ingredient,
+) -> Result<(), StorageError> {
+    check_revision(
+        conn,
+        "ingredients",
+        "Ingredient",
+        id,
+        Some(ingredient.revision),
+    )?;
+    conn.execute(
+        "UPDATE ingredients
+         SET id = ?2, name = ?3, allergens = ?4, may_contain = ?5, dietary_tags = ?6,
+             created_at = ?7, updated_at = ?8, trashed_at = ?9, revision = revision + 1
+         WHERE id = ?1",
+        params![
+            id.to_string(),
+            ingredient.id.to_string(),
+            ingredient.name,
+            serde_json::to_string(&ingredient.allergens)?,
+            serde_json::to_string(&ingredient.may_contain)?,
+            serde_json::to_string(&ingredient.dietary_tags)?,
+            ingredient.created_at,
+            ingredient.updated_at,
+            ingredient.trashed_at,
+        ],
+    )?;
+    Ok(())
+}
+
+fn feedback_from_row(row: &Row) -> rusqlite::Result<Feedback> {
+    Ok(Feedback {
+        id: uuid_column(row, 0)?,
+        menu_item_id: uuid_column(row, 1)?,
+        rating: row.get(2)?,
+        comment: row.get(3)?,
+        status: enum_column(row, 4)?,
+        created_at: row.get(5)?,
+        moderated_at: row.get(6)?,
+        revision: row.get(7)?,
+        trashed_at: row.get(8)?,
+    })
+}
+
+fn insert_feedback(conn: &Connection, feedback: &Feedback) -> Result<(), StorageError> {
+    conn.execute(
+        "INSERT INTO feedback (id, menu_item_id, rating, comment, status, created_at,
+             moderated_at, revision, trashed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            feedback.id.to_string(),
+            feedback.menu_item_id.to_string(),
+            feedback.rating,
+            feedback.comment,
+            enum_text(&feedback.status)?,
+            feedback.created_at,
+            feedback.moderated_at,
+            feedback.revision,
+            feedback.trashed_at,
+        ],
+    )?;
+    Ok(())
+}
+
+fn update_feedback(conn: &Connection, id: Uuid, feedback: &Feedback) -> Result<(), StorageError> {
+    check_revision(conn, "feedback", "Feedback", id, Some(feedback.revision))?;
+    conn.execute(
+        "UPDATE feedback
+         SET id = ?2, menu_item_id = ?3, rating = ?4, comment = ?5, status = ?6,
+             created_at = ?7, moderated_at = ?8, trashed_at = ?9, revision = revision + 1
+         WHERE id = ?1",
+        params![
+            id.to_string(),
+            feedback.id.to_string(),
+            feedback.menu_item_id.to_string(),
+            feedback.rating,
+            feedback.comment,
+            enum_text(&feedback.status)?,
+            feedback.created_at,
+            feedback.moderated_at,
+            feedback.trashed_at,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Move a live row in one of the entity tables to the trash
+fn trash_by_id(
+    conn: &Connection,
+    table: &'static str,
+    label: &str,
+    id: Uuid,
+    expected: Option<u64>,
+) -> Result<(), StorageError> {
+    // Trashed rows are invisible to callers, so report them as missing
+    if check_revision(conn, table, label, id, expected)? {
+        return Err(not_found(label, id));
+    }
+    conn.execute(
+        &format!(
+            "UPDATE {} SET trashed_at = ?2, revision = revision + 1 WHERE id = ?1",
+            table
+        ),
+        params![id.to_string(), chrono::Utc::now()],
+    )?;
+    Ok(())
+}
+
+/// Permanently delete a trashed row from one of the entity tables
+fn purge_by_id(
+    conn: &Connection,
+    table: &'static str,
+    label: &str,
+    id: Uuid,
+) -> Result<(), StorageError> {
+    if !check_revision(conn, table, label, id, None)? {
+        return Err(StorageError::Conflict(format!(
+            "{} with id {} is not in the trash",
+            label, id
+        )));
+    }
+    conn.execute(
+        &format!("DELETE FROM {} WHERE id = ?1", table),
+        params![id.to_string()],
+    )?;
+    Ok(())
+}
+
+/// Whether a row with this id exists and is not in the trash
+fn is_live(conn: &Connection, table: &'static str, id: Uuid) -> Result<bool, StorageError> {
+    let trashed: Option<bool> = conn
+        .query_row(
+            &format!("SELECT trashed_at IS NOT NULL FROM {} WHERE id = ?1", table),
+            params![id.to_string()],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(trashed == Some(false))
+}
+
+/// Make sure a live menu item is filed under a live category and made from
+/// live ingredients
+fn check_item_references(conn: &Connection, item: &MenuItem) -> Result<(), StorageError> {
+    if item.trashed_at.is_some() {
+        return Ok(());
+    }
+    if !is_live(conn, "menu_categories", item.category_id)? {
+        return Err(missing_reference(
+            "Menu item",
+            &item.name,
+            "menu category",
+            item.category_id,
+        ));
+    }
+    for line in &item.recipe {
+        if !is_live(conn, "ingredients", line.ingredient_id)? {
+            return Err(missing_reference(
+                "Menu item",
+                &item.name,
+                "ingredient",
+                line.ingredient_id,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Make sure every item a live preset lists is live itself
+fn check_preset_references(conn: &Connection, preset: &MenuPreset) -> Result<(), StorageError> {
+    if preset.trashed_at.is_some() {
+        return Ok(());
+    }
+    for item_id in &preset.menu_item_ids {
+        if !is_live(conn, "menu_items", *item_id)? {
+            return Err(missing_reference(
+                "Menu preset",
+                &preset.name,
+                "menu item",
+                *item_id,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Make sure a schedule that can still run points at a live preset
+fn check_schedule_references(
+    conn: &Connection,
+    schedule: &MenuSchedule,
+) -> Result<(), StorageError> {
+    if schedule.trashed_at.is_some()
+        || !is_upcoming(&schedule.status)
+        || is_live(conn, "menu_presets", schedule.preset_id)?
+    {
+        return Ok(());
+    }
+    Err(missing_reference(
+        "Menu schedule",
+        &schedule.name,
+        "menu preset",
+        schedule.preset_id,
+    ))
+}
+
+/// Live menu items filed under the category
+fn item_dependents(conn: &Connection, category_id: Uuid) -> Result<Vec<Dependent>, StorageError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name FROM menu_items
+         WHERE category_id = ?1 AND trashed_at IS NULL
+         ORDER BY rowid",
+    )?;
+    let rows = stmt.query_map(params![category_id.to_string()], |row| {
+        Ok(Dependent {
+            label: "menu item",
+            id: uuid_column(row, 0)?,
+            name: row.get(1)?,
+        })
+    })?;
+    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+}
+
+/// Live menu items whose recipe uses the ingredient
+fn recipe_dependents(
+    conn: &Connection,
+    ingredient_id: Uuid,
+) -> Result<Vec<Dependent>, StorageError> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT m.id, m.name FROM menu_items m, json_each(m.recipe) r
+         WHERE json_extract(r.value, '$.ingredient_id') = ?1 AND m.trashed_at IS NULL
+         ORDER BY m.rowid",
+    )?;
+    let rows = stmt.query_map(params![ingredient_id.to_string()], |row| {
+        Ok(Dependent {
+            label: "menu item",
+            id: uuid_column(row, 0)?,
+            name: row.get(1)?,
+        })
+    })?;
+    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+}
+
+/// Live presets that list the menu item
+fn preset_dependents(conn: &Connection, item_id: Uuid) -> Result<Vec<Dependent>, StorageError> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT p.id, p.name FROM menu_presets p
+         JOIN menu_preset_items l ON l.preset_id = p.id
+         WHERE l.menu_item_id = ?1 AND p.trashed_at IS NULL
+         ORDER BY p.rowid",
+    )?;
+    let rows = stmt.query_map(params![item_id.to_string()], |row| {
+        Ok(Dependent {
+            label: "menu preset",
+            id: uuid_column(row, 0)?,
+            name: row.get(1)?,
+        })
+    })?;
+    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+}
+
+/// Pending or active schedules that will apply the preset
+fn schedule_dependents(conn: &Connection, preset_id: Uuid) -> Result<Vec<Dependent>, StorageError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, status FROM menu_schedules
+         WHERE preset_id = ?1 AND trashed_at IS NULL
+         ORDER BY rowid",
+    )?;
+    let rows = stmt.query_map(params![preset_id.to_string()], |row| {
+        let status: ScheduleStatus = enum_column(row, 2)?;
+        Ok((
+            Dependent {
+                label: "menu schedule",
+                id: uuid_column(row, 0)?,
+                name: row.get(1)?,
+            },
+            status,
+        ))
+    })?;
+    let mut dependents = Vec::new();
+    for row in rows {
+        let (dependent, status) = row?;
+        if is_upcoming(&status) {
+            dependents.push(dependent);
+        }
+    }
+    Ok(dependents)
+}
+
+fn apply_op(conn: &Connection, op: &BatchOp) -> Result<(), StorageError> {
+    match op {
+        BatchOp::AddMenuItem(item) => {
+            check_item_references(conn, item)?;
+            insert_menu_item(conn, item)
+        }
+        BatchOp::UpdateMenuItem(id, item) => {
+            check_item_references(conn, item)?;
+            update_menu_item(conn, *id, item)
+        }
+        BatchOp::DeleteMenuItem(id, expected) => {
+            ensure_unreferenced(
+                "Menu item",
+                *id,
+                &preset_dependents(conn, *id)?,
+                CASCADE_REMEDY,
+            )?;
+            trash_by_id(conn, "menu_items", "Menu item", *id, *expected)
+        }
+        BatchOp::PurgeMenuItem(id) => purge_by_id(conn, "menu_items", "Menu item", *id),
+        BatchOp::AddNotice(notice) => insert_notice(conn, notice),
+        BatchOp::UpdateNotice(id, notice) => update_notice(conn, *id, notice),
+        BatchOp::DeleteNotice(id, expected) => {
+            trash_by_id(conn, "notices", "Notice", *id, *expected)
+        }
+        BatchOp::PurgeNotice(id) => purge_by_id(conn, "notices", "Notice", *id),
+        BatchOp::AddMenuPreset(preset) => {
+            check_preset_references(conn, preset)?;
+            insert_menu_preset(conn, preset)
+        }
+        BatchOp::UpdateMenuPreset(id, preset) => {
+            check_preset_references(conn, preset)?;
+            update_menu_preset(conn, *id, preset)
+        }
+        BatchOp::DeleteMenuPreset(id, expected) => {
+            ensure_unreferenced(
+                "Menu preset",
+                *id,
+                &schedule_dependents(conn, *id)?,
+                CASCADE_REMEDY,
+            )?;
+            trash_by_id(conn, "menu_presets", "Menu preset", *id, *expected)
+        }
+        BatchOp::PurgeMenuPreset(id) => purge_by_id(conn, "menu_presets", "Menu preset", *id),
+        BatchOp::AddMenuSchedule(schedule) => {
+            check_schedule_references(conn, schedule)?;
+            insert_menu_schedule(conn, schedule)
+        }
+        BatchOp::UpdateMenuSchedule(id, schedule) => {
+            check_schedule_references(conn, schedule)?;
+            update_menu_schedule(conn, *id, schedule)
+        }
+        BatchOp::DeleteMenuSchedule(id, expected) => {
+            trash_by_id(conn, "menu_schedules", "Menu schedule", *id, *expected)
+        }
+        BatchOp::PurgeMenuSchedule(id) => purge_by_id(conn, "menu_schedules", "Menu schedule", *id),
+        BatchOp::AddMenuCategory(category) => insert_menu_category(conn, category),
+        BatchOp::UpdateMenuCategory(id, category) => update_menu_category(conn, *id, category),
+        BatchOp::DeleteMenuCategory(id, expected) => {
+            ensure_unreferenced(
+                "Menu category",
+                *id,
+                &item_dependents(conn, *id)?,
+                MOVE_ITEMS_REMEDY,
+            )?;
+            trash_by_id(conn, "menu_categories", "Menu category", *id, *expected)
+        }
+        BatchOp::PurgeMenuCategory(id) => {
+            purge_by_id(conn, "menu_categories", "Menu category", *id)
+        }
+        BatchOp::AddIngredient(ingredient) => insert_ingredient(conn, ingredient),
+        BatchOp::UpdateIngredient(id, ingredient) => update_ingredient(conn, *id, ingredient),
+        BatchOp::DeleteIngredient(id, expected) => {
+            ensure_unreferenced(
+                "Ingredient",
+                *id,
+                &recipe_dependents(conn, *id)?,
+                REMOVE_FROM_RECIPES_REMEDY,
+            )?;
+            trash_by_id(conn, "ingredients", "Ingredient", *id, *expected)
+        }
+        BatchOp::PurgeIngredient(id) => purge_by_id(conn, "ingredients", "Ingredient", *id),
+        BatchOp::AddFeedback(feedback) => insert_feedback(conn, feedback),
+        BatchOp::UpdateFeedback(id, feedback) => update_feedback(conn, *id, feedback),
+        BatchOp::DeleteFeedback(id, expected) => {
+            trash_by_id(conn, "feedback", "Feedback", *id, *expected)
+        }
+        BatchOp::PurgeFeedback(id) => purge_by_id(conn, "feedback", "Feedback", *id),
+    }
+}
+
+const MENU_ITEM_COLUMNS: &str = "id, name, category_id, description, allergens, is_available, \
+     revision, trashed_at, may_contain, unmapped_allergens, dietary_tags, nutrition, prices, \
+     preset_prices, image, recipe, translations, stock, variants";
+const NOTICE_COLUMNS: &str = "id, title, content, is_active, created_at, updated_at, revision, \
+     trashed_at, translations, publish_at, expire_at, severity, pinned, emergency";
+const MENU_PRESET_COLUMNS: &str = "id, name, description, created_at, updated_at, revision, \
+     trashed_at, price_overrides, par_levels";
+const MENU_SCHEDULE_COLUMNS: &str = "id, preset_id, name, description, start_time, end_time, \
+     recurrence, status, error_message, created_at, updated_at, revision, trashed_at";
+const MENU_CATEGORY_COLUMNS: &str =
+    "id, name, icon, display_order, created_at, updated_at, revision, trashed_at";
+const FEEDBACK_COLUMNS: &str = "id, menu_item_id, rating, comment, status, created_at, \
+     moderated_at, revision, trashed_at";
+const INGREDIENT_COLUMNS: &str = "id, name, allergens, may_contain, dietary_tags, created_at, \
+     updated_at, revision, trashed_at";
+
+/// Rows of `table` that are live (`trashed = false`) or in the trash
+fn select_sql(columns: &str, table: &str, trashed: bool) -> String {
+    format!(
+        "SELECT {} FROM {} WHERE trashed_at IS {} ORDER BY rowid",
+        columns,
+        table,
+        if trashed { "NOT NULL" } else { "NULL" }
+    )
+}
+
+fn select_all<T>(
+    conn: &Connection,
+    sql: &str,
+    map: impl FnMut(&Row) -> rusqlite::Result<T>,
+) -> Result<Vec<T>, StorageError> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map([], map)?;
+    Ok(rows.collect::<rusqlite::Result<Vec<T>>>()?)
+}
+
+/// Presets selected by `sql`, with their item lists filled in
+fn select_presets(conn: &Connection, sql: &str) -> Result<Vec<MenuPreset>, StorageError> {
+    let mut presets = select_all(conn, sql, menu_preset_from_row)?;
+    let links = select_all(
+        conn,
+        "SELECT preset_id, menu_item_id FROM menu_preset_items
+         ORDER BY preset_id, position",
+        |row| Ok((uuid_column(row, 0)?, uuid_column(row, 1)?)),
+    )?;
+
+    let mut items_by_preset: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    for (preset_id, item_id) in links {
+        items_by_preset.entry(preset_id).or_default().push(item_id);
+    }
+    for preset in &mut presets {
+        preset.menu_item_ids = items_by_preset.remove(&preset.id).unwrap_or_default();
+    }
+    Ok(presets)
+}
+
+impl SqliteStorage {
+    fn query_all<T>(
+        &self,
+        sql: &str,
+        map: impl FnMut(&Row) -> rusqlite::Result<T>,
+    ) -> Result<Vec<T>, StorageError> {
+        let conn = self.conn.lock().map_err(|_| StorageError::PoisonError)?;
+        select_all(&conn, sql, map)
+    }
+
+    /// Live or trashed presets, with their item lists filled in
+    fn query_presets(&self, trashed: bool) -> Result<Vec<MenuPreset>, StorageError> {
+        let conn = self.conn.lock().map_err(|_| StorageError::PoisonError)?;
+        select_presets(
+            &conn,
+            &select_sql(MENU_PRESET_COLUMNS, "menu_presets", trashed),
+        )
+    }
+
+    /// Run `f` against the connection inside a transaction
+    fn with_transaction(
+        &self,
+        f: impl FnOnce(&Connection) -> Result<(), StorageError>,
+    ) -> Result<(), StorageError> {
+        let mut conn = self.conn.lock().map_err(|_| StorageError::PoisonError)?;
+        let tx = conn.transaction()?;
+        f(&tx)?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load_menu_items(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn load_notices(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn load_admin_users(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn load_menu_presets(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn load_menu_schedules(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn load_menu_categories(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn load_ingredients(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn load_feedback(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn get_menu_items(&self) -> Result<Vec<MenuItem>, StorageError> {
+        self.query_all(
+            &select_sql(MENU_ITEM_COLUMNS, "menu_items", false),
+            menu_item_from_row,
+        )
+    }
+
+    fn add_menu_item(&self, item: MenuItem) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::AddMenuItem(item)])
+    }
+
+    fn update_menu_item(&self, id: Uuid, updated_item: MenuItem) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::UpdateMenuItem(id, updated_item)])
+    }
+
+    fn delete_menu_item(
+        &self,
+        id: Uuid,
+        expected_revision: Option<u64>,
+    ) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::DeleteMenuItem(id, expected_revision)])
+    }
+
+    fn get_notices(&self) -> Result<Vec<Notice>, StorageError> {
+        self.query_all(
+            &select_sql(NOTICE_COLUMNS, "notices", false),
+            notice_from_row,
+        )
+    }
+
+    fn add_notice(&self, notice: Notice) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::AddNotice(notice)])
+    }
+
+    fn update_notice(&self, id: Uuid, updated_notice: Notice) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::UpdateNotice(id, updated_notice)])
+    }
+
+    fn delete_notice(&self, id: Uuid, expected_revision: Option<u64>) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::DeleteNotice(id, expected_revision)])
+    }
+
+    fn get_admin_users(&self) -> Result<Vec<AdminUser>, StorageError> {
+        self.query_all(
+            "SELECT id, username, password_hash FROM admin_users ORDER BY rowid",
+            admin_user_from_row,
+        )
+    }
+
+    fn get_admin_user_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<AdminUser>, StorageError> {
+        let conn = self.conn.lock().map_err(|_| StorageError::PoisonError)?;
+        Ok(conn
+            .query_row(
+                "SELECT id, username, password_hash FROM admin_users WHERE username = ?1",
+                params![username],
+                admin_user_from_row,
+            )
+            .optional()?)
+    }
+
+    fn add_admin_user(&self, user: AdminUser) -> Result<(), StorageError> {
+        let conn = self.conn.lock().map_err(|_| StorageError::PoisonError)?;
+        insert_admin_user(&conn, &user)
+    }
+
+    fn get_menu_presets(&self) -> Result<Vec<MenuPreset>, StorageError> {
+        self.query_presets(false)
+    }
+
+    fn add_menu_preset(&self, preset: MenuPreset) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::AddMenuPreset(preset)])
+    }
+
+    fn update_menu_preset(&self, id: Uuid, updated_preset: MenuPreset) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::UpdateMenuPreset(id, updated_preset)])
+    }
+
+    fn delete_menu_preset(
+        &self,
+        id: Uuid,
+        expected_revision: Option<u64>,
+    ) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::DeleteMenuPreset(id, expected_revision)])
+    }
+
+    fn get_menu_schedules(&self) -> Result<Vec<MenuSchedule>, StorageError> {
+        self.query_all(
+            &select_sql(MENU_SCHEDULE_COLUMNS, "menu_schedules", false),
+            menu_schedule_from_row,
+        )
+    }
+
+    fn add_menu_schedule(&self, schedule: MenuSchedule) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::AddMenuSchedule(schedule)])
+    }
+
+    fn update_menu_schedule(
+        &self,
+        id: Uuid,
+        updated_schedule: MenuSchedule,
+    ) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::UpdateMenuSchedule(id, updated_schedule)])
+    }
+
+    fn delete_menu_schedule(
+        &self,
+        id: Uuid,
+        expected_revision: Option<u64>,
+    ) -> Result<(), StorageError> {
+        self.apply_batch(vec![BatchOp::DeleteMenuSchedule(id, expected_revision)])
+    }
+
+    fn get_menu_categories(&self) -> Result<Vec<MenuCategory>, StorageError> {
+        self.query_all(
+            &format!(
+                "SELECT {} FROM menu_categories WHERE trashed_at IS NULL
+                 ORDER BY display_order, name",
+                MENU_CATEGORY_COLUMNS
+            ),
+            menu_category_from_row,
+        )
+    }
+
+    fn get_ingredients(&self) -> Result<Vec<Ingredient>, StorageError> {
+        self.query_all(
+            &format!(
+                "SELECT {} FROM ingredients WHERE trashed_at IS NULL ORDER BY name",
+                INGREDIENT_COLUMNS
+            ),
+            ingredient_from_row,
+        )
+    }
+
+    fn get_feedback(&self) -> Result<Vec<Feedback>, StorageError> {
+        self.query_all(
+            &format!(
+                "SELECT {} FROM feedback WHERE trashed_at IS NULL ORDER BY created_at",
+                FEEDBACK_COLUMNS
+            ),
+            feedback_from_row,
+        )
+    }
+
+    fn get_trash(&self) -> Result<Trash, StorageError> {
+        Ok(Trash {
+            menu_items: self.query_all(
+                &select_sql(MENU_ITEM_COLUMNS, "menu_items", true),
+                menu_item_from_row,
+            )?,
+            notices: self.query_all(
+                &select_sql(NOTICE_COLUMNS, "notices", true),
+                notice_from_row,
+            )?,
+            menu_presets: self.query_presets(true)?,
+            menu_schedules: self.query_all(
+                &select_sql(MENU_SCHEDULE_COLUMNS, "menu_schedules", true),
+                menu_schedule_from_row,
+            )?,
+            menu_categories: self.query_all(
+                &select_sql(MENU_CATEGORY_COLUMNS, "menu_categories", true),
+                menu_category_from_row,
+            )?,
+            ingredients: self.query_all(
+                &select_sql(INGREDIENT_COLUMNS, "ingredients", true),
+                ingredient_from_row,
+            )?,
+            feedback: self.query_all(
+                &select_sql(FEEDBACK_COLUMNS, "feedback", true),
+                feedback_from_row,
+            )?,
+        })
+    }
+
+    fn apply_batch(&self, ops: Vec<BatchOp>) -> Result<(), StorageError> {
+        self.with_transaction(|conn| {
+            for op in &ops {
+                apply_op(conn, op)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn export_all(&self) -> Result<Dataset, StorageError> {
+        // One lock and one read transaction, like replace_all, so a snapshot
+        // never mixes collections from before and after a concurrent write
+        let mut conn = self.conn.lock().map_err(|_| StorageError::PoisonError)?;
+        let tx = conn.transaction()?;
+        let all = |columns: &str, table: &str| {
+            format!("SELECT {} FROM {} ORDER BY rowid", columns, table)
+        };
+        let dataset = Dataset {
+            menu_items: select_all(
+                &tx,
+                &all(MENU_ITEM_COLUMNS, "menu_items"),
+                menu_item_from_row,
+            )?,
+            notices: select_all(&tx, &all(NOTICE_COLUMNS, "notices"), notice_from_row)?,
+            admin_users: select_all(
+                &tx,
+                "SELECT id, username, password_hash FROM admin_users ORDER BY rowid",
+                admin_user_from_row,
+            )?,
+            menu_presets: select_presets(&tx, &all(MENU_PRESET_COLUMNS, "menu_presets"))?,
+            menu_schedules: select_all(
+                &tx,
+                &all(MENU_SCHEDULE_COLUMNS, "menu_schedules"),
+                menu_schedule_from_row,
+            )?,
+            menu_categories: select_all(
+                &tx,
+                &all(MENU_CATEGORY_COLUMNS, "menu_categories"),
+                menu_category_from_row,
+            )?,
+            ingredients: select_all(
+                &tx,
+                &all(INGREDIENT_COLUMNS, "ingredients"),
+                ingredient_from_row,
+            )?,
+            feedback: select_all(&tx, &all(FEEDBACK_COLUMNS, "feedback"), feedback_from_row)?,
+        };
+        tx.commit()?;
+        Ok(dataset)
+    }
+
+    fn replace_all(&self, dataset: Dataset) -> Result<(), StorageError> {
+        self.with_transaction(|conn| {
+            conn.execute_batch(
+                "DELETE FROM menu_preset_items;
+                 DELETE FROM menu_schedules;
+                 DELETE FROM menu_presets;
+                 DELETE FROM notices;
+                 DELETE FROM menu_items;
+                 DELETE FROM menu_categories;
+                 DELETE FROM ingredients;
+                 DELETE FROM feedback;
+                 DELETE FROM admin_users;",
+            )?;
+            insert_dataset(conn, &dataset)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{Allergen, MemoryStorage, Prices, Translations};
+
+    fn item(name: &str) -> MenuItem {
+        MenuItem {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            category_id: category::legacy_id("Mains").unwrap(),
+            description: String::new(),
+            allergens: vec![Allergen::Milk],
+            is_available: true,
+            revision: 0,
+            trashed_at: None,
+            may_contain: Vec::new(),
+            unmapped_allergens: Vec::new(),
+            dietary_tags: Vec::new(),
+            nutrition: None,
+            prices: Prices::new(),
+            preset_prices: Prices::new(),
+            image: None,
+            recipe: Vec::new(),
+            translations: Translations::new(),
+            stock: None,
+            variants: Vec::new(),
+        }
+    }
+
+    /// A database as the first release created it, holding one item
+    fn version_one_database() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        let (version, description, sql) = MIGRATIONS[0];
+        conn.execute_batch(
+            "CREATE TABLE schema_migrations (
+                version INTEGER PRIMARY KEY NOT NULL,
+                description TEXT NOT NULL,
+                applied_at TEXT NOT NULL
+            );",
+        )
+        .unwrap();
+        conn.execute_batch(sql).unwrap();
+        conn.execute(
+            "INSERT INTO schema_migrations (version, description, applied_at) VALUES (?1, ?2, ?3)",
+            params![version, description, chrono::Utc::now()],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO menu_items (id, name, category, description, allergens, is_available)
+             VALUES (?1, 'Cheese Toastie', 'Mains', '', ?2, 1)",
+            params![
+                Uuid::new_v4().to_string(),
+                r#"["dairy", "traces of sesame", "glitter"]"#
+            ],
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn new_database_is_at_the_latest_version() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+
+        assert_eq!(
+            storage.schema_version().unwrap(),
+            MIGRATIONS.last().unwrap().0
+        );
+        assert_eq!(
+            storage.get_menu_categories().unwrap().len(),
+            category::legacy_categories(chrono::Utc::now()).len()
+        );
+    }
+
+    #[test]
+    fn old_database_is_upgraded_with_its_data() {
+        let mut conn = version_one_database();
+
+        run_migrations(&mut conn).unwrap();
+        let storage = SqliteStorage {
+            conn: Mutex::new(conn),
+        };
+
+        assert_eq!(
+            storage.schema_version().unwrap(),
+            MIGRATIONS.last().unwrap().0
+        );
+        let item = &storage.get_menu_items().unwrap()[0];
+        assert_eq!(item.allergens, vec![Allergen::Milk]);
+        assert_eq!(item.may_contain, vec![Allergen::Sesame]);
+        assert_eq!(item.unmapped_allergens, vec!["glitter".to_string()]);
+        assert_eq!(item.revision, 0);
+        assert_eq!(item.category_id, category::legacy_id("Mains").unwrap());
+    }
+
+    #[test]
+    fn migrations_are_not_applied_twice() {
+        let mut conn = version_one_database();
+        run_migrations(&mut conn).unwrap();
+
+        run_migrations(&mut conn).unwrap();
+
+        let applied: i64 = conn
+            .query_row("SELECT COUNT(*) FROM schema_migrations", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(applied, MIGRATIONS.len() as i64);
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        let mut soup = item("Soup");
+        storage.add_menu_item(soup.clone()).unwrap();
+
+        soup.name = "Tomato soup".to_string();
+        storage.update_menu_item(soup.id, soup.clone()).unwrap();
+
+        let items = storage.get_menu_items().unwrap();
+        assert_eq!(items[0].name, "Tomato soup");
+        assert_eq!(items[0].allergens, vec![Allergen::Milk]);
+        storage.delete_menu_item(soup.id, None).unwrap();
+        assert!(matches!(
+            storage.delete_menu_item(soup.id, None),
+            Err(StorageError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn import_copies_every_record_once() {
+        let source = MemoryStorage::new();
+        source.add_menu_item(item("Soup")).unwrap();
+        let target = SqliteStorage::open(":memory:").unwrap();
+
+        let summary = target.import_from(&source).unwrap();
+
+        assert_eq!(summary.menu_items, 1);
+        assert_eq!(target.get_menu_items().unwrap()[0].name, "Soup");
+        assert!(matches!(
+            target.import_from(&source),
+            Err(StorageError::Conflict(_))
+        ));
+    }
+
+    #[test]
+    fn import_refuses_a_database_whose_categories_were_edited() {
+        let source = MemoryStorage::new();
+        let target = SqliteStorage::open(":memory:").unwrap();
+        let mut mains = target.get_menu_categories().unwrap()[0].clone();
+        mains.name = "Hot food".to_string();
+        target.update_menu_category(mains.id, mains).unwrap();
+
+        assert!(matches!(
+            target.import_from(&source),
+            Err(StorageError::Conflict(_))
+        ));
+    }
+
+    #[test]
+    fn failing_batch_rolls_back_the_transaction() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        let soup = item("Soup");
+        storage.add_menu_item(soup.clone()).unwrap();
+
+        let mut renamed = soup.clone();
+        renamed.name = "Tomato soup".to_string();
+        let result = storage.apply_batch(vec![
+            BatchOp::UpdateMenuItem(soup.id, renamed),
+            BatchOp::DeleteMenuItem(Uuid::new_v4(), None),
+        ]);
+
+        assert!(matches!(result, Err(StorageError::NotFound(_))));
+        assert_eq!(storage.get_menu_items().unwrap()[0].name, "Soup");
+    }
+
+    #[test]
+    fn export_includes_trash_and_replace_swaps_everything() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        let soup = item("Soup");
+        let salad = item("Salad");
+        storage.add_menu_item(soup.clone()).unwrap();
+        storage.add_menu_item(salad.clone()).unwrap();
+        storage.delete_menu_item(salad.id, None).unwrap();
+
+        let dataset = storage.export_all().unwrap();
+        assert_eq!(dataset.menu_items.len(), 2);
+
+        let other = SqliteStorage::open(":memory:").unwrap();
+        other.add_menu_item(item("Stew")).unwrap();
+        other.replace_all(dataset).unwrap();
+
+        let live = other.get_menu_items().unwrap();
+        assert_eq!(live.len(), 1);
+        assert_eq!(live[0].id, soup.id);
+        assert_eq!(other.get_trash().unwrap().menu_items[0].id, salad.id);
+    }
+}
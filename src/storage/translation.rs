@@ -0,0 +1,56 @@
+use std::collections::BTreeMap;
+
+/// Translated text of a record by locale, then by field name, e.g.
+/// `{"fr": {"name": "Soupe du jour"}}`. A field left out for a locale falls
+/// back to the record's own text.
+pub type Translations = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Fields of a menu item that can be translated
+pub const MENU_ITEM_FIELDS: &[&str] = &["name", "description"];
+
+/// Fields of a notice that can be translated
+pub const NOTICE_FIELDS: &[&str] = &["title", "content"];
+
+/// Text of `field` in `locale`, if it has been translated
+pub fn lookup<'a>(translations: &'a Translations, locale: &str, field: &str) -> Option<&'a str> {
+    translations
+        .get(locale)
+        .and_then(|fields| fields.get(field))
+        .map(String::as_str)
+}
+
+/// Which of `fields` have no translation in `locale`
+pub fn missing(
+    translations: &Translations,
+    locale: &str,
+    fields: &[&'static str],
+) -> Vec<&'static str> {
+    fields
+        .iter()
+        .copied()
+        .filter(|field| lookup(translations, locale, field).is_none())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_lists_untranslated_fields_of_a_locale() {
+        let translations = Translations::from([(
+            "fr".to_string(),
+            BTreeMap::from([("name".to_string(), "Soupe du jour".to_string())]),
+        )]);
+
+        assert_eq!(lookup(&translations, "fr", "name"), Some("Soupe du jour"));
+        assert_eq!(
+            missing(&translations, "fr", MENU_ITEM_FIELDS),
+            ["description"]
+        );
+        assert_eq!(
+            missing(&translations, "de", MENU_ITEM_FIELDS),
+            MENU_ITEM_FIELDS
+        );
+    }
+}
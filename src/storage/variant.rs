@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{Nutrition, Prices};
+
+/// Portion sizes an item can be served in
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PortionSize {
+    Small,
+    Regular,
+    Large,
+}
+
+impl PortionSize {
+    pub const ALL: [PortionSize; 3] =
+        [PortionSize::Small, PortionSize::Regular, PortionSize::Large];
+
+    /// Name used in the API and data files
+    pub fn code(self) -> &'static str {
+        match self {
+            PortionSize::Small => "Small",
+            PortionSize::Regular => "Regular",
+            PortionSize::Large => "Large",
+        }
+    }
+
+    /// Name shown to diners
+    pub fn label(self) -> &'static str {
+        match self {
+            PortionSize::Small => "Small",
+            PortionSize::Regular => "Regular",
+            PortionSize::Large => "Large",
+        }
+    }
+}
+
+/// One way a menu item is served, e.g. a large coffee. Diners see the
+/// variants listed under their item, smallest first.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Variant {
+    pub id: Uuid,
+    pub name: String,
+    pub size: PortionSize,
+    /// Prices of this portion; a tier left out is charged the item's price
+    #[serde(default)]
+    pub prices: Prices,
+    /// Nutrition facts of this portion, if they differ from the item's
+    #[serde(default)]
+    pub nutrition: Option<Nutrition>,
+    /// Only shown to diners when the item itself is available too
+    pub is_available: bool,
+}
@@ -0,0 +1,496 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use super::batch::{live, trashed};
+use super::integrity::{find_dangling, missing_reference};
+use super::schema::{self, Migration};
+use super::{
+    DanglingReference, Ingredient, JsonStorage, MenuCategory, MenuItem, MenuPreset, MenuSchedule,
+    StorageError, Trash,
+};
+
+/// How long to let an editor finish writing before a changed file is reloaded
+const SETTLE_DELAY: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Collection {
+    MenuItems,
+    Notices,
+    AdminUsers,
+    MenuPresets,
+    MenuSchedules,
+    MenuCategories,
+    Ingredients,
+    Feedback,
+}
+
+/// Handle for a running data file watcher; hot reloading stops when it is dropped
+pub struct DataWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl JsonStorage {
+    /// Watch the eight data files and reload whichever one changes on disk.
+    ///
+    /// A file is only swapped in if it parses and does not leave presets or
+    /// schedules referring to records that are missing or in the trash;
+    /// otherwise the change is logged and rejected, and the data already in
+    /// memory stays in use.
+    pub fn watch(self: &Arc<Self>) -> Result<DataWatcher, StorageError> {
+        let targets = vec![
+            (normalize(&self.menu_items_path)?, Collection::MenuItems),
+            (normalize(&self.notices_path)?, Collection::Notices),
+            (normalize(&self.admin_users_path)?, Collection::AdminUsers),
+            (normalize(&self.menu_presets_path)?, Collection::MenuPresets),
+            (
+                normalize(&self.menu_schedules_path)?,
+                Collection::MenuSchedules,
+            ),
+            (
+                normalize(&self.menu_categories_path)?,
+                Collection::MenuCategories,
+            ),
+            (normalize(&self.ingredients_path)?, Collection::Ingredients),
+            (normalize(&self.feedback_path)?, Collection::Feedback),
+        ];
+
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+
+        // Watch the directories rather than the files themselves: atomic saves
+        // replace the file, which would silently end a watch on the old inode
+        let mut dirs: Vec<PathBuf> = targets
+            .iter()
+            .filter_map(|(path, _)| path.parent().map(Path::to_path_buf))
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        for dir in &dirs {
+            log::info!("Watching {:?} for data file changes", dir);
+            watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        }
+
+        let storage = Arc::clone(self);
+        thread::Builder::new()
+            .name("data-watcher".to_string())
+            .spawn(move || run(&storage, &targets, rx))?;
+
+        Ok(DataWatcher { _watcher: watcher })
+    }
+}
+
+/// Absolute form of a data path whose file may not exist yet
+fn normalize(path: &str) -> Result<PathBuf, StorageError> {
+    let path = Path::new(path);
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file_name = path.file_name().unwrap_or_default();
+    Ok(fs::canonicalize(dir)?.join(file_name))
+}
+
+fn run(
+    storage: &JsonStorage,
+    targets: &[(PathBuf, Collection)],
+    rx: Receiver<notify::Result<Event>>,
+) {
+    // The channel closes once the DataWatcher is dropped
+    while let Ok(first) = rx.recv() {
+        let mut changed = HashSet::new();
+        collect(first, targets, &mut changed);
+
+        // Let the writer finish, then fold in whatever arrived meanwhile
+        thread::sleep(SETTLE_DELAY);
+        while let Ok(event) = rx.try_recv() {
+            collect(event, targets, &mut changed);
+        }
+
+        for (path, collection) in targets {
+            if changed.contains(collection) {
+                reload_collection(storage, path, *collection);
+            }
+        }
+    }
+    log::debug!("Data watcher stopped");
+}
+
+fn collect(
+    event: notify::Result<Event>,
+    targets: &[(PathBuf, Collection)],
+    changed: &mut HashSet<Collection>,
+) {
+    let event = match event {
+        Ok(event) => event,
+        Err(e) => {
+            log::warn!("Data watcher error: {}", e);
+            return;
+        }
+    };
+    if event.kind.is_access() {
+        return;
+    }
+    for path in &event.paths {
+        let Ok(path) = normalize(&path.to_string_lossy()) else {
+            continue;
+        };
+        if let Some((_, collection)) = targets.iter().find(|(target, _)| *target == path) {
+            changed.insert(*collection);
+        }
+    }
+}
+
+fn reload_collection(storage: &JsonStorage, path: &Path, collection: Collection) {
+    match collection {
+        Collection::MenuItems => reload(path, &storage.menu_items, schema::MENU_ITEMS, |items| {
+            check_references(storage, Some(items), None, None, None, None)
+        }),
+        Collection::Notices => reload(path, &storage.notices, schema::NOTICES, |_| Ok(())),
+        Collection::AdminUsers => {
+            reload(path, &storage.admin_users, schema::ADMIN_USERS, |_| Ok(()))
+        }
+        Collection::MenuPresets => reload(
+            path,
+            &storage.menu_presets,
+            schema::MENU_PRESETS,
+            |presets| check_references(storage, None, Some(presets), None, None, None),
+        ),
+        Collection::MenuSchedules => reload(
+            path,
+            &storage.menu_schedules,
+            schema::MENU_SCHEDULES,
+            |schedules| check_references(storage, None, None, Some(schedules), None, None),
+        ),
+        Collection::MenuCategories => reload(
+            path,
+            &storage.menu_categories,
+            schema::MENU_CATEGORIES,
+            |categories| check_references(storage, None, None, None, Some(categories), None),
+        ),
+        Collection::Ingredients => reload(
+            path,
+            &storage.ingredients,
+            schema::INGREDIENTS,
+            |ingredients| check_references(storage, None, None, None, None, Some(ingredients)),
+        ),
+        Collection::Feedback => reload(path, &storage.feedback, schema::FEEDBACK, |_| Ok(())),
+    }
+}
+
+/// Everything in a collection, trashed records included, as written to disk
+fn snapshot<T: Clone>(records: &Mutex<Vec<T>>) -> Result<Vec<T>, StorageError> {
+    Ok(records
+        .lock()
+        .map_err(|_| StorageError::PoisonError)?
+        .clone())
+}
+
+/// Swap in the file's records if they parse and pass `check`, unless they
+/// only echo what is in memory
+fn reload<T: Serialize + DeserializeOwned + Clone>(
+    path: &Path,
+    records: &Mutex<Vec<T>>,
+    migrations: &[Migration],
+    check: impl FnOnce(&[T]) -> Result<(), StorageError>,
+) {
+    // A removed file is almost always mid-save; reading it as an empty
+    // collection would wipe the data in memory
+    let on_disk = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            log::warn!("Ignoring change to {:?}: {}", path, e);
+            return;
+        }
+    };
+
+    // Our own saves trigger events too; skip them when nothing differs
+    if let Ok(current) = snapshot(records)
+        && let Ok(in_memory) = schema::encode(&current, migrations)
+        && in_memory == on_disk
+    {
+        return;
+    }
+
+    let result = schema::decode::<T>(&on_disk, migrations).and_then(|reloaded| {
+        check(&reloaded)?;
+        *records.lock().map_err(|_| StorageError::PoisonError)? = reloaded;
+        Ok(())
+    });
+    match result {
+        Ok(()) => log::info!("Reloaded {:?} after it changed on disk", path),
+        Err(e) => log::error!(
+            "Rejected change to {:?}: {}. Keeping the data already loaded",
+            path,
+            e
+        ),
+    }
+}
+
+/// Refuse a reload that leaves live records referring to records that are
+/// missing or in the trash. References that were already broken before the
+/// change are left for the integrity report rather than blocking every reload.
+fn check_references(
+    storage: &JsonStorage,
+    items: Option<&[MenuItem]>,
+    presets: Option<&[MenuPreset]>,
+    schedules: Option<&[MenuSchedule]>,
+    categories: Option<&[MenuCategory]>,
+    ingredients: Option<&[Ingredient]>,
+) -> Result<(), StorageError> {
+    let current_items = snapshot(&storage.menu_items)?;
+    let current_presets = snapshot(&storage.menu_presets)?;
+    let current_schedules = snapshot(&storage.menu_schedules)?;
+    let current_categories = snapshot(&storage.menu_categories)?;
+    let current_ingredients = snapshot(&storage.ingredients)?;
+
+    let before = dangling(
+        &current_items,
+        &current_presets,
+        &current_schedules,
+        &current_categories,
+        &current_ingredients,
+    );
+    let after = dangling(
+        items.unwrap_or(&current_items),
+        presets.unwrap_or(&current_presets),
+        schedules.unwrap_or(&current_schedules),
+        categories.unwrap_or(&current_categories),
+        ingredients.unwrap_or(&current_ingredients),
+    );
+
+    let introduced = after.iter().find(|reference| {
+        !before.iter().any(|existing| {
+            existing.id == reference.id
+                && existing.field == reference.field
+                && existing.target_id == reference.target_id
+        })
+    });
+    match introduced {
+        None => Ok(()),
+        Some(reference) if reference.field == "recipe" => Err(missing_reference(
+            "Menu item",
+            &reference.name,
+            "ingredient",
+            reference.target_id,
+        )),
+        Some(reference) if reference.collection == "menu_items" => Err(missing_reference(
+            "Menu item",
+            &reference.name,
+            "menu category",
+            reference.target_id,
+        )),
+        Some(reference) if reference.collection == "menu_presets" => Err(missing_reference(
+            "Menu preset",
+            &reference.name,
+            "menu item",
+            reference.target_id,
+        )),
+        Some(reference) => Err(missing_reference(
+            "Menu schedule",
+            &reference.name,
+            "menu preset",
+            reference.target_id,
+        )),
+    }
+}
+
+/// Dangling references among whole collections, trashed records included
+fn dangling(
+    items: &[MenuItem],
+    presets: &[MenuPreset],
+    schedules: &[MenuSchedule],
+    categories: &[MenuCategory],
+    ingredients: &[Ingredient],
+) -> Vec<DanglingReference> {
+    let trash = Trash {
+        menu_items: trashed(items),
+        notices: Vec::new(),
+        menu_presets: trashed(presets),
+        menu_schedules: trashed(schedules),
+        menu_categories: trashed(categories),
+        ingredients: trashed(ingredients),
+        feedback: Vec::new(),
+    };
+    find_dangling(
+        &live(items),
+        &live(presets),
+        &live(schedules),
+        &live(categories),
+        &live(ingredients),
+        &trash,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use notify::EventKind;
+    use notify::event::{DataChange, ModifyKind};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::storage::{Prices, Storage, Translations, category};
+
+    fn storage() -> (Arc<JsonStorage>, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("watch-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        let storage = JsonStorage::new(
+            &path("menu_items.json"),
+            &path("notices.json"),
+            &path("admin_users.json"),
+            &path("menu_presets.json"),
+            &path("menu_schedules.json"),
+            &path("menu_categories.json"),
+            &path("ingredients.json"),
+            &path("feedback.json"),
+        )
+        .unwrap();
+        (Arc::new(storage), dir)
+    }
+
+    fn item(name: &str) -> MenuItem {
+        MenuItem {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            category_id: category::legacy_id("Mains").unwrap(),
+            description: String::new(),
+            allergens: Vec::new(),
+            is_available: true,
+            revision: 0,
+            trashed_at: None,
+            may_contain: Vec::new(),
+            unmapped_allergens: Vec::new(),
+            dietary_tags: Vec::new(),
+            nutrition: None,
+            prices: Prices::new(),
+            preset_prices: Prices::new(),
+            image: None,
+            recipe: Vec::new(),
+            translations: Translations::new(),
+            stock: None,
+            variants: Vec::new(),
+        }
+    }
+
+    fn write_items(path: &Path, items: &[MenuItem]) {
+        fs::write(path, serde_json::to_string_pretty(items).unwrap()).unwrap();
+    }
+
+    fn modified(path: &Path) -> notify::Result<Event> {
+        Ok(Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Any))).add_path(path.into()))
+    }
+
+    #[test]
+    fn changes_arriving_while_settling_are_reloaded_once() {
+        let (storage, dir) = storage();
+        let path = normalize(dir.join("menu_items.json").to_str().unwrap()).unwrap();
+        let targets = vec![(path.clone(), Collection::MenuItems)];
+        let (tx, rx) = mpsc::channel();
+
+        write_items(&path, &[item("Soup")]);
+        tx.send(modified(&path)).unwrap();
+        let watcher = {
+            let storage = Arc::clone(&storage);
+            thread::spawn(move || run(&storage, &targets, rx))
+        };
+
+        // The editor is still writing when the first event arrives
+        thread::sleep(SETTLE_DELAY / 5);
+        write_items(&path, &[item("Soup"), item("Salad")]);
+        tx.send(modified(&path)).unwrap();
+        drop(tx);
+        watcher.join().unwrap();
+
+        let names: Vec<String> = storage
+            .get_menu_items()
+            .unwrap()
+            .into_iter()
+            .map(|item| item.name)
+            .collect();
+        assert_eq!(names, ["Soup", "Salad"]);
+    }
+
+    #[test]
+    fn bad_json_is_rejected_and_the_loaded_data_kept() {
+        let (storage, dir) = storage();
+        storage.add_menu_item(item("Soup")).unwrap();
+        let path = dir.join("menu_items.json");
+
+        fs::write(&path, "[{ \"name\": ").unwrap();
+        reload_collection(&storage, &path, Collection::MenuItems);
+
+        assert_eq!(storage.get_menu_items().unwrap()[0].name, "Soup");
+    }
+
+    #[test]
+    fn missing_file_keeps_the_loaded_data() {
+        let (storage, dir) = storage();
+        storage.add_menu_item(item("Soup")).unwrap();
+        let path = dir.join("menu_items.json");
+
+        fs::remove_file(&path).unwrap();
+        reload_collection(&storage, &path, Collection::MenuItems);
+
+        assert_eq!(storage.get_menu_items().unwrap().len(), 1);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn change_that_breaks_a_preset_is_rejected() {
+        let (storage, dir) = storage();
+        let soup = item("Soup");
+        storage.add_menu_item(soup.clone()).unwrap();
+        let now = chrono::Utc::now();
+        storage
+            .add_menu_preset(MenuPreset {
+                id: Uuid::new_v4(),
+                name: "Lunch".to_string(),
+                description: String::new(),
+                menu_item_ids: vec![soup.id],
+                created_at: now,
+                updated_at: now,
+                revision: 0,
+                trashed_at: None,
+                price_overrides: BTreeMap::new(),
+                par_levels: BTreeMap::new(),
+            })
+            .unwrap();
+        let path = dir.join("menu_items.json");
+
+        // Someone edits the file by hand and drops the soup
+        write_items(&path, &[item("Salad")]);
+        reload_collection(&storage, &path, Collection::MenuItems);
+
+        let names: Vec<String> = storage
+            .get_menu_items()
+            .unwrap()
+            .into_iter()
+            .map(|item| item.name)
+            .collect();
+        assert_eq!(names, ["Soup"]);
+    }
+
+    #[test]
+    fn change_that_files_an_item_under_a_missing_category_is_rejected() {
+        let (storage, dir) = storage();
+        let path = dir.join("menu_items.json");
+        let mut salad = item("Salad");
+        salad.category_id = Uuid::new_v4();
+
+        write_items(&path, &[salad]);
+        reload_collection(&storage, &path, Collection::MenuItems);
+
+        assert!(storage.get_menu_items().unwrap().is_empty());
+    }
+}